@@ -1,18 +1,24 @@
-use std::f32::consts::PI;
+use std::{f32::consts::PI, process};
 
 use app::{
     camera::{OrbitCamera, OrbitCameraPlugin},
     capture_point::ClientCapturePointPlugin,
+    console::ConsolePlugin,
     controls::ControlsPlugin,
-    debug::fps::{fps_gui, team_swap_gui},
+    debug::{
+        fps::fps_gui,
+        network::{network_conditions_gui, network_stats_gui, sample_network_stats, NetworkStats},
+    },
     game_state::ClientGameState,
+    interpolation::InterpolationPlugin,
     player::ClientPlayerPlugin,
     skybox::cubemap::CubemapPlugin,
     ui::GameUIPlugin,
-    utils::{handle_ship_model_load, handle_turret_model_load, lerp_transform_targets},
+    utils::{handle_ship_model_load, handle_turret_model_load},
 };
 use bevy::{
     app::App,
+    asset::AssetPlugin,
     core_pipeline::{bloom::BloomSettings, fxaa::Fxaa},
     diagnostic::FrameTimeDiagnosticsPlugin,
     math::vec3,
@@ -32,73 +38,106 @@ use bevy_mod_gizmos::GizmosPlugin;
 use bevy_rapier3d::prelude::{NoUserData, RapierDebugRenderPlugin, RapierPhysicsPlugin};
 use bevy_scene_hook::HookPlugin;
 use spaaaace_shared::{
-    asteroid::AsteroidPlugin, weapons::WeaponsPlugin, Lobby, NetworkContext, ServerMessages,
+    asteroid::AsteroidPlugin, replication::ReplicationPlugin, ships::ShipsPlugin,
+    weapons::WeaponsPlugin, Lobby, NetworkContext, ServerMessages,
 };
 
-use crate::networking::ClientNetworkingPlugin;
+use crate::{
+    browser::ServerBrowserPlugin, config::ClientSettings, networking::ClientNetworkingPlugin,
+    replay::ReplayPlugin,
+};
 
 pub fn run() {
-    App::default()
-        .add_plugins(DefaultPlugins.set(WindowPlugin {
-            window: WindowDescriptor {
-                title: "Spaaace Client".to_string(),
-                width: 1280.,
-                height: 640.,
+    let settings = ClientSettings::load().unwrap_or_else(|error| {
+        eprintln!("{}", error);
+        process::exit(1);
+    });
+
+    let mut app = App::default();
+    app.add_plugins(
+        DefaultPlugins
+            .set(WindowPlugin {
+                window: WindowDescriptor {
+                    title: "Spaaace Client".to_string(),
+                    width: 1280.,
+                    height: 640.,
+                    ..default()
+                },
                 ..default()
-            },
-            ..default()
-        }))
-        .add_startup_system(init)
-        // ------------------
-        // Effects
-        // ------------------
-        .add_plugin(HanabiPlugin)
-        // ------------------
-        // Third party
-        // ------------------
-        .add_plugin(RapierPhysicsPlugin::<NoUserData>::default())
-        .add_plugin(RapierDebugRenderPlugin::default())
-        // ------------------
-        // Utils
-        // ------------------
-        .add_plugin(HookPlugin)
-        .add_system(lerp_transform_targets)
-        .add_system(handle_ship_model_load)
-        .add_system(handle_turret_model_load)
-        .add_plugin(OrbitCameraPlugin)
-        // ------------------
-        // UI Stuff
-        // ------------------
-        .insert_resource(ClientGameState {
-            is_paused: false,
-            is_focused: true,
-        })
-        .add_plugin(GameUIPlugin)
-        // ------------------
-        // Gameplay stuff
-        // ------------------
-        .insert_resource(NetworkContext { is_server: false })
-        .add_plugin(ClientNetworkingPlugin)
-        .insert_resource(Lobby::default())
-        .add_plugin(ClientPlayerPlugin {})
-        .add_plugin(ControlsPlugin {})
-        .add_plugin(WeaponsPlugin {})
-        .add_plugin(ClientCapturePointPlugin {})
-        .add_plugin(AsteroidPlugin)
-        .add_event::<ServerMessages>()
-        // ------------------
-        // Debug
-        // ------------------
-        //.add_plugin(FrameTimeDiagnosticsPlugin::default())
-        .add_plugin(WorldInspectorPlugin)
-        .add_plugin(FrameTimeDiagnosticsPlugin::default())
-        // .add_plugin(EguiPlugin)
-        .add_plugin(GizmosPlugin)
-        .add_plugin(CubemapPlugin)
-        .insert_resource(ClearColor(Color::rgb(0.01, 0.01, 0.01))) // Used by guis
-        .add_system(fps_gui)
-        .add_system(team_swap_gui)
-        .run();
+            })
+            // Ship definitions are reloaded when they change
+            .set(AssetPlugin {
+                watch_for_changes: true,
+                ..default()
+            }),
+    )
+    .add_startup_system(init)
+    // ------------------
+    // Effects
+    // ------------------
+    .add_plugin(HanabiPlugin)
+    // ------------------
+    // Third party
+    // ------------------
+    .add_plugin(RapierPhysicsPlugin::<NoUserData>::default())
+    .add_plugin(RapierDebugRenderPlugin::default())
+    // ------------------
+    // Utils
+    // ------------------
+    .add_plugin(HookPlugin)
+    .add_plugin(InterpolationPlugin {
+        settings: settings.interpolation,
+    })
+    .add_system(handle_ship_model_load)
+    .add_system(handle_turret_model_load)
+    .add_plugin(OrbitCameraPlugin)
+    // ------------------
+    // UI Stuff
+    // ------------------
+    .insert_resource(ClientGameState {
+        is_paused: false,
+        is_focused: true,
+        is_typing: false,
+    })
+    .add_plugin(GameUIPlugin)
+    .add_plugin(ConsolePlugin)
+    // ------------------
+    // Gameplay stuff
+    // ------------------
+    .insert_resource(NetworkContext { is_server: false })
+    .insert_resource(Lobby::default())
+    .add_plugin(ReplicationPlugin)
+    .add_plugin(ClientPlayerPlugin {})
+    .add_plugin(ControlsPlugin {})
+    .add_plugin(WeaponsPlugin {})
+    .add_plugin(ClientCapturePointPlugin {})
+    .add_plugin(AsteroidPlugin)
+    .add_plugin(ShipsPlugin)
+    .add_event::<ServerMessages>()
+    // ------------------
+    // Debug
+    // ------------------
+    //.add_plugin(FrameTimeDiagnosticsPlugin::default())
+    .add_plugin(WorldInspectorPlugin)
+    .add_plugin(FrameTimeDiagnosticsPlugin::default())
+    // .add_plugin(EguiPlugin)
+    .add_plugin(GizmosPlugin)
+    .add_plugin(CubemapPlugin)
+    .insert_resource(ClearColor(Color::rgb(0.01, 0.01, 0.01))) // Used by guis
+    .add_system(fps_gui)
+    .add_system(network_conditions_gui)
+    .init_resource::<NetworkStats>()
+    .add_system(sample_network_stats)
+    .add_system(network_stats_gui);
+
+    match settings.replay.clone() {
+        Some(path) => app.add_plugin(ReplayPlugin { path }),
+        None => app
+            .add_plugin(ClientNetworkingPlugin { settings })
+            .add_plugin(ServerBrowserPlugin),
+    };
+
+    app.run();
 }
 
 fn init(mut commands: Commands, mut ambient_light: ResMut<AmbientLight>) {
@@ -0,0 +1,214 @@
+use std::net::{Ipv4Addr, SocketAddr, UdpSocket};
+
+use app::game_state::ConnectionStatus;
+use bevy::{
+    app::App,
+    ecs::schedule::ShouldRun,
+    prelude::{Commands, IntoSystemDescriptor, Plugin, Res, ResMut, Resource},
+    time::Time,
+};
+use bevy_egui::{
+    egui::{Color32, Grid, Window},
+    EguiContext,
+};
+use spaaaace_shared::{
+    discovery::{DiscoveryPacket, ServerInfo, DISCOVERY_PORT},
+    handshake::PROTOCOL_VERSION,
+};
+
+use crate::{config::ClientSettings, networking::connect};
+
+/// Seconds between two discovery broadcasts.
+const QUERY_INTERVAL: f64 = 2.0;
+/// Servers that stopped answering for this long are taken off the list.
+const SERVER_TIMEOUT: f64 = 5.0;
+/// Larger than any reply, server names are short.
+const MAX_REPLY_BYTES: usize = 1024;
+
+/// Lists the servers on the LAN until one is picked. Only shown when no server was
+/// given in the settings.
+pub struct ServerBrowserPlugin;
+
+impl Plugin for ServerBrowserPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(ServerBrowser::new())
+            .add_system(discover_servers.with_run_criteria(run_if_browsing))
+            .add_system(server_browser_gui.with_run_criteria(run_if_browsing));
+    }
+}
+
+struct DiscoveredServer {
+    addr: SocketAddr,
+    info: ServerInfo,
+    /// Round trip time of the latest query, in seconds.
+    ping: f64,
+    last_seen: f64,
+}
+
+#[derive(Resource)]
+struct ServerBrowser {
+    /// `None` if it could not be opened, servers can still be entered by hand.
+    socket: Option<UdpSocket>,
+    servers: Vec<DiscoveredServer>,
+    last_query: Option<f64>,
+    /// Contents of the address field.
+    address: String,
+    /// Why the typed address could not be connected to.
+    error: Option<String>,
+}
+
+impl ServerBrowser {
+    fn new() -> Self {
+        let socket = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0)).and_then(|socket| {
+            socket.set_broadcast(true)?;
+            socket.set_nonblocking(true)?;
+            Ok(socket)
+        });
+        let socket = match socket {
+            Ok(socket) => Some(socket),
+            Err(error) => {
+                println!("Could not look for servers on the LAN: {}", error);
+                None
+            }
+        };
+
+        Self {
+            socket,
+            servers: Vec::new(),
+            last_query: None,
+            address: String::new(),
+            error: None,
+        }
+    }
+}
+
+fn run_if_browsing(status: Res<ConnectionStatus>) -> ShouldRun {
+    match *status {
+        ConnectionStatus::Browsing => ShouldRun::Yes,
+        _ => ShouldRun::No,
+    }
+}
+
+fn discover_servers(mut browser: ResMut<ServerBrowser>, time: Res<Time>) {
+    let browser = &mut *browser;
+    let socket = match &browser.socket {
+        Some(socket) => socket,
+        None => return,
+    };
+    let now = time.elapsed_seconds_f64();
+
+    if browser
+        .last_query
+        .map_or(true, |last_query| now - last_query >= QUERY_INTERVAL)
+    {
+        browser.last_query = Some(now);
+        let query = DiscoveryPacket::Query { sent_at: now }.encode();
+        if let Err(error) = socket.send_to(&query, (Ipv4Addr::BROADCAST, DISCOVERY_PORT)) {
+            println!("Could not look for servers on the LAN: {}", error);
+        }
+    }
+
+    let mut buffer = [0; MAX_REPLY_BYTES];
+    while let Ok((len, sender)) = socket.recv_from(&mut buffer) {
+        let (sent_at, info) = match DiscoveryPacket::decode(&buffer[..len]) {
+            Some(DiscoveryPacket::Reply { sent_at, info }) => (sent_at, info),
+            _ => continue,
+        };
+
+        // Replies come from the discovery port, the game runs on the one they name.
+        let addr = SocketAddr::new(sender.ip(), info.port);
+        let ping = now - sent_at;
+        match browser
+            .servers
+            .iter_mut()
+            .find(|server| server.addr == addr)
+        {
+            Some(server) => {
+                server.info = info;
+                server.ping = ping;
+                server.last_seen = now;
+            }
+            None => browser.servers.push(DiscoveredServer {
+                addr,
+                info,
+                ping,
+                last_seen: now,
+            }),
+        }
+    }
+
+    browser
+        .servers
+        .retain(|server| now - server.last_seen < SERVER_TIMEOUT);
+}
+
+fn server_browser_gui(
+    mut commands: Commands,
+    mut egui_context: ResMut<EguiContext>,
+    mut browser: ResMut<ServerBrowser>,
+    mut settings: ResMut<ClientSettings>,
+    mut status: ResMut<ConnectionStatus>,
+) {
+    let browser = &mut *browser;
+    let mut join = None;
+
+    Window::new("Servers").show(egui_context.ctx_mut(), |ui| {
+        if browser.servers.is_empty() {
+            ui.label("Looking for servers on the LAN...");
+        } else {
+            Grid::new("servers").striped(true).show(ui, |ui| {
+                ui.label("Name");
+                ui.label("Map");
+                ui.label("Players");
+                ui.label("Ping");
+                ui.end_row();
+
+                for server in browser.servers.iter() {
+                    ui.label(&server.info.name);
+                    ui.label(&server.info.map);
+                    ui.label(format!(
+                        "{}/{}",
+                        server.info.players, server.info.max_players
+                    ));
+                    ui.label(format!("{:.0} ms", server.ping * 1000.0));
+                    // The server would reject us anyway.
+                    if server.info.protocol_version != PROTOCOL_VERSION {
+                        ui.label(format!("Version {}", server.info.protocol_version));
+                    } else if ui.button("Join").clicked() {
+                        join = Some(server.addr.to_string());
+                    }
+                    ui.end_row();
+                }
+            });
+        }
+
+        ui.separator();
+        ui.horizontal(|ui| {
+            ui.label("Address");
+            ui.text_edit_singleline(&mut browser.address);
+            if ui.button("Connect").clicked() {
+                join = Some(browser.address.trim().to_string());
+            }
+        });
+        if let Some(error) = &browser.error {
+            ui.colored_label(Color32::RED, error);
+        }
+    });
+
+    let server = match join {
+        Some(server) => server,
+        None => return,
+    };
+    settings.server = Some(server);
+    match settings.server_addr() {
+        Ok(_) => {
+            println!("Connecting to {}.", settings.server.as_ref().unwrap());
+            connect(&mut commands, &settings);
+            *status = ConnectionStatus::Connecting;
+        }
+        Err(error) => {
+            browser.error = Some(error);
+            settings.server = None;
+        }
+    }
+}
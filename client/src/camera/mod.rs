@@ -1,15 +1,22 @@
 use std::f32::consts::PI;
 
 use bevy::{
+    ecs::schedule::ShouldRun,
     input::mouse::{MouseMotion, MouseWheel},
     prelude::{
-        App, Component, EventReader, Plugin, Quat, Query, Res, SystemSet, Transform, Vec2, Vec3,
-        With, Without,
+        App, Commands, Component, Entity, EventReader, Input, IntoSystemDescriptor, KeyCode,
+        Plugin, Quat, Query, Res, ResMut, Resource, SystemSet, Transform, Vec2, Vec3, With,
+        Without,
     },
+    time::Time,
     window::Windows,
 };
+use spaaaace_shared::Lobby;
 
-use crate::game_state::run_if_not_paused;
+use crate::game_state::{run_if_not_paused, ClientGameState};
+
+/// Units per second the free camera flies at.
+const FREE_CAMERA_SPEED: f32 = 60.0;
 
 pub struct OrbitCameraPlugin;
 
@@ -19,10 +26,42 @@ impl Plugin for OrbitCameraPlugin {
             SystemSet::new()
                 .with_system(camera_follow_local_player)
                 .with_run_criteria(run_if_not_paused),
+        )
+        .add_system(cycle_spectator_target.with_run_criteria(run_if_spectating))
+        .add_system_set(
+            SystemSet::new()
+                .with_system(free_camera)
+                .with_run_criteria(run_if_spectating_not_paused),
         );
     }
 }
 
+/// Present when there is no local ship, while spectating or watching a replay.
+#[derive(Resource, Default)]
+pub struct Spectating {
+    /// The player the camera orbits, the camera flies freely while `None`.
+    pub following: Option<u64>,
+}
+
+fn run_if_spectating(spectating: Option<Res<Spectating>>) -> ShouldRun {
+    if spectating.is_some() {
+        ShouldRun::Yes
+    } else {
+        ShouldRun::No
+    }
+}
+
+fn run_if_spectating_not_paused(
+    spectating: Option<Res<Spectating>>,
+    game_state: Res<ClientGameState>,
+) -> ShouldRun {
+    if spectating.is_some() && !game_state.is_paused && !game_state.is_typing {
+        ShouldRun::Yes
+    } else {
+        ShouldRun::No
+    }
+}
+
 #[derive(Component)]
 pub struct OrbitCameraTarget;
 
@@ -49,24 +88,105 @@ fn camera_follow_local_player(
 
     for (mut transform, mut orbit_camera) in camera_query.iter_mut() {
         orbit_camera.zoom += scroll_zoom;
-        match local_player_query.get_single() {
-            Ok(local_player_transform) => {
-                if rotation_move.length_squared() > 0.0 {
-                    let window = get_primary_window_size(&windows);
-                    let delta = (rotation_move / window) / PI;
-                    let yaw = Quat::from_rotation_y(-delta.x);
-                    let pitch = Quat::from_rotation_x(-delta.y);
-                    transform.rotation = yaw * transform.rotation; // rotate around global y axis
-                    transform.rotation = transform.rotation * pitch; // rotate around local x axis
-                }
-                transform.translation = local_player_transform.translation
-                    + orbit_camera.offset
-                    + transform.back() * orbit_camera.zoom;
-                // transform.rotation *= Rot
+        if let Ok(local_player_transform) = local_player_query.get_single() {
+            if rotation_move.length_squared() > 0.0 {
+                let window = get_primary_window_size(&windows);
+                let delta = (rotation_move / window) / PI;
+                let yaw = Quat::from_rotation_y(-delta.x);
+                let pitch = Quat::from_rotation_x(-delta.y);
+                transform.rotation = yaw * transform.rotation; // rotate around global y axis
+                transform.rotation = transform.rotation * pitch; // rotate around local x axis
             }
-            Err(_) => {}
+            transform.translation = local_player_transform.translation
+                + orbit_camera.offset
+                + transform.back() * orbit_camera.zoom;
+            // transform.rotation *= Rot
+        }
+    }
+}
+
+/// Tab follows the next player, after the last one the camera flies freely again.
+fn cycle_spectator_target(
+    mut commands: Commands,
+    mut spectating: ResMut<Spectating>,
+    keys: Res<Input<KeyCode>>,
+    lobby: Res<Lobby>,
+    target_query: Query<Entity, With<OrbitCameraTarget>>,
+    game_state: Res<ClientGameState>,
+) {
+    let mut following = spectating.following;
+    if following.map_or(false, |id| !lobby.players.contains_key(&id)) {
+        following = None;
+    }
+
+    if keys.just_pressed(KeyCode::Tab) && !game_state.is_typing {
+        let mut ids: Vec<u64> = lobby.players.keys().copied().collect();
+        ids.sort();
+        following = match following {
+            Some(current) => ids.into_iter().find(|id| *id > current),
+            None => ids.first().copied(),
+        };
+    }
+
+    spectating.following = following;
+
+    // Checked every frame, the followed ship is respawned when a replay seeks.
+    let target = following.and_then(|id| lobby.players.get(&id).copied());
+    for entity in target_query.iter() {
+        if Some(entity) != target {
+            commands.entity(entity).remove::<OrbitCameraTarget>();
+        }
+    }
+    if let Some(entity) = target {
+        if !target_query.contains(entity) {
+            commands.entity(entity).insert(OrbitCameraTarget);
+        }
+    }
+}
+
+/// WASD to fly, space and shift to rise and sink, the mouse to look around.
+fn free_camera(
+    mut camera_query: Query<&mut Transform, With<OrbitCamera>>,
+    target_query: Query<(), With<OrbitCameraTarget>>,
+    mut motion_evr: EventReader<MouseMotion>,
+    keys: Res<Input<KeyCode>>,
+    windows: Res<Windows>,
+    time: Res<Time>,
+) {
+    let rotation_move = motion_evr.iter().map(|ev| ev.delta * 10.0).sum::<Vec2>();
+    if !target_query.is_empty() {
+        return;
+    }
+
+    let mut transform = match camera_query.get_single_mut() {
+        Ok(transform) => transform,
+        Err(_) => return,
+    };
+
+    if rotation_move.length_squared() > 0.0 {
+        let window = get_primary_window_size(&windows);
+        let delta = (rotation_move / window) / PI;
+        let yaw = Quat::from_rotation_y(-delta.x);
+        let pitch = Quat::from_rotation_x(-delta.y);
+        transform.rotation = yaw * transform.rotation;
+        transform.rotation = transform.rotation * pitch;
+    }
+
+    let mut direction = Vec3::ZERO;
+    for (key, key_direction) in [
+        (KeyCode::W, transform.forward()),
+        (KeyCode::S, transform.back()),
+        (KeyCode::A, transform.left()),
+        (KeyCode::D, transform.right()),
+        (KeyCode::Space, Vec3::Y),
+        (KeyCode::LShift, Vec3::NEG_Y),
+    ] {
+        if keys.pressed(key) {
+            direction += key_direction;
         }
     }
+    transform.translation +=
+        direction.normalize_or_zero() * FREE_CAMERA_SPEED * time.delta_seconds();
 }
 
 fn get_primary_window_size(windows: &Res<Windows>) -> Vec2 {
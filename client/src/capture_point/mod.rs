@@ -1,125 +1,83 @@
+#[allow(clippy::module_inception)]
 mod capture_point;
 
 use bevy::{
     pbr::NotShadowCaster,
     prelude::{
-        shape, App, AssetServer, Assets, Color, Commands, EventReader, Handle, MaterialMeshBundle,
-        MaterialPlugin, Mesh, Plugin, Query, Res, ResMut, Transform,
+        shape, Added, App, AssetServer, Assets, Changed, Color, Commands, Entity, Handle,
+        MaterialPlugin, Mesh, Plugin, Query, Res, ResMut,
     },
     time::Time,
-    utils::default,
 };
 
-use spaaaace_shared::{team::team_enum::Team, Lobby, ServerMessages};
+use spaaaace_shared::{
+    capture_point::{CapturePoint, CapturePointPlugin},
+    team::team_enum::Team,
+};
 
 use self::capture_point::ForceFieldMaterial;
 
 pub struct ClientCapturePointPlugin;
 impl Plugin for ClientCapturePointPlugin {
     fn build(&self, app: &mut App) {
-        app.add_system(on_capture_point_spawned)
+        app.add_plugin(CapturePointPlugin)
+            .add_system(on_capture_point_spawned)
             .add_system(on_capture_point_updated)
             .add_plugin(MaterialPlugin::<ForceFieldMaterial>::default());
     }
 }
 
+fn team_color(team: &Team) -> Color {
+    match team {
+        Team::Neutral => Color::WHITE,
+        Team::Red => Color::RED,
+        Team::Blue => Color::BLUE,
+    }
+}
+
 fn on_capture_point_spawned(
     mut commands: Commands,
-    mut lobby: ResMut<Lobby>,
-    mut event_reader: EventReader<ServerMessages>,
+    query: Query<(Entity, &CapturePoint), Added<CapturePoint>>,
     mut meshes: ResMut<Assets<Mesh>>,
     mut force_field_materials: ResMut<Assets<ForceFieldMaterial>>,
     time: Res<Time>,
     ass: Res<AssetServer>,
 ) {
-    for event in event_reader.iter() {
-        match event {
-            ServerMessages::CapturePointSpawned {
-                position,
-                rotation,
-                id,
-                owner,
-                progress: _,
-            } => {
-                let capture_entity = commands
-                    .spawn(MaterialMeshBundle {
-                        mesh: meshes.add(
-                            shape::Icosphere {
-                                radius: 50.,
-                                subdivisions: 8,
-                            }
-                            .into(),
-                        ),
-                        material: force_field_materials.add(ForceFieldMaterial {
-                            color: match owner {
-                                Team::Neutral => Color::WHITE,
-                                Team::Red => Color::RED,
-                                Team::Blue => Color::BLUE,
-                            },
-                            prev_color: Color::WHITE,
-                            last_color_change: time.elapsed_seconds(),
-                            color_texture: Some(ass.load("hex_grid.jpg")),
-                        }),
-                        transform: Transform {
-                            rotation: *rotation,
-                            translation: *position,
-                            ..Default::default()
-                        },
-                        ..default()
-                    })
-                    .insert(NotShadowCaster)
-                    .id();
-
-                lobby.capture_points.insert(*id, capture_entity);
-            }
-            _ => {}
-        }
+    for (entity, capture_point) in query.iter() {
+        commands
+            .entity(entity)
+            .insert(
+                meshes.add(
+                    shape::Icosphere {
+                        radius: capture_point.radius,
+                        subdivisions: 8,
+                    }
+                    .into(),
+                ),
+            )
+            .insert(force_field_materials.add(ForceFieldMaterial {
+                color: team_color(&capture_point.owner),
+                prev_color: Color::WHITE,
+                last_color_change: time.elapsed_seconds(),
+                color_texture: Some(ass.load("hex_grid.jpg")),
+            }))
+            .insert(NotShadowCaster);
     }
 }
 
 fn on_capture_point_updated(
-    mut event_reader: EventReader<ServerMessages>,
     mut force_field_materials: ResMut<Assets<ForceFieldMaterial>>,
-    lobby: ResMut<Lobby>,
     time: Res<Time>,
-    query: Query<&Handle<ForceFieldMaterial>>,
+    query: Query<(&CapturePoint, &Handle<ForceFieldMaterial>), Changed<CapturePoint>>,
 ) {
-    for event in event_reader.iter() {
-        match event {
-            ServerMessages::CapturePointUpdate {
-                id,
-                owner,
-                attacker: _,
-                progress: _,
-            } => {
-                if let Some(entity) = lobby.capture_points.get(&id) {
-                    match query.get(*entity) {
-                        Ok(material) => {
-                            if let Some(material) = force_field_materials.get_mut(material) {
-                                let next_color = match owner {
-                                    Team::Neutral => Color::WHITE,
-                                    Team::Red => Color::RED,
-                                    Team::Blue => Color::BLUE,
-                                };
-                                if material.color != next_color {
-                                    material.prev_color = material.color;
-                                    material.color = next_color;
-                                    material.last_color_change = time.elapsed_seconds();
-
-                                    println!(
-                                        "{:?} {:?} {:?}",
-                                        material.color,
-                                        material.prev_color,
-                                        material.last_color_change
-                                    );
-                                }
-                            }
-                        }
-                        _ => (),
-                    }
-                }
+    for (capture_point, material) in query.iter() {
+        if let Some(material) = force_field_materials.get_mut(material) {
+            let next_color = team_color(&capture_point.owner);
+            if material.color != next_color {
+                material.prev_color = material.color;
+                material.color = next_color;
+                material.last_color_change = time.elapsed_seconds();
             }
-            _ => {}
         }
     }
 }
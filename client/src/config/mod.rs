@@ -0,0 +1,148 @@
+use std::{
+    fs,
+    net::{SocketAddr, ToSocketAddrs},
+    path::{Path, PathBuf},
+};
+
+use app::interpolation::InterpolationSettings;
+use bevy::prelude::Resource;
+use clap::Parser;
+use serde::Deserialize;
+use spaaaace_shared::netsim::{LinkConditionArgs, LinkConditions};
+
+const DEFAULT_CONFIG_PATH: &str = "client.toml";
+
+/// Command line arguments, these override the values from the config file.
+#[derive(Parser, Debug)]
+#[command(about = "Spaaaace client")]
+struct Args {
+    /// TOML file to read the settings from.
+    #[arg(long)]
+    config: Option<PathBuf>,
+    /// Server to connect to, as `host:port`. The server browser opens without one.
+    #[arg(long)]
+    server: Option<String>,
+    #[arg(long)]
+    name: Option<String>,
+    #[arg(long)]
+    ship_type: Option<String>,
+    /// Join without a ship and only watch.
+    #[arg(long)]
+    spectate: bool,
+    /// Connect token issued for the server, required unless it runs in unsecure mode.
+    #[arg(long)]
+    token: Option<PathBuf>,
+    /// Play back a replay file instead of connecting to a server.
+    #[arg(long)]
+    replay: Option<PathBuf>,
+    #[command(flatten)]
+    network_conditions: LinkConditionArgs,
+    /// Seconds other ships are shown in the past, so there are snapshots on both
+    /// sides to interpolate between.
+    #[arg(long)]
+    interpolation_delay: Option<f32>,
+    /// Seconds other ships keep moving once snapshots stop arriving.
+    #[arg(long)]
+    max_extrapolation: Option<f32>,
+}
+
+#[derive(Resource, Deserialize, Debug, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct ClientSettings {
+    /// Picked in the server browser if not set.
+    pub server: Option<String>,
+    pub name: String,
+    pub ship_type: String,
+    pub spectate: bool,
+    pub token: Option<PathBuf>,
+    /// Simulated bad network, for testing.
+    pub network_conditions: Option<LinkConditions>,
+    /// Replay file to play back instead of connecting to a server.
+    pub replay: Option<PathBuf>,
+    pub interpolation: InterpolationSettings,
+}
+
+impl Default for ClientSettings {
+    fn default() -> Self {
+        Self {
+            server: None,
+            name: "Pilot".to_string(),
+            ship_type: "TEST_SHIP".to_string(),
+            spectate: false,
+            token: None,
+            network_conditions: None,
+            replay: None,
+            interpolation: InterpolationSettings::default(),
+        }
+    }
+}
+
+impl ClientSettings {
+    /// Reads the config file and applies the command line arguments on top of it.
+    ///
+    /// A missing `client.toml` is fine, a missing file passed with `--config` is not.
+    pub fn load() -> Result<Self, String> {
+        let args = Args::parse();
+
+        let mut settings = match &args.config {
+            Some(path) => read_config(path)?,
+            None => {
+                let path = Path::new(DEFAULT_CONFIG_PATH);
+                if path.exists() {
+                    read_config(path)?
+                } else {
+                    ClientSettings::default()
+                }
+            }
+        };
+
+        if let Some(server) = args.server {
+            settings.server = Some(server);
+        }
+        if let Some(name) = args.name {
+            settings.name = name;
+        }
+        if let Some(ship_type) = args.ship_type {
+            settings.ship_type = ship_type;
+        }
+        settings.spectate |= args.spectate;
+        if let Some(token) = args.token {
+            settings.token = Some(token);
+        }
+        if let Some(replay) = args.replay {
+            settings.replay = Some(replay);
+        }
+        args.network_conditions
+            .apply(&mut settings.network_conditions);
+
+        if let Some(delay) = args.interpolation_delay {
+            settings.interpolation.delay = delay;
+        }
+        if let Some(max_extrapolation) = args.max_extrapolation {
+            settings.interpolation.max_extrapolation = max_extrapolation;
+        }
+
+        if let Some(conditions) = &settings.network_conditions {
+            conditions.validate()?;
+        }
+        settings.interpolation.validate()?;
+
+        Ok(settings)
+    }
+
+    /// Resolves the server address, which may be a host name on the LAN.
+    pub fn server_addr(&self) -> Result<SocketAddr, String> {
+        let server = self.server.as_deref().ok_or("No server to connect to")?;
+        server
+            .to_socket_addrs()
+            .map_err(|error| format!("Could not resolve {}: {}", server, error))?
+            .next()
+            .ok_or_else(|| format!("No address found for {}", server))
+    }
+}
+
+fn read_config(path: &Path) -> Result<ClientSettings, String> {
+    let contents = fs::read_to_string(path)
+        .map_err(|error| format!("Could not read {}: {}", path.display(), error))?;
+    toml::from_str(&contents).map_err(|error| format!("Invalid {}: {}", path.display(), error))
+}
@@ -0,0 +1,251 @@
+use std::collections::VecDeque;
+
+use bevy::prelude::{App, EventReader, Input, KeyCode, Plugin, Res, ResMut, Resource};
+use bevy_egui::{
+    egui::{
+        text::{CCursor, CCursorRange},
+        Color32, Key, Modifiers, RichText, ScrollArea, TextEdit, TextStyle, TopBottomPanel,
+    },
+    EguiContext,
+};
+use bevy_renet::renet::{DefaultChannel, RenetClient};
+use spaaaace_shared::{
+    command::{complete, find_command, Command, CommandError, COMMANDS},
+    ClientMessages, ServerMessages,
+};
+
+use crate::{debug::network::NetworkStats, game_state::ClientGameState};
+
+/// Lines of output kept, older ones scroll away.
+const MAX_LINES: usize = 200;
+const MAX_HISTORY: usize = 50;
+/// Height of the output above the input line.
+const OUTPUT_HEIGHT: f32 = 240.0;
+
+/// Drop-down console toggled with the key left of 1, for running commands on the
+/// server and reading their replies.
+pub struct ConsolePlugin;
+
+impl Plugin for ConsolePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Console>()
+            .add_system(toggle_console)
+            .add_system(receive_command_output)
+            .add_system(console_gui);
+    }
+}
+
+#[derive(Resource, Default)]
+pub struct Console {
+    pub open: bool,
+    input: String,
+    lines: VecDeque<String>,
+    /// Lines entered before, oldest first.
+    history: Vec<String>,
+    /// Position in `history` while browsing it with the arrow keys.
+    history_index: Option<usize>,
+}
+
+impl Console {
+    pub fn print(&mut self, line: impl Into<String>) {
+        self.lines.push_back(line.into());
+        while self.lines.len() > MAX_LINES {
+            self.lines.pop_front();
+        }
+    }
+
+    /// Completes the command name being typed, as far as it is unambiguous. Returns
+    /// whether the input changed.
+    fn autocomplete(&mut self) -> bool {
+        if self.input.contains(' ') {
+            return false;
+        }
+        let matches: Vec<&str> = complete(&self.input).map(|spec| spec.name).collect();
+        let completed = match matches[..] {
+            [] => return false,
+            [name] => format!("{} ", name),
+            [first, ..] => {
+                let common = matches.iter().fold(first.len(), |common, name| {
+                    first
+                        .bytes()
+                        .zip(name.bytes())
+                        .take(common)
+                        .take_while(|(a, b)| a == b)
+                        .count()
+                });
+                first[..common].to_string()
+            }
+        };
+        if completed == self.input {
+            return false;
+        }
+        self.input = completed;
+        true
+    }
+
+    /// Steps through the history, towards older lines for `back`. Returns whether the
+    /// input changed.
+    fn browse_history(&mut self, back: bool) -> bool {
+        let index = match (self.history_index, back) {
+            (None, true) => self.history.len().checked_sub(1),
+            (None, false) => return false,
+            (Some(index), true) => Some(index.saturating_sub(1)),
+            (Some(index), false) => Some(index + 1).filter(|index| *index < self.history.len()),
+        };
+        self.history_index = index;
+        self.input = match index {
+            Some(index) => self.history[index].clone(),
+            None => String::new(),
+        };
+        true
+    }
+
+    fn remember(&mut self, line: &str) {
+        self.history_index = None;
+        if self.history.last().map(String::as_str) != Some(line) {
+            self.history.push(line.to_string());
+        }
+        if self.history.len() > MAX_HISTORY {
+            self.history.remove(0);
+        }
+    }
+}
+
+fn toggle_console(
+    keys: Res<Input<KeyCode>>,
+    mut console: ResMut<Console>,
+    mut game_state: ResMut<ClientGameState>,
+) {
+    // Not while the key is being typed into the chat.
+    if keys.just_pressed(KeyCode::Grave) && (console.open || !game_state.is_typing) {
+        console.open = !console.open;
+        game_state.is_typing = console.open;
+    }
+}
+
+fn receive_command_output(
+    mut server_message_reader: EventReader<ServerMessages>,
+    mut console: ResMut<Console>,
+) {
+    for message in server_message_reader.iter() {
+        if let ServerMessages::CommandOutput { text } = message {
+            for line in text.lines() {
+                console.print(line);
+            }
+        }
+    }
+}
+
+fn console_gui(
+    mut egui_context: ResMut<EguiContext>,
+    mut console: ResMut<Console>,
+    mut client: Option<ResMut<RenetClient>>,
+    mut stats: ResMut<NetworkStats>,
+) {
+    if !console.open {
+        return;
+    }
+    let console = &mut *console;
+    let mut submitted = None;
+
+    TopBottomPanel::top("console").show(egui_context.ctx_mut(), |ui| {
+        ScrollArea::vertical()
+            .max_height(OUTPUT_HEIGHT)
+            .stick_to_bottom(true)
+            .auto_shrink([false, true])
+            .show(ui, |ui| {
+                for line in console.lines.iter() {
+                    ui.monospace(line);
+                }
+            });
+        ui.separator();
+
+        // Taken before the text field sees them, they would move the focus or cursor.
+        let (tab, up, down) = {
+            let mut input = ui.input_mut();
+            (
+                input.consume_key(Modifiers::NONE, Key::Tab),
+                input.consume_key(Modifiers::NONE, Key::ArrowUp),
+                input.consume_key(Modifiers::NONE, Key::ArrowDown),
+            )
+        };
+        let replaced = (tab && console.autocomplete())
+            || (up && console.browse_history(true))
+            || (down && console.browse_history(false));
+
+        let mut output = TextEdit::singleline(&mut console.input)
+            .desired_width(f32::INFINITY)
+            .font(TextStyle::Monospace)
+            .hint_text("Type help for the commands")
+            .show(ui);
+        // The key that opens the console must not end up in it.
+        console.input.retain(|c| c != '`' && c != '~');
+        if replaced {
+            output
+                .state
+                .set_ccursor_range(Some(CCursorRange::one(CCursor::new(
+                    console.input.chars().count(),
+                ))));
+            output.state.store(ui.ctx(), output.response.id);
+        }
+        if output.response.lost_focus() && ui.input().key_pressed(Key::Enter) {
+            submitted = Some(std::mem::take(&mut console.input));
+        }
+        output.response.request_focus();
+
+        // What the command being typed takes.
+        let name = console.input.split_whitespace().next().unwrap_or("");
+        let hints: Vec<_> = match find_command(name) {
+            Some(spec) if console.input.contains(' ') => vec![spec],
+            _ if name.is_empty() => Vec::new(),
+            _ => complete(name).collect(),
+        };
+        for spec in hints {
+            ui.label(
+                RichText::new(format!("{} {} - {}", spec.name, spec.usage, spec.help))
+                    .monospace()
+                    .color(Color32::GRAY),
+            );
+        }
+    });
+
+    let line = match submitted {
+        Some(line) if !line.trim().is_empty() => line,
+        _ => return,
+    };
+    let parsed = Command::parse(&line);
+    // Passwords stay out of the history and off the screen.
+    match &parsed {
+        Ok(command @ Command::Login { .. }) => console.print(format!("> {}", command)),
+        _ => {
+            console.remember(line.trim());
+            console.print(format!("> {}", line.trim()));
+        }
+    }
+
+    let command = match parsed {
+        Ok(command) => command,
+        Err(CommandError::Empty) => return,
+        Err(error) => {
+            console.print(error.to_string());
+            return;
+        }
+    };
+
+    match client.as_deref_mut() {
+        Some(client) if client.is_connected() => {
+            let message = bincode::serialize(&ClientMessages::Command { command }).unwrap();
+            stats.record_sent(DefaultChannel::Reliable, message.len());
+            client.send_message(DefaultChannel::Reliable, message);
+        }
+        // The server decides who may run what, without one all we can do is list them.
+        _ => match command {
+            Command::Help => {
+                for spec in COMMANDS {
+                    console.print(format!("{} {} - {}", spec.name, spec.usage, spec.help));
+                }
+            }
+            _ => console.print("Not connected to a server."),
+        },
+    }
+}
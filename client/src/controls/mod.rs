@@ -6,7 +6,10 @@ use bevy_mod_gizmos::{draw_gizmo, Gizmo};
 use bevy_rapier3d::prelude::{QueryFilter, RapierContext};
 use spaaaace_shared::{player::player_input::PlayerInput, targeting::Targetable, NetworkedId};
 
-use crate::camera::{OrbitCamera, OrbitCameraTarget};
+use crate::{
+    camera::{OrbitCamera, OrbitCameraTarget},
+    game_state::ClientGameState,
+};
 
 #[derive(Component)]
 pub struct LocalPlayer;
@@ -34,25 +37,28 @@ pub fn player_input(
     k_input: Res<Input<KeyCode>>,
     m_input: Res<Input<MouseButton>>,
     mut player_input: ResMut<PlayerInput>,
+    game_state: Res<ClientGameState>,
 ) {
-    player_input.rotate_left = k_input.pressed(KeyCode::A);
-    player_input.rotate_right = k_input.pressed(KeyCode::D);
-    player_input.thrust_forward = k_input.pressed(KeyCode::W);
-    player_input.thrust_reverse = k_input.pressed(KeyCode::S);
-    player_input.thrust_left = k_input.pressed(KeyCode::Q);
-    player_input.thrust_right = k_input.pressed(KeyCode::E);
-    player_input.thrust_up = k_input.pressed(KeyCode::Space);
-    player_input.thrust_down = k_input.pressed(KeyCode::LControl);
-    player_input.ability_slot_1 = k_input.pressed(KeyCode::Key1);
-    player_input.ability_slot_2 = k_input.pressed(KeyCode::Key2);
-    player_input.ability_slot_3 = k_input.pressed(KeyCode::Key3);
-    player_input.ability_slot_4 = k_input.pressed(KeyCode::Key4);
-    player_input.ability_slot_5 = k_input.pressed(KeyCode::Key5);
-    player_input.ability_slot_6 = k_input.pressed(KeyCode::Key6);
-    player_input.ability_slot_7 = k_input.pressed(KeyCode::Key7);
-    player_input.ability_slot_8 = k_input.pressed(KeyCode::Key8);
-    player_input.ability_slot_9 = k_input.pressed(KeyCode::Key9);
-    player_input.primary_fire = m_input.pressed(MouseButton::Left);
+    // Keys typed into a text field let go of everything.
+    let pressed = |key| !game_state.is_typing && k_input.pressed(key);
+    player_input.rotate_left = pressed(KeyCode::A);
+    player_input.rotate_right = pressed(KeyCode::D);
+    player_input.thrust_forward = pressed(KeyCode::W);
+    player_input.thrust_reverse = pressed(KeyCode::S);
+    player_input.thrust_left = pressed(KeyCode::Q);
+    player_input.thrust_right = pressed(KeyCode::E);
+    player_input.thrust_up = pressed(KeyCode::Space);
+    player_input.thrust_down = pressed(KeyCode::LControl);
+    player_input.ability_slot_1 = pressed(KeyCode::Key1);
+    player_input.ability_slot_2 = pressed(KeyCode::Key2);
+    player_input.ability_slot_3 = pressed(KeyCode::Key3);
+    player_input.ability_slot_4 = pressed(KeyCode::Key4);
+    player_input.ability_slot_5 = pressed(KeyCode::Key5);
+    player_input.ability_slot_6 = pressed(KeyCode::Key6);
+    player_input.ability_slot_7 = pressed(KeyCode::Key7);
+    player_input.ability_slot_8 = pressed(KeyCode::Key8);
+    player_input.ability_slot_9 = pressed(KeyCode::Key9);
+    player_input.primary_fire = !game_state.is_typing && m_input.pressed(MouseButton::Left);
 }
 
 fn aiming(
@@ -61,31 +67,24 @@ fn aiming(
     rapier_context: Res<RapierContext>,
     mut player_input: ResMut<PlayerInput>,
 ) {
-    match camera_query.get_single() {
-        Ok(transform) => {
-            let target_transform_result = camera_target_query.get_single();
-            match target_transform_result {
-                Ok(_) => {
-                    let max_toi = 1000000.0;
-                    let solid = true;
-                    let ray_pos = transform.translation;
-                    let ray_dir = transform.forward();
-                    let filter = QueryFilter::default();
+    if let Ok(transform) = camera_query.get_single() {
+        if camera_target_query.get_single().is_ok() {
+            let max_toi = 1000000.0;
+            let solid = true;
+            let ray_pos = transform.translation;
+            let ray_dir = transform.forward();
+            let filter = QueryFilter::default();
 
-                    let mut hit_point = ray_pos + ray_dir * max_toi;
-                    if let Some((_, toi)) =
-                        rapier_context.cast_ray(ray_pos, ray_dir, max_toi, solid, filter)
-                    {
-                        hit_point = ray_pos + ray_dir * toi;
-                    }
-                    player_input.aim_point = hit_point;
-                }
-                Err(_) => (),
+            let mut hit_point = ray_pos + ray_dir * max_toi;
+            if let Some((_, toi)) =
+                rapier_context.cast_ray(ray_pos, ray_dir, max_toi, solid, filter)
+            {
+                hit_point = ray_pos + ray_dir * toi;
             }
-
-            draw_gizmo(Gizmo::new(player_input.aim_point, 1.0, Color::GREEN));
+            player_input.aim_point = hit_point;
         }
-        Err(_) => {}
+
+        draw_gizmo(Gizmo::new(player_input.aim_point, 1.0, Color::GREEN));
     }
 }
 
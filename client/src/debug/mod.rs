@@ -1 +1,2 @@
 pub mod fps;
+pub mod network;
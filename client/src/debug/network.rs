@@ -0,0 +1,210 @@
+use std::collections::VecDeque;
+
+use bevy::{
+    prelude::{Query, Res, ResMut, Resource},
+    time::Time,
+};
+use bevy_egui::{
+    egui::{
+        plot::{Line, Plot, PlotPoints},
+        Slider, Ui, Window,
+    },
+    EguiContext,
+};
+use bevy_renet::renet::{DefaultChannel, RenetClient};
+use spaaaace_shared::netsim::NetworkConditioner;
+
+use crate::interpolation::SnapshotBuffer;
+
+/// Seconds between two points on the graphs.
+const SAMPLE_INTERVAL: f64 = 0.25;
+/// Points kept per graph, 30 seconds worth.
+const GRAPH_LENGTH: usize = 120;
+
+const CHANNELS: [&str; 3] = ["Reliable", "Unreliable", "Chunk"];
+
+fn channel_index(channel: DefaultChannel) -> usize {
+    match channel {
+        DefaultChannel::Reliable => 0,
+        DefaultChannel::Unreliable => 1,
+        DefaultChannel::Chunk => 2,
+    }
+}
+
+/// The last [`GRAPH_LENGTH`] samples of a value.
+#[derive(Default)]
+pub struct Graph {
+    samples: VecDeque<f64>,
+}
+
+impl Graph {
+    fn push(&mut self, value: f64) {
+        self.samples.push_back(value);
+        while self.samples.len() > GRAPH_LENGTH {
+            self.samples.pop_front();
+        }
+    }
+
+    pub fn latest(&self) -> f64 {
+        self.samples.back().copied().unwrap_or_default()
+    }
+
+    fn show(&self, ui: &mut Ui, id: &str) {
+        let points: PlotPoints = self
+            .samples
+            .iter()
+            .enumerate()
+            .map(|(index, value)| [index as f64 * SAMPLE_INTERVAL, *value])
+            .collect();
+        Plot::new(id)
+            .height(40.0)
+            .show_axes([false, true])
+            .include_y(0.0)
+            .allow_drag(false)
+            .allow_zoom(false)
+            .show(ui, |plot_ui| plot_ui.line(Line::new(points)));
+    }
+}
+
+/// Traffic counted since the last sample.
+#[derive(Default)]
+struct Counters {
+    sent_bytes: [usize; 3],
+    received_bytes: [usize; 3],
+    snapshots: u32,
+}
+
+/// Connection quality and traffic, shown by [`network_stats_gui`].
+#[derive(Resource, Default)]
+pub struct NetworkStats {
+    counters: Counters,
+    last_sample: f64,
+    /// Entities in the newest snapshot.
+    entities_updated: usize,
+    pub rtt: Graph,
+    pub packet_loss: Graph,
+    /// Kilobits per second of messages on each channel.
+    pub sent_kbps: [Graph; 3],
+    pub received_kbps: [Graph; 3],
+    /// Snapshots received per second.
+    pub snapshot_rate: Graph,
+    /// Average samples buffered per interpolated entity.
+    pub buffer_depth: Graph,
+    pub entities: Graph,
+}
+
+impl NetworkStats {
+    pub fn record_sent(&mut self, channel: DefaultChannel, bytes: usize) {
+        self.counters.sent_bytes[channel_index(channel)] += bytes;
+    }
+
+    pub fn record_received(&mut self, channel: DefaultChannel, bytes: usize) {
+        self.counters.received_bytes[channel_index(channel)] += bytes;
+    }
+
+    pub fn record_snapshot(&mut self, entities: usize) {
+        self.counters.snapshots += 1;
+        self.entities_updated = entities;
+    }
+}
+
+fn kbps(bytes: usize) -> f64 {
+    bytes as f64 * 8.0 / 1000.0 / SAMPLE_INTERVAL
+}
+
+pub fn sample_network_stats(
+    time: Res<Time>,
+    client: Option<Res<RenetClient>>,
+    buffer_query: Query<&SnapshotBuffer>,
+    mut stats: ResMut<NetworkStats>,
+) {
+    let now = time.elapsed_seconds_f64();
+    if now - stats.last_sample < SAMPLE_INTERVAL {
+        return;
+    }
+    stats.last_sample = now;
+
+    let counters = std::mem::take(&mut stats.counters);
+    for channel in 0..CHANNELS.len() {
+        stats.sent_kbps[channel].push(kbps(counters.sent_bytes[channel]));
+        stats.received_kbps[channel].push(kbps(counters.received_bytes[channel]));
+    }
+    stats
+        .snapshot_rate
+        .push(counters.snapshots as f64 / SAMPLE_INTERVAL);
+
+    let (buffered, buffers) = buffer_query
+        .iter()
+        .fold((0, 0), |(buffered, buffers), buffer| {
+            (buffered + buffer.len(), buffers + 1)
+        });
+    let depth = if buffers > 0 {
+        buffered as f64 / buffers as f64
+    } else {
+        0.0
+    };
+    stats.buffer_depth.push(depth);
+    let entities = stats.entities_updated as f64;
+    stats.entities.push(entities);
+
+    if let Some(client) = client {
+        let info = client.network_info();
+        stats.rtt.push(info.rtt as f64);
+        stats.packet_loss.push(info.packet_loss as f64 * 100.0);
+    }
+}
+
+pub fn network_stats_gui(mut egui_context: ResMut<EguiContext>, stats: Res<NetworkStats>) {
+    Window::new("Network").show(egui_context.ctx_mut(), |ui| {
+        ui.label(format!("RTT: {:.0} ms", stats.rtt.latest()));
+        stats.rtt.show(ui, "rtt");
+        ui.label(format!("Packet loss: {:.1}%", stats.packet_loss.latest()));
+        stats.packet_loss.show(ui, "packet_loss");
+
+        for (index, name) in CHANNELS.iter().enumerate() {
+            ui.label(format!(
+                "{}: {:.1} kbps sent, {:.1} kbps received",
+                name,
+                stats.sent_kbps[index].latest(),
+                stats.received_kbps[index].latest()
+            ));
+            stats.sent_kbps[index].show(ui, &format!("{}_sent", name));
+            stats.received_kbps[index].show(ui, &format!("{}_received", name));
+        }
+
+        ui.label(format!("Snapshots: {:.0}/s", stats.snapshot_rate.latest()));
+        stats.snapshot_rate.show(ui, "snapshot_rate");
+        ui.label(format!(
+            "Interpolation buffer: {:.1} samples",
+            stats.buffer_depth.latest()
+        ));
+        stats.buffer_depth.show(ui, "buffer_depth");
+        ui.label(format!("Entities updated: {:.0}", stats.entities.latest()));
+        stats.entities.show(ui, "entities");
+    });
+}
+
+/// Tweaks the simulated network conditions, only shown when the client was started
+/// with them.
+pub fn network_conditions_gui(
+    mut egui_context: ResMut<EguiContext>,
+    conditioner: Option<Res<NetworkConditioner>>,
+) {
+    let conditioner = match conditioner {
+        Some(conditioner) => conditioner,
+        None => return,
+    };
+
+    let mut conditions = conditioner.conditions();
+    Window::new("Network Conditions").show(egui_context.ctx_mut(), |ui| {
+        ui.add(Slider::new(&mut conditions.latency_ms, 0..=500).text("Latency (ms)"));
+        ui.add(Slider::new(&mut conditions.jitter_ms, 0..=200).text("Jitter (ms)"));
+        ui.add(Slider::new(&mut conditions.packet_loss, 0.0..=1.0).text("Packet loss"));
+        ui.add(Slider::new(&mut conditions.duplicate, 0.0..=1.0).text("Duplicate"));
+        ui.add(Slider::new(&mut conditions.reorder, 0.0..=1.0).text("Reorder"));
+    });
+
+    if conditions != conditioner.conditions() {
+        conditioner.set_conditions(conditions);
+    }
+}
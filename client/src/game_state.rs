@@ -2,15 +2,38 @@ use bevy::{
     ecs::schedule::ShouldRun,
     prelude::{Res, Resource},
 };
+use spaaaace_shared::handshake::ConnectRejectReason;
 
 #[derive(Resource)]
 pub struct ClientGameState {
     pub is_paused: bool,
     pub is_focused: bool,
+    /// A text field has the keyboard, keys must not steer the ship.
+    pub is_typing: bool,
+}
+
+#[derive(Resource, Debug, Clone, PartialEq, Eq)]
+pub enum ConnectionStatus {
+    /// No server picked yet, the server browser is open.
+    Browsing,
+    Connecting,
+    /// Sent our hello, waiting for the server to accept or reject it.
+    Handshaking,
+    Accepted {
+        player_id: u64,
+    },
+    Rejected {
+        reason: ConnectRejectReason,
+    },
+    Disconnected {
+        reason: String,
+    },
+    /// Playing back a replay file, there is no server.
+    Replaying,
 }
 
 pub fn run_if_not_paused(ctx: Res<ClientGameState>) -> ShouldRun {
-    match ctx.is_paused {
+    match ctx.is_paused || ctx.is_typing {
         true => ShouldRun::No,
         false => ShouldRun::Yes,
     }
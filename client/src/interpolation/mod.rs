@@ -0,0 +1,234 @@
+use std::collections::VecDeque;
+
+use bevy::{
+    prelude::{App, Commands, Component, Plugin, Quat, Query, Res, Resource, Transform, Vec3},
+    time::Time,
+};
+use serde::Deserialize;
+use spaaaace_shared::{Lobby, Snapshot};
+
+/// How far in the past remote entities are rendered and how long they keep moving
+/// once samples stop arriving, in seconds.
+#[derive(Resource, Deserialize, Debug, Clone, Copy)]
+#[serde(default, deny_unknown_fields)]
+pub struct InterpolationSettings {
+    pub delay: f32,
+    pub max_extrapolation: f32,
+}
+
+impl InterpolationSettings {
+    pub fn validate(&self) -> Result<(), String> {
+        for (name, seconds) in [
+            ("delay", self.delay),
+            ("max_extrapolation", self.max_extrapolation),
+        ] {
+            if !seconds.is_finite() || seconds < 0.0 {
+                return Err(format!("interpolation {} must be at least 0", name));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Default for InterpolationSettings {
+    fn default() -> Self {
+        Self {
+            delay: 0.1,
+            max_extrapolation: 0.25,
+        }
+    }
+}
+
+/// Estimate of the server's simulation time on the local clock.
+#[derive(Resource, Default)]
+pub struct ServerClock {
+    offset: Option<f64>,
+}
+
+impl ServerClock {
+    /// Feeds in a sample that was taken at `server_time` and received at `local_time`.
+    pub fn observe(&mut self, server_time: f64, local_time: f64) {
+        let offset = server_time - local_time;
+        self.offset = Some(match self.offset {
+            Some(current) if (offset - current).abs() < 1.0 => current + (offset - current) * 0.1,
+            _ => offset,
+        });
+    }
+
+    /// Jumps straight to `server_time`, for when the server clock is not a real one.
+    pub fn set(&mut self, server_time: f64, local_time: f64) {
+        self.offset = Some(server_time - local_time);
+    }
+
+    pub fn server_time(&self, local_time: f64) -> Option<f64> {
+        self.offset.map(|offset| local_time + offset)
+    }
+}
+
+#[derive(Clone, Copy)]
+struct TransformSample {
+    time: f64,
+    translation: Vec3,
+    rotation: Quat,
+}
+
+/// Timestamped server transforms of a remote entity.
+#[derive(Component, Default)]
+pub struct SnapshotBuffer {
+    samples: VecDeque<TransformSample>,
+}
+
+impl SnapshotBuffer {
+    pub fn push(&mut self, time: f64, translation: Vec3, rotation: Quat) {
+        // Keep the buffer sorted, a late sample is only useful if it fills a gap.
+        let index = self
+            .samples
+            .iter()
+            .rposition(|sample| sample.time < time)
+            .map_or(0, |index| index + 1);
+        if self
+            .samples
+            .get(index)
+            .map_or(false, |sample| sample.time == time)
+        {
+            return;
+        }
+        self.samples.insert(
+            index,
+            TransformSample {
+                time,
+                translation,
+                rotation,
+            },
+        );
+    }
+
+    pub fn len(&self) -> usize {
+        self.samples.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.samples.is_empty()
+    }
+
+    /// Drops samples that can no longer be interpolated from at `time`, keeping the
+    /// newest one before it.
+    fn discard_before(&mut self, time: f64) {
+        while self.samples.len() > 2 && self.samples[1].time <= time {
+            self.samples.pop_front();
+        }
+    }
+
+    /// Transform at `time`, interpolated between the surrounding samples or
+    /// extrapolated from the last two for at most `max_extrapolation` seconds.
+    fn sample(&self, time: f64, max_extrapolation: f32) -> Option<(Vec3, Quat)> {
+        let first = self.samples.front()?;
+        if self.samples.len() == 1 || time <= first.time {
+            return Some((first.translation, first.rotation));
+        }
+
+        let (from, to) = match self.samples.iter().position(|sample| sample.time > time) {
+            Some(index) => (self.samples[index - 1], self.samples[index]),
+            None => (
+                self.samples[self.samples.len() - 2],
+                self.samples[self.samples.len() - 1],
+            ),
+        };
+
+        let time = time.min(to.time + max_extrapolation as f64);
+        let t = ((time - from.time) / (to.time - from.time)) as f32;
+
+        let translation = from.translation.lerp(to.translation, t);
+        let rotation = if t <= 1.0 {
+            from.rotation.slerp(to.rotation, t)
+        } else {
+            let mut step = to.rotation * from.rotation.inverse();
+            // q and -q are the same rotation, but only the one with a positive w takes
+            // the short way around.
+            if step.w < 0.0 {
+                step = -step;
+            }
+            let (axis, angle) = step.to_axis_angle();
+            (Quat::from_axis_angle(axis, angle * (t - 1.0)) * to.rotation).normalize()
+        };
+
+        Some((translation, rotation))
+    }
+}
+
+/// Adds the transforms in `snapshot` to the buffers of the entities they belong to,
+/// skipping `skip_id` and entities that haven't been spawned yet.
+pub fn buffer_snapshot(
+    commands: &mut Commands,
+    lobby: &Lobby,
+    buffer_query: &mut Query<&mut SnapshotBuffer>,
+    snapshot: &Snapshot,
+    sample_time: f64,
+    skip_id: Option<u64>,
+) {
+    for (id, translation_rotation) in snapshot.entities.iter() {
+        if Some(*id) == skip_id {
+            continue;
+        }
+
+        let entity = match lobby
+            .players
+            .get(id)
+            .or_else(|| lobby.networked_entities.get(id))
+        {
+            Some(entity) => *entity,
+            None => continue,
+        };
+
+        match buffer_query.get_mut(entity) {
+            Ok(mut buffer) => buffer.push(
+                sample_time,
+                translation_rotation.translation,
+                translation_rotation.rotation,
+            ),
+            Err(_) => {
+                let mut buffer = SnapshotBuffer::default();
+                buffer.push(
+                    sample_time,
+                    translation_rotation.translation,
+                    translation_rotation.rotation,
+                );
+                commands.entity(entity).insert(buffer);
+            }
+        }
+    }
+}
+
+pub struct InterpolationPlugin {
+    pub settings: InterpolationSettings,
+}
+
+impl Plugin for InterpolationPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(ServerClock::default())
+            .insert_resource(self.settings)
+            .add_system(interpolate_snapshots);
+    }
+}
+
+fn interpolate_snapshots(
+    time: Res<Time>,
+    clock: Res<ServerClock>,
+    settings: Res<InterpolationSettings>,
+    mut query: Query<(&mut Transform, &mut SnapshotBuffer)>,
+) {
+    let render_time = match clock.server_time(time.elapsed_seconds_f64()) {
+        Some(server_time) => server_time - settings.delay as f64,
+        None => return,
+    };
+
+    for (mut transform, mut buffer) in query.iter_mut() {
+        buffer.discard_before(render_time);
+        if let Some((translation, rotation)) =
+            buffer.sample(render_time, settings.max_extrapolation)
+        {
+            transform.translation = translation;
+            transform.rotation = rotation;
+        }
+    }
+}
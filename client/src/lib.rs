@@ -1,5 +1,6 @@
 pub mod camera;
 pub mod capture_point;
+pub mod console;
 pub mod controls;
 pub mod debug;
 pub mod particles;
@@ -7,6 +8,7 @@ pub mod ui;
 pub mod utils;
 pub mod skybox;
 pub mod game_state;
+pub mod interpolation;
 pub mod player;
 
 #[macro_use]
@@ -4,7 +4,10 @@ extern crate cfg_if;
 cfg_if! {
     if #[cfg(not(target_arch = "wasm32"))] {
 
+        mod browser;
+        mod config;
         mod networking;
+        mod replay;
         mod app;
 
         fn main() {
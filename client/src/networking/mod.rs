@@ -1,17 +1,21 @@
-use std::{net::UdpSocket, time::SystemTime};
+use std::{collections::VecDeque, net::UdpSocket, process, time::SystemTime};
 
 use app::{
+    camera::Spectating,
     controls::{player_input},
-    game_state::run_if_not_paused,
-    utils::LerpTransformTarget,
+    debug::network::NetworkStats,
+    game_state::{run_if_not_paused, ConnectionStatus},
+    interpolation::{buffer_snapshot, InterpolationSettings, ServerClock, SnapshotBuffer},
+    player::prediction::{predict_local_player, LocalPlayerSnapshot},
 };
 use bevy::{
     app::App,
+    ecs::schedule::ShouldRun,
     prelude::{
-        Commands, DespawnRecursiveExt, EventWriter, IntoSystemDescriptor, Plugin, Res, ResMut,
-        SystemSet, Transform,
+        Commands, DespawnRecursiveExt, EventWriter, IntoSystemDescriptor, Plugin, Query, Res,
+        ResMut, Resource, SystemSet,
     },
-    utils::HashMap,
+    time::Time,
 };
 
 use bevy_renet::{
@@ -20,110 +24,454 @@ use bevy_renet::{
 };
 
 use spaaaace_shared::{
-    player::player_input::PlayerInput, ClientMessages, Lobby, ServerMessages, TranslationRotation,
-    PROTOCOL_ID, SERVER_TICKRATE,
+    auth::read_connect_token,
+    codec::{decode, DecodeError},
+    handshake::{ConnectRejectReason, ConnectReplyPrefix, BUILD_HASH, PROTOCOL_VERSION},
+    netsim::{condition_client_socket, NetworkConditioner},
+    player::player_input::PlayerInput,
+    snapshot::SnapshotDecoder,
+    tick::{run_on_tick, NetworkTick},
+    ClientMessages, Lobby, ServerMessages, ServerPacket, TranslationRotation, PROTOCOL_ID,
+    SERVER_TICKRATE,
 };
 
-pub struct ClientNetworkingPlugin;
+use crate::config::ClientSettings;
+
+/// Inputs the server has not acknowledged yet are repeated in every input message, so
+/// a dropped packet doesn't drop input. This caps how many are repeated.
+const MAX_REDUNDANT_INPUTS: usize = 5;
+
+/// Sent inputs the server has not acknowledged yet, oldest first.
+#[derive(Resource, Default, Debug)]
+pub struct UnackedInputs(VecDeque<PlayerInput>);
+
+impl UnackedInputs {
+    /// Forgets inputs up to and including `sequence`.
+    pub fn acknowledge(&mut self, sequence: u32) {
+        while self
+            .0
+            .front()
+            .map_or(false, |input| input.sequence <= sequence)
+        {
+            self.0.pop_front();
+        }
+    }
+}
+
+/// What the client needs to take its ship back after the connection drops.
+#[derive(Resource, Default, Debug)]
+pub struct Session {
+    /// Handed out by the server on joining, spectators get none.
+    token: Option<u64>,
+    /// Seconds the server keeps our ship after a disconnect.
+    resume_window: f32,
+    /// When the connection dropped, while we are trying to get it back.
+    lost_at: Option<f64>,
+}
+
+/// Newest server ticks seen on each channel.
+#[derive(Resource, Default, Debug)]
+pub struct LatestServerTick {
+    pub reliable: u64,
+    pub snapshot: u64,
+}
+
+pub struct ClientNetworkingPlugin {
+    pub settings: ClientSettings,
+}
 
 impl Plugin for ClientNetworkingPlugin {
     fn build(&self, app: &mut App) {
+        let settings = self.settings.clone();
+        // Without a server the browser picks one and calls `connect`.
+        let status = if settings.server.is_some() {
+            let (client, conditioner) = new_renet_client(&settings);
+            app.insert_resource(client);
+            if let Some(conditioner) = conditioner {
+                app.insert_resource(conditioner);
+            }
+            ConnectionStatus::Connecting
+        } else {
+            ConnectionStatus::Browsing
+        };
+
         app.add_plugin(RenetClientPlugin::default())
-            .insert_resource(new_renet_client())
             .insert_resource(PlayerInput::default())
-            .add_system_set(
+            .insert_resource(UnackedInputs::default())
+            .insert_resource(Session::default())
+            .insert_resource(LatestServerTick::default())
+            .insert_resource(SnapshotDecoder::default())
+            .insert_resource(settings)
+            .insert_resource(status)
+            .insert_resource(NetworkTick::new(SERVER_TICKRATE))
+            .add_system(send_hello.with_run_criteria(run_if_client_connected))
+            .add_system(detect_disconnect)
+            .add_system(client_reliable_message_handler.with_run_criteria(run_if_client_connected))
+            .add_system(
+                client_unreliable_message_handler.with_run_criteria(run_if_client_connected),
+            );
+
+        // Spectators have no ship to control.
+        if self.settings.spectate {
+            app.init_resource::<Spectating>();
+        } else {
+            app.add_system_set(
                 SystemSet::new()
                     .with_run_criteria(run_if_not_paused)
                     .with_system(player_input),
             )
-            .add_system(client_send_input.with_run_criteria(run_if_client_connected))
-            .add_system(client_reliable_message_handler.with_run_criteria(run_if_client_connected))
-            .add_system(
-                client_unreliable_message_handler.with_run_criteria(run_if_client_connected),
+            .add_system_set(
+                SystemSet::new()
+                    .with_run_criteria(run_on_connected_tick)
+                    .with_system(client_send_input.after(player_input))
+                    .with_system(predict_local_player.after(client_send_input)),
             );
+        }
     }
 }
 
-fn new_renet_client() -> RenetClient {
-    let server_addr = "127.0.0.1:5000".parse().unwrap();
-    let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+/// Starts connecting to the server in `settings`, replacing any earlier connection.
+pub fn connect(commands: &mut Commands, settings: &ClientSettings) {
+    let (client, conditioner) = new_renet_client(settings);
+    commands.insert_resource(client);
+    // Dropping the old conditioner stops its relay.
+    match conditioner {
+        Some(conditioner) => commands.insert_resource(conditioner),
+        None => commands.remove_resource::<NetworkConditioner>(),
+    }
+}
+
+fn new_renet_client(settings: &ClientSettings) -> (RenetClient, Option<NetworkConditioner>) {
+    let server_addr = settings.server_addr().unwrap_or_else(|error| {
+        eprintln!("{}", error);
+        process::exit(1);
+    });
+    let network_conditions = match (settings.network_conditions, &settings.token) {
+        // Tokens are only valid for the real server address, so packets can't be
+        // routed through the relay.
+        (Some(_), Some(_)) => {
+            println!(
+                "Network conditions can only be simulated without a connect token, simulate them on the server instead."
+            );
+            None
+        }
+        (network_conditions, _) => network_conditions,
+    };
+    let (socket, server_addr, conditioner) = match network_conditions {
+        Some(conditions) => {
+            println!("Simulating network conditions: {:?}", conditions);
+            let (socket, server_addr, conditioner) =
+                condition_client_socket(server_addr, conditions).unwrap();
+            (socket, server_addr, Some(conditioner))
+        }
+        None => (UdpSocket::bind("0.0.0.0:0").unwrap(), server_addr, None),
+    };
+
     let connection_config = RenetConnectionConfig::default();
     let current_time = SystemTime::now()
         .duration_since(SystemTime::UNIX_EPOCH)
         .unwrap();
-    let client_id = current_time.as_millis() as u64;
-    let authentication = ClientAuthentication::Unsecure {
-        client_id,
-        protocol_id: PROTOCOL_ID,
-        server_addr,
-        user_data: None,
+    let authentication = match &settings.token {
+        Some(path) => {
+            let connect_token = read_connect_token(path).unwrap_or_else(|error| {
+                eprintln!("Could not read connect token {}: {}", path.display(), error);
+                process::exit(1);
+            });
+            ClientAuthentication::Secure { connect_token }
+        }
+        // Only accepted by servers running in unsecure mode.
+        None => ClientAuthentication::Unsecure {
+            client_id: current_time.as_millis() as u64,
+            protocol_id: PROTOCOL_ID,
+            server_addr,
+            user_data: None,
+        },
+    };
+    let client =
+        RenetClient::new(current_time, socket, connection_config, authentication).unwrap();
+    (client, conditioner)
+}
+
+/// Runs once per client tick while connected, inputs are sampled and sent at the
+/// server's tick rate so the server can simulate one input per tick.
+fn run_on_connected_tick(
+    time: Res<Time>,
+    tick: ResMut<NetworkTick>,
+    client: Option<Res<RenetClient>>,
+) -> ShouldRun {
+    match client {
+        Some(client) if client.is_connected() => run_on_tick(time, tick),
+        _ => ShouldRun::No,
+    }
+}
+
+fn send_hello(
+    mut client: ResMut<RenetClient>,
+    mut status: ResMut<ConnectionStatus>,
+    mut stats: ResMut<NetworkStats>,
+    settings: Res<ClientSettings>,
+    session: Res<Session>,
+) {
+    if *status != ConnectionStatus::Connecting {
+        return;
+    }
+
+    let hello = ClientMessages::Hello {
+        protocol_version: PROTOCOL_VERSION,
+        build_hash: BUILD_HASH.to_string(),
+        ship_type: settings.ship_type.clone(),
+        name: settings.name.clone(),
+        spectator: settings.spectate,
+        session_token: session.token,
     };
-    RenetClient::new(current_time, socket, connection_config, authentication).unwrap()
+    let hello_message = bincode::serialize(&hello).unwrap();
+    stats.record_sent(DefaultChannel::Reliable, hello_message.len());
+    client.send_message(DefaultChannel::Reliable, hello_message);
+    *status = ConnectionStatus::Handshaking;
 }
 
-fn client_send_input(player_input: Res<PlayerInput>, mut client: ResMut<RenetClient>) {
-    let client_message = ClientMessages::PlayerInput {
-        input: *player_input,
+fn detect_disconnect(
+    mut commands: Commands,
+    mut session: ResMut<Session>,
+    mut lobby: ResMut<Lobby>,
+    mut status: ResMut<ConnectionStatus>,
+    client: Option<Res<RenetClient>>,
+    settings: Res<ClientSettings>,
+    time: Res<Time>,
+) {
+    let client = match client {
+        Some(client) => client,
+        None => return,
+    };
+    let reason = match *status {
+        // The server disconnects us after rejecting, keep showing why.
+        ConnectionStatus::Rejected { .. } | ConnectionStatus::Disconnected { .. } => return,
+        _ => match client.disconnected() {
+            Some(reason) => reason.to_string(),
+            None => return,
+        },
+    };
+
+    // The server keeps our ship for a while, keep trying to get it back until then.
+    let now = time.elapsed_seconds_f64();
+    if session.token.is_some() {
+        let lost_at = *session.lost_at.get_or_insert(now);
+        if now - lost_at < session.resume_window as f64 {
+            println!("Connection lost: {}. Reconnecting.", reason);
+
+            // The server sends the whole world again once we are back.
+            for entity in lobby
+                .players
+                .values()
+                .chain(lobby.networked_entities.values())
+            {
+                commands.entity(*entity).despawn_recursive();
+            }
+            lobby.players.clear();
+            lobby.networked_entities.clear();
+
+            connect(&mut commands, &settings);
+            *status = ConnectionStatus::Connecting;
+            return;
+        }
+    }
+
+    *status = ConnectionStatus::Disconnected { reason };
+}
+
+#[allow(clippy::too_many_arguments)]
+fn client_send_input(
+    mut player_input: ResMut<PlayerInput>,
+    mut unacked: ResMut<UnackedInputs>,
+    mut client: ResMut<RenetClient>,
+    mut stats: ResMut<NetworkStats>,
+    tick: Res<NetworkTick>,
+    clock: Res<ServerClock>,
+    interpolation: Res<InterpolationSettings>,
+    time: Res<Time>,
+) {
+    player_input.sequence += 1;
+    player_input.client_tick = tick.tick;
+    // Remote entities are drawn this far behind the server, see `interpolate_snapshots`.
+    player_input.view_time = clock
+        .server_time(time.elapsed_seconds_f64())
+        .map_or(0.0, |server_time| server_time - interpolation.delay as f64);
+
+    unacked.0.push_back(*player_input);
+    while unacked.0.len() > MAX_REDUNDANT_INPUTS {
+        unacked.0.pop_front();
+    }
+
+    let client_message = ClientMessages::PlayerInputs {
+        inputs: unacked.0.iter().copied().collect(),
     };
     let input_message = bincode::serialize(&client_message).unwrap();
-    client.send_message(DefaultChannel::Reliable, input_message);
+    stats.record_sent(DefaultChannel::Unreliable, input_message.len());
+    client.send_message(DefaultChannel::Unreliable, input_message);
 }
 
+#[allow(clippy::too_many_arguments)]
 fn client_reliable_message_handler(
     mut commands: Commands,
     mut client: ResMut<RenetClient>,
     mut lobby: ResMut<Lobby>,
+    mut latest_tick: ResMut<LatestServerTick>,
+    mut status: ResMut<ConnectionStatus>,
+    mut tick: ResMut<NetworkTick>,
+    mut stats: ResMut<NetworkStats>,
+    mut session: ResMut<Session>,
     mut server_message_event_writer: EventWriter<ServerMessages>,
 ) {
     while let Some(message) = client.receive_message(DefaultChannel::Reliable) {
-        let server_message = bincode::deserialize(&message).unwrap();
-        server_message_event_writer.send(bincode::deserialize(&message).unwrap());
+        stats.record_received(DefaultChannel::Reliable, message.len());
+        // The rest of an accept from another version may not decode, or decode wrong.
+        if let Ok(ServerPacket {
+            message: ConnectReplyPrefix::ConnectAccepted { protocol_version },
+            ..
+        }) = decode(&message)
+        {
+            if protocol_version != PROTOCOL_VERSION {
+                let reason = ConnectRejectReason::VersionMismatch {
+                    server_version: protocol_version,
+                    client_version: PROTOCOL_VERSION,
+                };
+                println!("Connection rejected: {}", reason);
+                *status = ConnectionStatus::Rejected { reason };
+                continue;
+            }
+        }
+        let packet: ServerPacket = match decode(&message) {
+            Ok(packet) => packet,
+            Err(error) => {
+                println!("Bad message from server: {}", error);
+                continue;
+            }
+        };
+        latest_tick.reliable = packet.tick;
+        let server_message = packet.message;
 
-        match server_message {
+        match &server_message {
+            ServerMessages::ConnectAccepted {
+                protocol_version: _,
+                player_id,
+                tick_rate,
+                session_token,
+                resume_window,
+            } => {
+                println!("Connected as player {}.", player_id);
+                tick.rate = *tick_rate;
+                tick.epoch_tick = 0;
+                tick.epoch_seconds = 0.0;
+                session.token = *session_token;
+                session.resume_window = *resume_window;
+                session.lost_at = None;
+                *status = ConnectionStatus::Accepted {
+                    player_id: *player_id,
+                };
+            }
+            ServerMessages::ConnectRejected { reason } => {
+                println!("Connection rejected: {}", reason);
+                *status = ConnectionStatus::Rejected {
+                    reason: reason.clone(),
+                };
+            }
             ServerMessages::EntityDespawn { id } => {
-                if let Some(entity) = lobby.networked_entities.remove(&id) {
+                if let Some(entity) = lobby.networked_entities.remove(id) {
                     commands.entity(entity).despawn_recursive();
                 }
             }
+            ServerMessages::TickRateChanged {
+                tick_rate,
+                epoch_tick,
+                epoch_seconds,
+            } => {
+                tick.rate = *tick_rate;
+                tick.epoch_tick = *epoch_tick;
+                tick.epoch_seconds = *epoch_seconds;
+            }
 
             _ => (),
         }
+
+        server_message_event_writer.send(server_message);
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 fn client_unreliable_message_handler(
     mut commands: Commands,
     mut client: ResMut<RenetClient>,
+    mut latest_tick: ResMut<LatestServerTick>,
+    mut decoder: ResMut<SnapshotDecoder>,
+    mut local_player_snapshot: ResMut<LocalPlayerSnapshot>,
+    mut unacked: ResMut<UnackedInputs>,
+    mut clock: ResMut<ServerClock>,
+    mut buffer_query: Query<&mut SnapshotBuffer>,
+    mut stats: ResMut<NetworkStats>,
     lobby: ResMut<Lobby>,
+    status: Res<ConnectionStatus>,
+    tick: Res<NetworkTick>,
+    time: Res<Time>,
 ) {
+    // Our ship keeps the id it was spawned with when we reconnect, so it can differ from
+    // the client id.
+    let local_id = match *status {
+        ConnectionStatus::Accepted { player_id } => Some(player_id),
+        _ => None,
+    };
+
     while let Some(message) = client.receive_message(DefaultChannel::Unreliable) {
-        let networked_translation: HashMap<u64, TranslationRotation> =
-            bincode::deserialize(&message).unwrap();
-
-        for (id, translation_rotation) in networked_translation.iter() {
-            if let Some(entity) = lobby.players.get(id) {
-                commands.entity(*entity).insert(LerpTransformTarget {
-                    target: Transform {
-                        translation: translation_rotation.translation,
-                        rotation: translation_rotation.rotation,
-                        ..Default::default()
-                    },
-                    speed: SERVER_TICKRATE / 1.2,
-                });
+        stats.record_received(DefaultChannel::Unreliable, message.len());
+        let snapshot = match decoder.decode(&message) {
+            Ok(snapshot) => snapshot,
+            // Snapshots whose baseline we no longer have are dropped, the server falls back
+            // to a full update once it stops getting acks for newer ones.
+            Err(DecodeError::UnknownBaseline { .. }) => continue,
+            Err(error) => {
+                println!("Bad snapshot from server: {}", error);
+                continue;
             }
+        };
+        stats.record_snapshot(snapshot.entities.len());
+        let sample_time = tick.tick_to_seconds(snapshot.tick);
+
+        // Unreliable packets can arrive out of order. A late snapshot can still fill a
+        // gap in the interpolation buffers, but must not roll back the local ship.
+        let is_latest = snapshot.tick > latest_tick.snapshot;
+        if is_latest {
+            latest_tick.snapshot = snapshot.tick;
+            clock.observe(sample_time, time.elapsed_seconds_f64());
+
+            let ack = ClientMessages::SnapshotAck {
+                tick: snapshot.tick,
+            };
+            let ack_message = bincode::serialize(&ack).unwrap();
+            stats.record_sent(DefaultChannel::Unreliable, ack_message.len());
+            client.send_message(DefaultChannel::Unreliable, ack_message);
         }
 
-        for (id, translation_rotation) in networked_translation.iter() {
-            if let Some(entity) = lobby.networked_entities.get(id) {
-                commands.entity(*entity).insert(LerpTransformTarget {
-                    target: Transform {
-                        translation: translation_rotation.translation,
-                        rotation: translation_rotation.rotation,
-                        ..Default::default()
-                    },
-                    speed: SERVER_TICKRATE / 1.2,
-                });
-            }
+        if let (true, Some(translation_rotation), Some(state)) = (
+            is_latest,
+            local_id.and_then(|id| snapshot.entities.get(&id)),
+            local_id.and_then(|id| snapshot.players.get(&id)),
+        ) {
+            unacked.acknowledge(state.last_processed_input);
+            local_player_snapshot.latest = Some((
+                TranslationRotation {
+                    translation: translation_rotation.translation,
+                    rotation: translation_rotation.rotation,
+                },
+                *state,
+            ));
         }
+
+        buffer_snapshot(
+            &mut commands,
+            &lobby,
+            &mut buffer_query,
+            &snapshot,
+            sample_time,
+            local_id,
+        );
     }
 }
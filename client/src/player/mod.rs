@@ -6,17 +6,27 @@ use bevy::{
     },
     utils::default,
 };
-use bevy_renet::renet::RenetClient;
-use spaaaace_shared::{player::player_input::PlayerInput, Lobby, ServerMessages};
+use spaaaace_shared::{
+    player::{movement::ShipVelocity, player_input::PlayerInput},
+    ships::{PlayerShipType, ShipTypes},
+    Lobby, ServerMessages,
+};
+
+use crate::{camera::OrbitCameraTarget, controls::LocalPlayer, game_state::ConnectionStatus};
+
+use self::prediction::{reconcile_local_player, InputHistory, LocalPlayerSnapshot};
 
-use crate::{camera::OrbitCameraTarget, controls::LocalPlayer};
+pub mod prediction;
 
 pub struct ClientPlayerPlugin;
 
 impl Plugin for ClientPlayerPlugin {
     fn build(&self, app: &mut App) {
+        app.insert_resource(InputHistory::default());
+        app.insert_resource(LocalPlayerSnapshot::default());
         app.add_system(on_client_connected);
         app.add_system(on_client_disconnected);
+        app.add_system(reconcile_local_player);
     }
 }
 
@@ -27,34 +37,46 @@ fn on_client_connected(
     mut commands: Commands,
     mut lobby: ResMut<Lobby>,
     mut event_reader: EventReader<ServerMessages>,
-    client: ResMut<RenetClient>,
+    status: Res<ConnectionStatus>,
+    ship_types: Res<ShipTypes>,
     ass: Res<AssetServer>,
 ) {
     for event in event_reader.iter() {
-        match event {
-            ServerMessages::PlayerConnected { id } => {
-                println!("Player {} connected.", id);
-
-                let my_gltf = ass.load("../../shared/assets/ships/test_ship/test_ship.gltf");
-                let mut cmd =
-                    commands.spawn((SpatialBundle { ..default() }, ShipModelLoadHandle(my_gltf)));
+        if let ServerMessages::PlayerConnected { id, ship_type } = event {
+            // Broadcasts sent while we were handshaking can repeat a player the
+            // server later sends us again.
+            if lobby.players.contains_key(id) {
+                continue;
+            }
+            println!("Player {} connected.", id);
 
-                if *id == client.client_id() {
-                    cmd.insert((
-                        OrbitCameraTarget {},
-                        LocalPlayer {},
-                        PlayerInput {
-                            ..Default::default()
-                        },
-                    ));
+            let mut cmd = commands.spawn((
+                SpatialBundle { ..default() },
+                PlayerShipType(ship_type.clone()),
+            ));
+            match ship_types.get(ship_type) {
+                Some(ship) => {
+                    let gltf = ass.load(format!("../../shared/assets/ships/{}", ship.model));
+                    cmd.insert(ShipModelLoadHandle(gltf));
                 }
+                None => println!("Player {} flies unknown ship type {}.", id, ship_type),
+            }
 
-                let player_entity = cmd.id();
-
-                lobby.players.insert(*id, player_entity);
+            // Replays have no local player.
+            if *status == (ConnectionStatus::Accepted { player_id: *id }) {
+                cmd.insert((
+                    OrbitCameraTarget {},
+                    LocalPlayer {},
+                    PlayerInput {
+                        ..Default::default()
+                    },
+                    ShipVelocity::default(),
+                ));
             }
 
-            _ => {}
+            let player_entity = cmd.id();
+
+            lobby.players.insert(*id, player_entity);
         }
     }
 }
@@ -65,14 +87,11 @@ fn on_client_disconnected(
     mut event_reader: EventReader<ServerMessages>,
 ) {
     for event in event_reader.iter() {
-        match event {
-            ServerMessages::PlayerDisconnected { id } => {
-                println!("Player {} disconnected.", id);
-                if let Some(player_entity) = lobby.players.remove(&id) {
-                    commands.entity(player_entity).despawn_recursive();
-                }
+        if let ServerMessages::PlayerDisconnected { id } = event {
+            println!("Player {} disconnected.", id);
+            if let Some(player_entity) = lobby.players.remove(&id) {
+                commands.entity(player_entity).despawn_recursive();
             }
-            _ => {}
         }
     }
 }
@@ -0,0 +1,102 @@
+use std::collections::VecDeque;
+
+use bevy::prelude::{Query, Res, ResMut, Resource, Transform, With};
+use spaaaace_shared::{
+    player::{
+        movement::{step_ship, ShipVelocity},
+        player_input::PlayerInput,
+    },
+    ships::{PlayerShipType, ShipTypes},
+    tick::NetworkTick,
+    PlayerState, TranslationRotation,
+};
+
+use crate::controls::LocalPlayer;
+
+const MAX_INPUT_HISTORY: usize = 128;
+
+/// Inputs that have been sent to the server but not yet acknowledged by a snapshot.
+#[derive(Resource, Default)]
+pub struct InputHistory {
+    pub inputs: VecDeque<PlayerInput>,
+}
+
+/// Latest authoritative state of the local ship, waiting to be reconciled.
+#[derive(Resource, Default)]
+pub struct LocalPlayerSnapshot {
+    pub latest: Option<(TranslationRotation, PlayerState)>,
+}
+
+/// Runs once per client tick after the input for that tick has been sent.
+pub fn predict_local_player(
+    player_input: Res<PlayerInput>,
+    mut history: ResMut<InputHistory>,
+    mut query: Query<
+        (
+            &mut Transform,
+            &mut ShipVelocity,
+            &mut PlayerInput,
+            &PlayerShipType,
+        ),
+        With<LocalPlayer>,
+    >,
+    ship_types: Res<ShipTypes>,
+    tick: Res<NetworkTick>,
+) {
+    history.inputs.push_back(*player_input);
+    while history.inputs.len() > MAX_INPUT_HISTORY {
+        history.inputs.pop_front();
+    }
+
+    for (mut transform, mut velocity, mut input, ship_type) in query.iter_mut() {
+        *input = *player_input;
+        if let Some(ship) = ship_types.get(&ship_type.0) {
+            step_ship(
+                &mut transform,
+                &mut velocity,
+                &player_input,
+                ship,
+                tick.delta_seconds(),
+            );
+        }
+    }
+}
+
+/// Resets the local ship to the server's state and replays the inputs the server
+/// hadn't processed yet.
+pub fn reconcile_local_player(
+    mut snapshot: ResMut<LocalPlayerSnapshot>,
+    mut history: ResMut<InputHistory>,
+    mut query: Query<(&mut Transform, &mut ShipVelocity, &PlayerShipType), With<LocalPlayer>>,
+    ship_types: Res<ShipTypes>,
+    tick: Res<NetworkTick>,
+) {
+    let (translation_rotation, state) = match snapshot.latest.take() {
+        Some(latest) => latest,
+        None => return,
+    };
+
+    history
+        .inputs
+        .retain(|input| input.sequence > state.last_processed_input);
+
+    for (mut transform, mut velocity, ship_type) in query.iter_mut() {
+        transform.translation = translation_rotation.translation;
+        transform.rotation = translation_rotation.rotation;
+        *velocity = state.velocity;
+
+        let ship = match ship_types.get(&ship_type.0) {
+            Some(ship) => ship,
+            None => continue,
+        };
+        for input in history.inputs.iter() {
+            step_ship(
+                &mut transform,
+                &mut velocity,
+                input,
+                ship,
+                tick.delta_seconds(),
+            );
+        }
+    }
+}
@@ -0,0 +1,201 @@
+use std::{path::PathBuf, process};
+
+use app::{
+    camera::Spectating,
+    game_state::ConnectionStatus,
+    interpolation::{buffer_snapshot, ServerClock, SnapshotBuffer},
+};
+use bevy::{
+    app::App,
+    prelude::{Commands, DespawnRecursiveExt, EventWriter, Plugin, Query, Res, ResMut, Resource},
+    time::Time,
+};
+use bevy_egui::{
+    egui::{Slider, Window},
+    EguiContext,
+};
+use spaaaace_shared::{
+    player::player_input::PlayerInput,
+    replay::{read_replay, ReplayFrame, ReplayHeader, ReplayPlayer, ReplayRecord},
+    tick::NetworkTick,
+    Lobby, ServerMessages,
+};
+
+/// After a seek, only snapshots this many seconds before the new position are
+/// interpolated, older ones would be discarded right away.
+const SEEK_SNAPSHOT_WINDOW: f64 = 1.0;
+
+/// Plays a recorded match back in place of a server connection.
+pub struct ReplayPlugin {
+    pub path: PathBuf,
+}
+
+impl Plugin for ReplayPlugin {
+    fn build(&self, app: &mut App) {
+        let (header, records) = read_replay(&self.path).unwrap_or_else(|error| {
+            eprintln!("Could not read replay {}: {}", self.path.display(), error);
+            process::exit(1);
+        });
+        println!(
+            "Playing back {} on map {}, recorded on build {}.",
+            self.path.display(),
+            header.map,
+            header.build_hash
+        );
+
+        app.insert_resource(NetworkTick::new(header.tick_rate))
+            .insert_resource(PlayerInput::default())
+            .insert_resource(ConnectionStatus::Replaying)
+            .init_resource::<Spectating>()
+            .insert_resource(ReplayPlayback::new(header, records))
+            .add_system(play_replay)
+            .add_system(replay_gui);
+    }
+}
+
+#[derive(Resource)]
+pub struct ReplayPlayback {
+    header: ReplayHeader,
+    records: Vec<ReplayRecord>,
+    /// Index of the next record to play.
+    cursor: usize,
+    /// Server time the playback is at.
+    time: f64,
+    /// Players that have joined up to `time`.
+    players: Vec<ReplayPlayer>,
+    pub speed: f32,
+    pub paused: bool,
+    /// Server time to jump to on the next update.
+    pub seek_to: Option<f64>,
+}
+
+impl ReplayPlayback {
+    fn new(header: ReplayHeader, records: Vec<ReplayRecord>) -> Self {
+        Self {
+            time: header.start_time,
+            players: header.players.clone(),
+            header,
+            records,
+            cursor: 0,
+            speed: 1.0,
+            paused: false,
+            seek_to: None,
+        }
+    }
+
+    pub fn start_time(&self) -> f64 {
+        self.header.start_time
+    }
+
+    pub fn end_time(&self) -> f64 {
+        self.records
+            .last()
+            .map_or(self.header.start_time, |record| record.time)
+    }
+}
+
+fn play_replay(
+    mut commands: Commands,
+    mut playback: ResMut<ReplayPlayback>,
+    mut lobby: ResMut<Lobby>,
+    mut clock: ResMut<ServerClock>,
+    mut buffer_query: Query<&mut SnapshotBuffer>,
+    mut server_message_event_writer: EventWriter<ServerMessages>,
+    time: Res<Time>,
+) {
+    let playback = &mut *playback;
+
+    if let Some(seek_to) = playback.seek_to.take() {
+        // Messages only make sense played in order, so going back starts over.
+        if seek_to < playback.time {
+            for entity in lobby
+                .players
+                .values()
+                .chain(lobby.networked_entities.values())
+            {
+                commands.entity(*entity).despawn_recursive();
+            }
+            lobby.players.clear();
+            lobby.networked_entities.clear();
+            playback.cursor = 0;
+            playback.players = playback.header.players.clone();
+        }
+        playback.time = seek_to;
+    } else if !playback.paused {
+        playback.time += time.delta_seconds_f64() * playback.speed as f64;
+    }
+    playback.time = playback.time.min(playback.end_time());
+    clock.set(playback.time, time.elapsed_seconds_f64());
+
+    while let Some(record) = playback.records.get(playback.cursor) {
+        if record.time > playback.time {
+            break;
+        }
+
+        match &record.frame {
+            ReplayFrame::Message(message) => {
+                if let ServerMessages::EntityDespawn { id } = message {
+                    if let Some(entity) = lobby.networked_entities.remove(id) {
+                        commands.entity(entity).despawn_recursive();
+                    }
+                }
+                server_message_event_writer.send(message.clone());
+            }
+            ReplayFrame::Snapshot(snapshot) => {
+                if playback.time - record.time <= SEEK_SNAPSHOT_WINDOW {
+                    buffer_snapshot(
+                        &mut commands,
+                        &lobby,
+                        &mut buffer_query,
+                        snapshot,
+                        record.time,
+                        None,
+                    );
+                }
+            }
+            ReplayFrame::PlayerJoined(player) => playback.players.push(player.clone()),
+        }
+        playback.cursor += 1;
+    }
+}
+
+fn format_time(seconds: f64) -> String {
+    let seconds = seconds.max(0.0) as u64;
+    format!("{}:{:02}", seconds / 60, seconds % 60)
+}
+
+fn replay_gui(mut egui_context: ResMut<EguiContext>, mut playback: ResMut<ReplayPlayback>) {
+    Window::new("Replay").show(egui_context.ctx_mut(), |ui| {
+        let start_time = playback.start_time();
+        let end_time = playback.end_time();
+
+        ui.label(format!(
+            "Map {}, build {}",
+            playback.header.map, playback.header.build_hash
+        ));
+        ui.label(format!(
+            "{} / {}",
+            format_time(playback.time - start_time),
+            format_time(end_time - start_time)
+        ));
+
+        let label = if playback.paused { "Play" } else { "Pause" };
+        if ui.button(label).clicked() {
+            playback.paused = !playback.paused;
+        }
+
+        let mut position = playback.time;
+        if ui
+            .add(Slider::new(&mut position, start_time..=end_time).text("Time"))
+            .changed()
+        {
+            playback.seek_to = Some(position);
+        }
+        ui.add(Slider::new(&mut playback.speed, 0.25..=4.0).text("Speed"));
+
+        ui.separator();
+        for player in playback.players.iter() {
+            ui.label(format!("{} ({})", player.name, player.ship_type));
+        }
+    });
+}
@@ -0,0 +1,248 @@
+use std::collections::VecDeque;
+
+use bevy::{
+    prelude::{
+        default, AssetServer, BuildChildren, ChildBuilder, Children, Color, Commands, Component,
+        DespawnRecursiveExt, Entity, EventReader, Input, KeyCode, Local, NodeBundle, Query, Res,
+        ResMut, Resource, Text, TextBundle, With,
+    },
+    text::TextStyle,
+    time::Time,
+    ui::{FlexDirection, PositionType, Size, Style, UiRect, Val},
+    window::ReceivedCharacter,
+};
+use bevy_renet::renet::{DefaultChannel, RenetClient};
+use spaaaace_shared::{
+    chat::{clean_chat_message, ChatChannel, ChatSender, MAX_CHAT_LENGTH},
+    ClientMessages, ServerMessages,
+};
+
+use crate::{debug::network::NetworkStats, game_state::ClientGameState};
+
+/// Messages kept around, the chat box shows the newest [`VISIBLE_LINES`] of them.
+const MAX_LINES: usize = 50;
+const VISIBLE_LINES: usize = 8;
+/// Seconds a message stays on screen while the chat is closed.
+const LINE_DURATION: f64 = 10.0;
+const FONT_SIZE: f32 = 18.0;
+
+#[derive(Component)]
+pub struct ChatLines;
+
+#[derive(Component)]
+pub struct ChatInput;
+
+struct ChatLine {
+    text: String,
+    color: Color,
+    received_at: f64,
+}
+
+#[derive(Resource, Default)]
+pub struct Chat {
+    /// The channel being typed to, `None` while the chat is closed.
+    typing: Option<ChatChannel>,
+    input: String,
+    lines: VecDeque<ChatLine>,
+}
+
+impl Chat {
+    fn push(&mut self, text: String, color: Color, now: f64) {
+        self.lines.push_back(ChatLine {
+            text,
+            color,
+            received_at: now,
+        });
+        while self.lines.len() > MAX_LINES {
+            self.lines.pop_front();
+        }
+    }
+}
+
+fn text_style(asset_server: &AssetServer, color: Color) -> TextStyle {
+    TextStyle {
+        font: asset_server.load("fonts/FiraSans-Bold.ttf"),
+        font_size: FONT_SIZE,
+        color,
+    }
+}
+
+/// The chat box in the bottom left corner, spawned with the rest of the UI.
+pub fn spawn_chat_box(parent: &mut ChildBuilder, asset_server: &AssetServer) {
+    parent
+        .spawn(NodeBundle {
+            style: Style {
+                size: Size::new(Val::Px(500.0), Val::Auto),
+                position_type: PositionType::Absolute,
+                position: UiRect {
+                    left: Val::Px(20.0),
+                    bottom: Val::Px(20.0),
+                    ..default()
+                },
+                flex_direction: FlexDirection::Column,
+                ..default()
+            },
+            ..default()
+        })
+        .with_children(|parent| {
+            parent
+                .spawn(NodeBundle {
+                    style: Style {
+                        flex_direction: FlexDirection::Column,
+                        ..default()
+                    },
+                    ..default()
+                })
+                .insert(ChatLines);
+            parent
+                .spawn(TextBundle::from_section(
+                    "",
+                    text_style(asset_server, Color::WHITE),
+                ))
+                .insert(ChatInput);
+        });
+}
+
+/// Enter opens the chat to everyone, T to the own team.
+pub fn chat_input(
+    keys: Res<Input<KeyCode>>,
+    mut characters: EventReader<ReceivedCharacter>,
+    mut chat: ResMut<Chat>,
+    mut game_state: ResMut<ClientGameState>,
+    mut client: Option<ResMut<RenetClient>>,
+    mut stats: ResMut<NetworkStats>,
+    time: Res<Time>,
+) {
+    let channel = match chat.typing {
+        Some(channel) => channel,
+        None => {
+            // The key that opens the chat must not end up in it.
+            for _ in characters.iter() {}
+            if game_state.is_typing {
+                return;
+            }
+            if keys.just_pressed(KeyCode::Return) {
+                chat.typing = Some(ChatChannel::All);
+            } else if keys.just_pressed(KeyCode::T) {
+                chat.typing = Some(ChatChannel::Team);
+            } else {
+                return;
+            }
+            game_state.is_typing = true;
+            return;
+        }
+    };
+
+    for character in characters.iter() {
+        if !character.char.is_control() && chat.input.chars().count() < MAX_CHAT_LENGTH {
+            chat.input.push(character.char);
+        }
+    }
+    if keys.just_pressed(KeyCode::Back) {
+        chat.input.pop();
+    }
+
+    if keys.just_pressed(KeyCode::Return) {
+        let input = std::mem::take(&mut chat.input);
+        if let Some(text) = clean_chat_message(&input) {
+            match client.as_deref_mut() {
+                Some(client) if client.is_connected() => {
+                    let message =
+                        bincode::serialize(&ClientMessages::Chat { channel, text }).unwrap();
+                    stats.record_sent(DefaultChannel::Reliable, message.len());
+                    client.send_message(DefaultChannel::Reliable, message);
+                }
+                _ => chat.push(
+                    "Not connected to a server.".to_string(),
+                    Color::YELLOW,
+                    time.elapsed_seconds_f64(),
+                ),
+            }
+        }
+    } else if !keys.just_pressed(KeyCode::Escape) {
+        return;
+    }
+    chat.typing = None;
+    chat.input.clear();
+    game_state.is_typing = false;
+}
+
+pub fn receive_chat(
+    mut server_message_reader: EventReader<ServerMessages>,
+    mut chat: ResMut<Chat>,
+    time: Res<Time>,
+) {
+    for message in server_message_reader.iter() {
+        let (sender, channel, text) = match message {
+            ServerMessages::Chat {
+                sender,
+                channel,
+                text,
+            } => (sender, channel, text),
+            _ => continue,
+        };
+
+        let (line, color) = match (sender, channel) {
+            (ChatSender::Server, _) => (format!("[Server] {}", text), Color::YELLOW),
+            (ChatSender::Player { name, .. }, ChatChannel::All) => {
+                (format!("{}: {}", name, text), Color::WHITE)
+            }
+            (ChatSender::Player { name, .. }, ChatChannel::Team) => {
+                (format!("[Team] {}: {}", name, text), Color::CYAN)
+            }
+        };
+        chat.push(line, color, time.elapsed_seconds_f64());
+    }
+}
+
+/// Shows the newest messages, older ones fade out unless the chat is open.
+pub fn update_chat_box(
+    mut commands: Commands,
+    chat: Res<Chat>,
+    lines_query: Query<(Entity, Option<&Children>), With<ChatLines>>,
+    mut input_query: Query<&mut Text, With<ChatInput>>,
+    asset_server: Res<AssetServer>,
+    time: Res<Time>,
+    mut shown: Local<usize>,
+) {
+    let now = time.elapsed_seconds_f64();
+    let visible: Vec<&ChatLine> = chat
+        .lines
+        .iter()
+        .rev()
+        .take(VISIBLE_LINES)
+        .take_while(|line| chat.typing.is_some() || now - line.received_at < LINE_DURATION)
+        .collect();
+
+    // Rebuilt only when a message comes or goes.
+    if chat.is_changed() || visible.len() != *shown {
+        *shown = visible.len();
+        for (entity, children) in lines_query.iter() {
+            if let Some(children) = children {
+                for child in children.iter() {
+                    commands.entity(*child).despawn_recursive();
+                }
+            }
+            for line in visible.iter().rev() {
+                let item = commands
+                    .spawn(TextBundle::from_section(
+                        line.text.clone(),
+                        text_style(&asset_server, line.color),
+                    ))
+                    .id();
+                commands.entity(entity).add_child(item);
+            }
+        }
+    }
+
+    if chat.is_changed() {
+        let prompt = match chat.typing {
+            Some(ChatChannel::All) => format!("[All] {}_", chat.input),
+            Some(ChatChannel::Team) => format!("[Team] {}_", chat.input),
+            None => String::new(),
+        };
+        for mut text in input_query.iter_mut() {
+            text.sections[0].value = prompt.clone();
+        }
+    }
+}
@@ -1,17 +1,21 @@
 use bevy::{
     prelude::{
         default, App, AssetServer, BuildChildren, Children, Color, Commands, Component,
-        DespawnRecursiveExt, Entity, ImageBundle, Input, KeyCode, NodeBundle, Plugin, Query, Res,
-        ResMut, TextBundle, Visibility, With,
+        DespawnRecursiveExt, Entity, ImageBundle, Input, IntoSystemDescriptor, KeyCode, NodeBundle,
+        Plugin, Query, Res, ResMut, Text, TextBundle, Visibility, With,
     },
     text::TextStyle,
-    ui::{AlignItems, FlexDirection, JustifyContent, Node, PositionType, Size, Style, Val},
+    ui::{AlignItems, FlexDirection, JustifyContent, Node, PositionType, Size, Style, UiRect, Val},
     window::{CursorGrabMode, Windows},
 };
 
 use spaaaace_shared::Lobby;
 
-use crate::game_state::ClientGameState;
+use crate::game_state::{ClientGameState, ConnectionStatus};
+
+use self::chat::{chat_input, receive_chat, spawn_chat_box, update_chat_box, Chat};
+
+pub mod chat;
 
 pub struct GameUIPlugin;
 
@@ -20,7 +24,13 @@ impl Plugin for GameUIPlugin {
         app.add_startup_system(setup)
             .add_system(input)
             .add_system(update_pause_mode)
-            .add_system(scoreboard);
+            .add_system(scoreboard)
+            .add_system(connection_status)
+            .init_resource::<Chat>()
+            // After input, so the Escape closing the chat doesn't also pause.
+            .add_system(chat_input.after(input))
+            .add_system(receive_chat)
+            .add_system(update_chat_box.after(chat_input).after(receive_chat));
     }
 }
 
@@ -32,6 +42,9 @@ struct PauseBackdrop;
 #[derive(Component)]
 struct Scoreboard;
 
+#[derive(Component)]
+struct ConnectionStatusText;
+
 fn setup(mut commands: Commands, asset_server: Res<AssetServer>) {
     // root node
     commands
@@ -90,6 +103,29 @@ fn setup(mut commands: Commands, asset_server: Res<AssetServer>) {
                 })
                 .insert(Scoreboard {});
 
+            parent
+                .spawn(
+                    TextBundle::from_section(
+                        "",
+                        TextStyle {
+                            font: asset_server.load("fonts/FiraSans-Bold.ttf"),
+                            font_size: 30.0,
+                            color: Color::WHITE,
+                        },
+                    )
+                    .with_style(Style {
+                        position_type: PositionType::Absolute,
+                        position: UiRect {
+                            top: Val::Px(20.0),
+                            ..default()
+                        },
+                        ..default()
+                    }),
+                )
+                .insert(ConnectionStatusText);
+
+            spawn_chat_box(parent, &asset_server);
+
             parent.spawn(ImageBundle {
                 style: Style {
                     size: Size::new(Val::Px(CROSSHAIR_SIZE), Val::Px(CROSSHAIR_SIZE)),
@@ -106,7 +142,8 @@ fn input(
     mut game_state: ResMut<ClientGameState>,
     mut windows: ResMut<Windows>,
 ) {
-    if keys.just_pressed(KeyCode::Escape) {
+    // Escape is left to whatever is being typed into.
+    if keys.just_pressed(KeyCode::Escape) && !game_state.is_typing {
         game_state.is_paused = !game_state.is_paused;
     }
 
@@ -188,3 +225,25 @@ fn scoreboard(
         }
     }
 }
+
+fn connection_status(
+    status: Res<ConnectionStatus>,
+    mut query: Query<&mut Text, With<ConnectionStatusText>>,
+) {
+    if !status.is_changed() {
+        return;
+    }
+
+    let message = match status.as_ref() {
+        ConnectionStatus::Connecting | ConnectionStatus::Handshaking => "Connecting...".to_string(),
+        ConnectionStatus::Browsing
+        | ConnectionStatus::Accepted { .. }
+        | ConnectionStatus::Replaying => String::new(),
+        ConnectionStatus::Rejected { reason } => format!("Connection rejected. {}", reason),
+        ConnectionStatus::Disconnected { reason } => format!("Disconnected: {}", reason),
+    };
+
+    for mut text in query.iter_mut() {
+        text.sections[0].value = message.clone();
+    }
+}
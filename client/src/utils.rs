@@ -5,7 +5,6 @@ use bevy::{
         Query, Res, ResMut, SpatialBundle, Transform, Vec2, Vec4,
     },
     scene::SceneBundle,
-    time::Time,
 };
 use bevy_hanabi::{
     BillboardModifier, ColorOverLifetimeModifier, EffectAsset, Gradient, ParticleEffectBundle,
@@ -13,28 +12,13 @@ use bevy_hanabi::{
     Spawner,
 };
 use bevy_scene_hook::{HookedSceneBundle, SceneHook};
-use spaaaace_shared::weapons::{Barrel, Turret, TurretOwner};
+use spaaaace_shared::{
+    ships::{PlayerShipType, ShipTypes},
+    weapons::{Barrel, Turret, TurretOwner},
+};
 
 use crate::player::ShipModelLoadHandle;
 
-#[derive(Component)]
-pub struct LerpTransformTarget {
-    pub target: Transform,
-    pub speed: f32,
-}
-
-pub fn lerp_transform_targets(
-    mut query: Query<(&mut Transform, &LerpTransformTarget)>,
-    time: Res<Time>,
-) {
-    for (mut t, l) in query.iter_mut() {
-        let s = l.speed * time.delta_seconds();
-        t.translation = t.translation.lerp(l.target.translation, s);
-        t.rotation = t.rotation.lerp(l.target.rotation, s);
-        // t.scale = t.scale.lerp(l.target.scale, s); Removed since current scale is not sent
-    }
-}
-
 #[derive(Component)]
 pub struct LocalTurretModelLoadHandle(pub Handle<Gltf>, pub Entity);
 
@@ -57,13 +41,14 @@ pub fn spawn_local_turret(
 
 pub fn handle_ship_model_load(
     mut commands: Commands,
-    query: Query<(Entity, &ShipModelLoadHandle)>,
+    query: Query<(Entity, &ShipModelLoadHandle, &PlayerShipType)>,
     assets_gltf: Res<Assets<Gltf>>,
     assets_gltfnode: Res<Assets<GltfNode>>,
     ass: Res<AssetServer>,
+    ship_types: Res<ShipTypes>,
     mut effects: ResMut<Assets<EffectAsset>>,
 ) {
-    for (entity, handle) in query.iter() {
+    for (entity, handle, ship_type) in query.iter() {
         if let Some(gltf) = assets_gltf.get(&handle.0) {
             let mut gradient = Gradient::new();
             gradient.add_key(0.0, Vec4::new(0.0, 1.0, 1.0, 1.0) * 3.0);
@@ -101,7 +86,17 @@ pub fn handle_ship_model_load(
                 })
                 .id();
             let mut thruster_points: Vec<Entity> = vec![];
-            let mut turrets: Vec<Entity> = vec![];
+            // Same hardpoints the server mounts its turrets on.
+            let turrets: Vec<Entity> = match ship_types.get(&ship_type.0) {
+                Some(ship) => ship
+                    .hardpoints
+                    .iter()
+                    .map(|hardpoint| {
+                        spawn_local_turret(&mut commands, &ass, &hardpoint.transform(), entity)
+                    })
+                    .collect(),
+                None => Vec::new(),
+            };
 
             for node_name in gltf.named_nodes.keys().into_iter() {
                 if node_name.contains("forward_thrusters") {
@@ -113,13 +108,6 @@ pub fn handle_ship_model_load(
                         thruster_points.push(thruster);
                     }
                 }
-                if node_name.contains("turret_pad_large") {
-                    if let Some(node) = assets_gltfnode.get(&gltf.named_nodes[node_name]) {
-                        let turret =
-                            spawn_local_turret(&mut commands, &ass, &node.transform, entity);
-                        turrets.push(turret);
-                    }
-                }
             }
 
             commands
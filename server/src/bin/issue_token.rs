@@ -0,0 +1,70 @@
+//! Issues connect tokens for a server running in secure mode, without needing the
+//! server to be running.
+//!
+//! ```text
+//! cargo run --bin issue_token -- --generate-key private.key
+//! cargo run --bin issue_token -- --private-key private.key --client-id 42 --out connect.token
+//! ```
+//!
+//! Start the client with `--token connect.token` to use the token.
+
+use std::{env, net::SocketAddr, path::Path, process};
+
+use spaaaace_shared::auth::{
+    generate_connect_token, generate_private_key, read_private_key, write_connect_token,
+    write_private_key, DEFAULT_CONNECT_TOKEN_PATH, DEFAULT_PRIVATE_KEY_PATH,
+};
+
+const DEFAULT_SERVER_ADDR: &str = "127.0.0.1:5000";
+const DEFAULT_EXPIRE_SECONDS: u64 = 300;
+
+fn arg_value(name: &str) -> Option<String> {
+    let mut args = env::args();
+    args.find(|arg| arg == name)?;
+    args.next()
+}
+
+fn fail(message: String) -> ! {
+    eprintln!("{}", message);
+    process::exit(1);
+}
+
+fn main() {
+    if let Some(path) = arg_value("--generate-key") {
+        write_private_key(Path::new(&path), &generate_private_key())
+            .unwrap_or_else(|error| fail(format!("Could not write {}: {}", path, error)));
+        println!("Wrote a new private key to {}.", path);
+        return;
+    }
+
+    let key_path = arg_value("--private-key").unwrap_or(DEFAULT_PRIVATE_KEY_PATH.to_string());
+    let private_key = read_private_key(Path::new(&key_path))
+        .unwrap_or_else(|error| fail(format!("Could not read {}: {}", key_path, error)));
+
+    let client_id = match arg_value("--client-id") {
+        Some(id) => id
+            .parse()
+            .unwrap_or_else(|_| fail(format!("Invalid client id {}", id))),
+        None => rand::random(),
+    };
+    let server_addr: SocketAddr = arg_value("--server")
+        .unwrap_or(DEFAULT_SERVER_ADDR.to_string())
+        .parse()
+        .unwrap_or_else(|_| fail("Invalid server address".to_string()));
+    let expire_seconds = match arg_value("--expire") {
+        Some(seconds) => seconds
+            .parse()
+            .unwrap_or_else(|_| fail(format!("Invalid expiry {}", seconds))),
+        None => DEFAULT_EXPIRE_SECONDS,
+    };
+    let out = arg_value("--out").unwrap_or(DEFAULT_CONNECT_TOKEN_PATH.to_string());
+
+    let token = generate_connect_token(&private_key, client_id, server_addr, expire_seconds);
+    write_connect_token(Path::new(&out), &token)
+        .unwrap_or_else(|error| fail(format!("Could not write {}: {}", out, error)));
+
+    println!(
+        "Wrote a token for client {} to {}, valid for {} seconds.",
+        client_id, out, expire_seconds
+    );
+}
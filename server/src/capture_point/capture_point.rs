@@ -1,40 +1,34 @@
 use bevy::{
-    prelude::{Bundle, Component, PbrBundle, Query, Res, ResMut, Transform},
-    time::Time,
+    prelude::{Component, Query, Res, Transform},
     utils::{HashMap, HashSet},
 };
-use bevy_renet::renet::{DefaultChannel, RenetServer};
-use spaaaace_shared::{team::team_enum::Team, ServerMessages};
+use spaaaace_shared::{capture_point::CapturePoint, team::team_enum::Team, tick::NetworkTick};
 
-use crate::Player;
+use spaaaace_shared::player::Player;
 
-#[derive(Component, Clone)]
+/// Clients are sent the progress in steps this big, so a capture doesn't update the
+/// capture point every tick.
+const PROGRESS_STEP: f32 = 0.05;
+
+#[derive(Component, Clone, Default)]
 pub struct CaptureSphere {
-    pub id: u64,
-    pub radius: f32,
-    pub progress: f32,
-    pub owner: Team,
     pub attackers: HashSet<Player>,
-}
-
-#[derive(Bundle)]
-pub struct CapturePoint {
-    pub capture: CaptureSphere,
-    pub pbr_bundle: PbrBundle,
+    /// Exact progress, [`CapturePoint::progress`] is rounded to [`PROGRESS_STEP`].
+    pub progress: f32,
 }
 
 pub fn capture_arena(
-    mut query_capture_spheres: Query<(&Transform, &mut CaptureSphere)>,
+    mut query_capture_spheres: Query<(&Transform, &CapturePoint, &mut CaptureSphere)>,
     query_space_ship: Query<(&Transform, &Player)>,
 ) {
-    for (capture_transform, mut capture_sphere) in query_capture_spheres.iter_mut() {
+    for (capture_transform, capture_point, mut capture_sphere) in query_capture_spheres.iter_mut() {
         capture_sphere.attackers.clear();
         for (ship_transform, player) in query_space_ship.iter() {
             let distance = ship_transform
                 .translation
                 .distance(capture_transform.translation);
 
-            if capture_sphere.radius > distance {
+            if capture_point.radius > distance {
                 capture_sphere.attackers.insert(player.clone());
             }
         }
@@ -42,12 +36,12 @@ pub fn capture_arena(
 }
 
 pub fn capture_progress(
-    mut query_capture_spheres: Query<&mut CaptureSphere>,
-    time: Res<Time>,
-    mut server: ResMut<RenetServer>,
+    mut query_capture_spheres: Query<(&mut CaptureSphere, &mut CapturePoint)>,
+    tick: Res<NetworkTick>,
 ) {
-    for mut capture_sphere in query_capture_spheres.iter_mut() {
-        let old_progress = capture_sphere.progress;
+    for (mut capture_sphere, mut capture_point) in query_capture_spheres.iter_mut() {
+        let mut next = capture_point.clone();
+        next.progress = capture_sphere.progress;
         let mut attackers_by_team = HashMap::<Team, u8>::new();
 
         for element in capture_sphere.attackers.clone().into_iter() {
@@ -64,43 +58,39 @@ pub fn capture_progress(
         let capture_rate = 0.1;
 
         if let Some((team, _)) = superior_team {
-            if capture_sphere.owner == Team::Neutral {
-                capture_sphere.progress =
-                    (capture_sphere.progress + capture_rate * time.delta_seconds()).clamp(0.0, 1.0);
+            if next.owner == Team::Neutral {
+                next.progress =
+                    (next.progress + capture_rate * tick.delta_seconds()).clamp(0.0, 1.0);
 
-                if capture_sphere.progress == 1.0 {
-                    capture_sphere.owner = team.clone();
+                if next.progress == 1.0 {
+                    next.owner = team.clone();
                 }
-            } else if &capture_sphere.owner != team {
-                capture_sphere.progress =
-                    (capture_sphere.progress - capture_rate * time.delta_seconds()).clamp(0.0, 1.0);
+            } else if &next.owner != team {
+                next.progress =
+                    (next.progress - capture_rate * tick.delta_seconds()).clamp(0.0, 1.0);
 
-                if capture_sphere.progress == 0.0 {
-                    capture_sphere.owner = Team::Neutral;
+                if next.progress == 0.0 {
+                    next.owner = Team::Neutral;
                 }
             }
-        } else if capture_sphere.progress < 1.0 && capture_sphere.owner != Team::Neutral {
-            capture_sphere.progress =
-                (capture_sphere.progress + capture_rate * time.delta_seconds()).clamp(0.0, 1.0);
-        } else if capture_sphere.progress > 0.0 && capture_sphere.owner == Team::Neutral {
-            capture_sphere.progress =
-                (capture_sphere.progress - capture_rate * time.delta_seconds()).clamp(0.0, 1.0);
+        } else if next.progress < 1.0 && next.owner != Team::Neutral {
+            next.progress = (next.progress + capture_rate * tick.delta_seconds()).clamp(0.0, 1.0);
+        } else if next.progress > 0.0 && next.owner == Team::Neutral {
+            next.progress = (next.progress - capture_rate * tick.delta_seconds()).clamp(0.0, 1.0);
         }
 
-        if old_progress != capture_sphere.progress {
-            //println!(
-            //    "Q {} {} {}",
-            //    capture_sphere.progress,
-            //    capture_sphere.owner
-            //);
-            let message = bincode::serialize(&ServerMessages::CapturePointUpdate {
-                id: capture_sphere.id,
-                owner: capture_sphere.owner.clone(),
-                attacker: capture_sphere.owner.clone(),
-                progress: capture_sphere.progress,
-            })
-            .unwrap();
-            server.broadcast_message(DefaultChannel::Reliable, message);
+        next.attacker = superior_team
+            .map(|(team, _)| team.clone())
+            .unwrap_or(Team::Neutral);
+
+        capture_sphere.progress = next.progress;
+        next.progress = (next.progress / PROGRESS_STEP).round() * PROGRESS_STEP;
+
+        if next.progress != capture_point.progress
+            || next.owner != capture_point.owner
+            || next.attacker != capture_point.attacker
+        {
+            *capture_point = next;
         }
     }
 }
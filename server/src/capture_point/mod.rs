@@ -1,106 +1,98 @@
 use bevy::{
     math::vec3,
-    prelude::{App, Color, Commands, EventReader, Plugin, Query, ResMut, Transform},
+    prelude::{
+        App, Commands, DespawnRecursiveExt, Entity, EventReader, IntoSystemDescriptor, Plugin,
+        Query, Res, ResMut, Transform, Vec3, With,
+    },
     transform::TransformBundle,
-    utils::HashSet,
 };
 
-use bevy_mod_gizmos::{draw_gizmo, Gizmo};
-use bevy_renet::renet::{DefaultChannel, RenetServer, ServerEvent};
-use spaaaace_shared::{team::team_enum::Team, ServerMessages};
+use spaaaace_shared::{
+    capture_point::{CapturePoint, CapturePointPlugin},
+    team::team_enum::Team,
+    NetworkIdProvider,
+};
+
+use crate::{
+    config::ServerSettings,
+    console::commands::RestartMatch,
+    tick::{TickAppExt, TickStage},
+};
 
 use self::capture_point::{capture_arena, capture_progress, CaptureSphere};
 
+#[allow(clippy::module_inception)]
 pub mod capture_point;
 
-pub struct CapturePointPlugin;
+pub struct ServerCapturePointPlugin;
 
-impl Plugin for CapturePointPlugin {
+impl Plugin for ServerCapturePointPlugin {
     fn build(&self, app: &mut App) {
-        app.add_startup_system(init)
-            .add_system(capture_arena)
-            .add_system(on_client_connected)
-            .add_system(draw_capture_sphere_gizmos)
-            .add_system(capture_progress);
+        app.add_plugin(CapturePointPlugin)
+            .add_startup_system(init)
+            .add_system(restart_capture_points)
+            .add_tick_system(TickStage::Simulate, capture_arena)
+            .add_tick_system(TickStage::Simulate, capture_progress.after(capture_arena));
     }
 }
 
-fn on_client_connected(
-    mut event_reader: EventReader<ServerEvent>,
-    mut server: ResMut<RenetServer>,
-    capture_point_query: Query<(&Transform, &CaptureSphere)>,
-) {
-    for event in event_reader.iter() {
-        match event {
-            ServerEvent::ClientConnected(id, _) => {
-                for (&transform, capture_point) in capture_point_query.iter() {
-                    let message = bincode::serialize(&ServerMessages::CapturePointSpawned {
-                        position: transform.translation,
-                        rotation: transform.rotation,
-                        id: capture_point.id,
-                        owner: capture_point.owner.clone(),
-                        progress: capture_point.progress,
-                    })
-                    .unwrap();
-                    server.send_message(*id, DefaultChannel::Reliable, message);
-                }
-            }
+/// Maps the server can be started with.
+pub const MAPS: &[&str] = &["default"];
 
-            _ => (),
-        }
+fn map_capture_points(map: &str) -> Vec<(Vec3, Team)> {
+    match map {
+        "default" => vec![
+            (vec3(0.0, 100.0, 100.0), Team::Blue),
+            (vec3(150.0, 50.0, 100.0), Team::Neutral),
+            (vec3(200.0, 10.0, 200.0), Team::Red),
+        ],
+        _ => unreachable!("the map is validated when the settings are loaded"),
     }
 }
 
-fn draw_capture_sphere_gizmos(cap_query: Query<(&CaptureSphere, &Transform)>) {
-    for (_, transform) in cap_query.iter() {
-        draw_gizmo(Gizmo::new(transform.translation, 1.0, Color::GREEN))
+fn init(
+    mut commands: Commands,
+    mut id_provider: ResMut<NetworkIdProvider>,
+    settings: Res<ServerSettings>,
+) {
+    spawn_capture_points(&mut commands, &mut id_provider, &settings.map);
+}
+
+/// Respawns the capture points, the map may have changed.
+fn restart_capture_points(
+    mut restart_reader: EventReader<RestartMatch>,
+    mut commands: Commands,
+    mut id_provider: ResMut<NetworkIdProvider>,
+    settings: Res<ServerSettings>,
+    query: Query<Entity, With<CapturePoint>>,
+) {
+    if restart_reader.iter().count() == 0 {
+        return;
+    }
+
+    for entity in query.iter() {
+        commands.entity(entity).despawn_recursive();
     }
+    spawn_capture_points(&mut commands, &mut id_provider, &settings.map);
 }
 
-fn init(mut commands: Commands) {
-    commands
-        .spawn(TransformBundle {
-            local: Transform {
-                translation: vec3(0.0, 100.0, 100.0),
+fn spawn_capture_points(commands: &mut Commands, id_provider: &mut NetworkIdProvider, map: &str) {
+    for (translation, owner) in map_capture_points(map) {
+        commands
+            .spawn(TransformBundle {
+                local: Transform {
+                    translation,
+                    ..Default::default()
+                },
                 ..Default::default()
-            },
-            ..Default::default()
-        })
-        .insert(CaptureSphere {
-            radius: 50.,
-            progress: 0.0,
-            owner: Team::Blue,
-            attackers: HashSet::default(),
-            id: 1,
-        });
-    commands
-        .spawn(TransformBundle {
-            local: Transform {
-                translation: vec3(150.0, 50.0, 100.0),
-                ..Default::default()
-            },
-            ..Default::default()
-        })
-        .insert(CaptureSphere {
-            radius: 50.,
-            progress: 0.0,
-            owner: Team::Neutral,
-            attackers: HashSet::default(),
-            id: 2,
-        });
-    commands
-        .spawn(TransformBundle {
-            local: Transform {
-                translation: vec3(200.0, 10.0, 200.0),
-                ..Default::default()
-            },
-            ..Default::default()
-        })
-        .insert(CaptureSphere {
-            radius: 50.,
-            progress: 0.0,
-            owner: Team::Red,
-            attackers: HashSet::default(),
-            id: 3,
-        });
+            })
+            .insert(CapturePoint {
+                radius: 50.,
+                progress: 0.0,
+                owner,
+                attacker: Team::Neutral,
+            })
+            .insert(CaptureSphere::default())
+            .insert(id_provider.new_id());
+    }
 }
@@ -0,0 +1,256 @@
+use std::collections::HashMap;
+
+use bevy::{
+    prelude::{App, EventReader, EventWriter, Plugin, Query, Res, ResMut, Resource},
+    time::Time,
+};
+use bevy_renet::renet::{RenetServer, ServerEvent};
+use spaaaace_shared::{
+    broadcast_server_message,
+    chat::{clean_chat_message, ChatChannel, ChatSender},
+    command::Command,
+    handshake::ClientAccepted,
+    player::Player,
+    replay::ReplayRecorder,
+    send_server_message,
+    tick::NetworkTick,
+    ClientMessages, Lobby, ServerMessages,
+};
+
+use crate::{
+    console::{CommandAppExt, CommandInvoked, CommandOutput, Permission},
+    ClientEvent,
+};
+
+/// Messages a client can send in a row before it has to slow down.
+const CHAT_BURST: f32 = 5.0;
+/// Seconds until a client may send one more message.
+const CHAT_REFILL_SECONDS: f32 = 2.0;
+
+/// Passes chat messages between players and sends server announcements.
+pub struct ChatPlugin;
+
+impl Plugin for ChatPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ChatState>()
+            .add_console_command("say", Permission::Admin)
+            .add_system(track_chatters)
+            .add_system(relay_chat)
+            .add_system(say_command);
+    }
+}
+
+struct Chatter {
+    name: String,
+    player_id: u64,
+    spectator: bool,
+    /// Messages the client may send right now, see [`CHAT_BURST`].
+    allowance: f32,
+    last_message: f64,
+}
+
+#[derive(Resource, Default)]
+struct ChatState {
+    chatters: HashMap<u64, Chatter>,
+}
+
+fn track_chatters(
+    mut accepted_reader: EventReader<ClientAccepted>,
+    mut event_reader: EventReader<ServerEvent>,
+    mut state: ResMut<ChatState>,
+    mut server: ResMut<RenetServer>,
+    mut recorder: Option<ResMut<ReplayRecorder>>,
+    tick: Res<NetworkTick>,
+) {
+    for event in accepted_reader.iter() {
+        // The connection being taken over should leave quietly.
+        if event.resumed {
+            state
+                .chatters
+                .retain(|_, chatter| chatter.player_id != event.player_id);
+        }
+        state.chatters.insert(
+            event.client_id,
+            Chatter {
+                name: event.name.clone(),
+                player_id: event.player_id,
+                spectator: event.spectator,
+                allowance: CHAT_BURST,
+                last_message: 0.0,
+            },
+        );
+        if !event.resumed {
+            announce(
+                &mut server,
+                &tick,
+                recorder.as_deref_mut(),
+                format!("{} joined the game.", event.name),
+            );
+        }
+    }
+
+    for event in event_reader.iter() {
+        if let ServerEvent::ClientDisconnected(id) = event {
+            if let Some(chatter) = state.chatters.remove(id) {
+                announce(
+                    &mut server,
+                    &tick,
+                    recorder.as_deref_mut(),
+                    format!("{} left the game.", chatter.name),
+                );
+            }
+        }
+    }
+}
+
+fn announce(
+    server: &mut RenetServer,
+    tick: &NetworkTick,
+    recorder: Option<&mut ReplayRecorder>,
+    text: String,
+) {
+    broadcast_server_message(
+        server,
+        tick,
+        recorder,
+        ServerMessages::Chat {
+            sender: ChatSender::Server,
+            channel: ChatChannel::All,
+            text,
+        },
+    );
+}
+
+/// Tells a single client something through the chat.
+fn reply(server: &mut RenetServer, tick: &NetworkTick, client_id: u64, text: &str) {
+    send_server_message(
+        server,
+        client_id,
+        tick,
+        ServerMessages::Chat {
+            sender: ChatSender::Server,
+            channel: ChatChannel::All,
+            text: text.to_string(),
+        },
+    );
+}
+
+#[allow(clippy::too_many_arguments)]
+fn relay_chat(
+    mut client_message_event_reader: EventReader<ClientEvent>,
+    mut state: ResMut<ChatState>,
+    mut server: ResMut<RenetServer>,
+    mut recorder: Option<ResMut<ReplayRecorder>>,
+    player_query: Query<&Player>,
+    lobby: Res<Lobby>,
+    tick: Res<NetworkTick>,
+    time: Res<Time>,
+) {
+    let now = time.elapsed_seconds_f64();
+    let team_of = |player_id: u64| {
+        lobby
+            .players
+            .get(&player_id)
+            .and_then(|entity| player_query.get(*entity).ok())
+            .map(|player| player.team.clone())
+    };
+
+    for event in client_message_event_reader.iter() {
+        let (channel, text) = match &event.message {
+            ClientMessages::Chat { channel, text } => (*channel, text),
+            _ => continue,
+        };
+        let chatter = match state.chatters.get_mut(&event.client_id) {
+            Some(chatter) => chatter,
+            None => continue,
+        };
+        let text = match clean_chat_message(text) {
+            Some(text) => text,
+            None => continue,
+        };
+
+        chatter.allowance = (chatter.allowance
+            + (now - chatter.last_message) as f32 / CHAT_REFILL_SECONDS)
+            .min(CHAT_BURST);
+        chatter.last_message = now;
+        if chatter.allowance < 1.0 {
+            reply(
+                &mut server,
+                &tick,
+                event.client_id,
+                "You are sending messages too fast.",
+            );
+            continue;
+        }
+        chatter.allowance -= 1.0;
+
+        let name = chatter.name.clone();
+        let player_id = chatter.player_id;
+        let spectator = chatter.spectator;
+        let sender = ChatSender::Player { player_id, name };
+        match channel {
+            ChatChannel::All => {
+                broadcast_server_message(
+                    &mut server,
+                    &tick,
+                    recorder.as_deref_mut(),
+                    ServerMessages::Chat {
+                        sender,
+                        channel,
+                        text,
+                    },
+                );
+            }
+            ChatChannel::Team => {
+                let team = match team_of(player_id) {
+                    Some(team) if !spectator => team,
+                    _ => {
+                        reply(
+                            &mut server,
+                            &tick,
+                            event.client_id,
+                            "You are not on a team.",
+                        );
+                        continue;
+                    }
+                };
+                for (client_id, _) in state.chatters.iter().filter(|(_, other)| {
+                    !other.spectator && team_of(other.player_id).as_ref() == Some(&team)
+                }) {
+                    send_server_message(
+                        &mut server,
+                        *client_id,
+                        &tick,
+                        ServerMessages::Chat {
+                            sender: sender.clone(),
+                            channel,
+                            text: text.clone(),
+                        },
+                    );
+                }
+            }
+        }
+    }
+}
+
+fn say_command(
+    mut invoked_reader: EventReader<CommandInvoked>,
+    mut output_writer: EventWriter<CommandOutput>,
+    mut server: ResMut<RenetServer>,
+    mut recorder: Option<ResMut<ReplayRecorder>>,
+    tick: Res<NetworkTick>,
+) {
+    for invoked in invoked_reader.iter() {
+        let text = match &invoked.command {
+            Command::Say { text } => text,
+            _ => continue,
+        };
+        let text = match clean_chat_message(text) {
+            Some(text) => text,
+            None => continue,
+        };
+
+        announce(&mut server, &tick, recorder.as_deref_mut(), text);
+        output_writer.send(CommandOutput::new(invoked.source, "Announced."));
+    }
+}
@@ -0,0 +1,257 @@
+use std::{
+    fs,
+    net::{IpAddr, Ipv4Addr, SocketAddr},
+    path::{Path, PathBuf},
+};
+
+use bevy::prelude::Resource;
+use clap::Parser;
+use serde::Deserialize;
+use spaaaace_shared::{
+    auth::DEFAULT_PRIVATE_KEY_PATH,
+    netsim::{LinkConditionArgs, LinkConditions},
+    SERVER_TICKRATE,
+};
+
+use crate::capture_point::MAPS;
+
+const DEFAULT_CONFIG_PATH: &str = "server.toml";
+
+/// Rewinding further than this lets players with bad connections hit ships that are
+/// long gone.
+const MAX_REWIND_MS: u32 = 1000;
+
+/// Command line arguments, these override the values from the config file.
+#[derive(Parser, Debug)]
+#[command(about = "Spaaaace dedicated server")]
+struct Args {
+    /// TOML file to read the settings from.
+    #[arg(long)]
+    config: Option<PathBuf>,
+    /// Address the server socket binds to.
+    #[arg(long)]
+    bind_address: Option<IpAddr>,
+    /// Address clients connect to, if it differs from the bind address.
+    #[arg(long)]
+    public_address: Option<IpAddr>,
+    #[arg(long)]
+    port: Option<u16>,
+    /// Name shown in the server browser.
+    #[arg(long)]
+    name: Option<String>,
+    /// Don't answer LAN discovery queries.
+    #[arg(long)]
+    hidden: bool,
+    #[arg(long)]
+    max_players: Option<usize>,
+    /// Clients that may watch without a ship, on top of `max_players`.
+    #[arg(long)]
+    max_spectators: Option<usize>,
+    /// Simulation ticks per second.
+    #[arg(long)]
+    tick_rate: Option<f32>,
+    #[arg(long)]
+    map: Option<String>,
+    /// Password clients log in with to run admin commands.
+    #[arg(long)]
+    admin_password: Option<String>,
+    /// Take console commands on this port of 127.0.0.1.
+    #[arg(long)]
+    rcon_port: Option<u16>,
+    /// Let clients connect without a connect token. This is the default while the
+    /// server only listens on a loopback address.
+    #[arg(long)]
+    unsecure: bool,
+    /// Hex encoded key used to verify connect tokens.
+    #[arg(long)]
+    private_key: Option<PathBuf>,
+    /// Seconds a disconnected player's ship is kept for them to reconnect to.
+    #[arg(long)]
+    session_grace_secs: Option<u32>,
+    /// How far back in milliseconds hits are checked against what the shooter saw.
+    #[arg(long)]
+    max_rewind_ms: Option<u32>,
+    #[command(flatten)]
+    network_conditions: LinkConditionArgs,
+    /// Record the match to this replay file.
+    #[arg(long)]
+    record: Option<PathBuf>,
+    /// Open a window showing the game, the server is headless otherwise. Needs a server
+    /// built with the `debug-view` feature.
+    #[arg(long)]
+    debug_view: bool,
+}
+
+#[derive(Resource, Deserialize, Debug, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServerSettings {
+    pub bind_address: IpAddr,
+    pub public_address: Option<IpAddr>,
+    pub port: u16,
+    pub name: String,
+    pub hidden: bool,
+    pub max_players: usize,
+    pub max_spectators: usize,
+    pub tick_rate: f32,
+    pub map: String,
+    pub admin_password: Option<String>,
+    pub rcon_port: Option<u16>,
+    /// Let clients connect without a connect token. Unset, only a server listening on a
+    /// loopback address is unsecure, so a local client works without a token, see
+    /// [`ServerSettings::is_unsecure`].
+    pub unsecure: Option<bool>,
+    pub private_key: PathBuf,
+    pub session_grace_secs: u32,
+    pub max_rewind_ms: u32,
+    /// Simulated bad network, for testing.
+    pub network_conditions: Option<LinkConditions>,
+    /// Replay file to record the match to.
+    pub record: Option<PathBuf>,
+    pub debug_view: bool,
+}
+
+impl Default for ServerSettings {
+    fn default() -> Self {
+        Self {
+            bind_address: IpAddr::V4(Ipv4Addr::LOCALHOST),
+            public_address: None,
+            port: 5000,
+            name: "Spaaaace server".to_string(),
+            hidden: false,
+            max_players: 64,
+            max_spectators: 8,
+            tick_rate: SERVER_TICKRATE,
+            map: "default".to_string(),
+            admin_password: None,
+            rcon_port: None,
+            unsecure: None,
+            private_key: PathBuf::from(DEFAULT_PRIVATE_KEY_PATH),
+            session_grace_secs: 30,
+            max_rewind_ms: 200,
+            network_conditions: None,
+            record: None,
+            debug_view: false,
+        }
+    }
+}
+
+impl ServerSettings {
+    /// Reads the config file and applies the command line arguments on top of it.
+    ///
+    /// A missing `server.toml` is fine, a missing file passed with `--config` is not.
+    pub fn load() -> Result<Self, String> {
+        let args = Args::parse();
+
+        let mut settings = match &args.config {
+            Some(path) => read_config(path)?,
+            None => {
+                let path = PathBuf::from(DEFAULT_CONFIG_PATH);
+                if path.exists() {
+                    read_config(&path)?
+                } else {
+                    ServerSettings::default()
+                }
+            }
+        };
+
+        if let Some(bind_address) = args.bind_address {
+            settings.bind_address = bind_address;
+        }
+        if let Some(public_address) = args.public_address {
+            settings.public_address = Some(public_address);
+        }
+        if let Some(port) = args.port {
+            settings.port = port;
+        }
+        if let Some(name) = args.name {
+            settings.name = name;
+        }
+        if let Some(max_players) = args.max_players {
+            settings.max_players = max_players;
+        }
+        if let Some(max_spectators) = args.max_spectators {
+            settings.max_spectators = max_spectators;
+        }
+        if let Some(tick_rate) = args.tick_rate {
+            settings.tick_rate = tick_rate;
+        }
+        if let Some(map) = args.map {
+            settings.map = map;
+        }
+        if let Some(admin_password) = args.admin_password {
+            settings.admin_password = Some(admin_password);
+        }
+        if let Some(rcon_port) = args.rcon_port {
+            settings.rcon_port = Some(rcon_port);
+        }
+        if let Some(private_key) = args.private_key {
+            settings.private_key = private_key;
+        }
+        if let Some(session_grace_secs) = args.session_grace_secs {
+            settings.session_grace_secs = session_grace_secs;
+        }
+        if let Some(max_rewind_ms) = args.max_rewind_ms {
+            settings.max_rewind_ms = max_rewind_ms;
+        }
+        args.network_conditions
+            .apply(&mut settings.network_conditions);
+        if let Some(record) = args.record {
+            settings.record = Some(record);
+        }
+        settings.hidden |= args.hidden;
+        if args.unsecure {
+            settings.unsecure = Some(true);
+        }
+        settings.debug_view |= args.debug_view;
+
+        settings.validate()?;
+        Ok(settings)
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        if self.max_players == 0 {
+            return Err("max_players must be at least 1".to_string());
+        }
+        if self.tick_rate.is_nan() || self.tick_rate <= 0.0 {
+            return Err("tick_rate must be positive".to_string());
+        }
+        if self.debug_view && !cfg!(feature = "debug-view") {
+            return Err("debug_view needs a server built with the debug-view feature".to_string());
+        }
+        if self.max_rewind_ms > MAX_REWIND_MS {
+            return Err(format!("max_rewind_ms can be at most {}", MAX_REWIND_MS));
+        }
+        if let Some(conditions) = &self.network_conditions {
+            conditions.validate()?;
+        }
+        if !MAPS.contains(&self.map.as_str()) {
+            return Err(format!(
+                "Unknown map {}, available maps are: {}",
+                self.map,
+                MAPS.join(", ")
+            ));
+        }
+        Ok(())
+    }
+
+    pub fn is_unsecure(&self) -> bool {
+        self.unsecure
+            .unwrap_or_else(|| self.bind_address.is_loopback())
+    }
+
+    pub fn bind_addr(&self) -> SocketAddr {
+        SocketAddr::new(self.bind_address, self.port)
+    }
+
+    /// The address clients are told to connect to, connect tokens are only valid for
+    /// this address.
+    pub fn public_addr(&self) -> SocketAddr {
+        SocketAddr::new(self.public_address.unwrap_or(self.bind_address), self.port)
+    }
+}
+
+fn read_config(path: &Path) -> Result<ServerSettings, String> {
+    let contents = fs::read_to_string(path)
+        .map_err(|error| format!("Could not read {}: {}", path.display(), error))?;
+    toml::from_str(&contents).map_err(|error| format!("Invalid {}: {}", path.display(), error))
+}
@@ -0,0 +1,272 @@
+use bevy::prelude::{
+    App, Commands, DespawnRecursiveExt, Entity, EventReader, EventWriter, Plugin, Query, Res,
+    ResMut, With,
+};
+use bevy_rapier3d::prelude::{RapierConfiguration, TimestepMode};
+use bevy_renet::renet::RenetServer;
+use spaaaace_shared::{
+    asteroid::{random_asteroid_transform, spawn_asteroid, Asteroid, ASTEROID_COUNT},
+    broadcast_server_message,
+    command::Command,
+    replay::ReplayRecorder,
+    tick::NetworkTick,
+    weapons::bullet::Bullet,
+    NetworkIdProvider, ServerMessages,
+};
+
+use crate::{
+    capture_point::MAPS, config::ServerSettings, handshake::HandshakeSettings, session::Sessions,
+};
+
+use super::{CommandAppExt, CommandInvoked, CommandOutput, Console, Permission};
+
+/// Tick rates an admin can switch to, the clients can't keep up outside of these.
+const MIN_TICK_RATE: f32 = 10.0;
+pub const MAX_TICK_RATE: f32 = 128.0;
+
+/// The admin commands.
+pub struct CommandsPlugin;
+
+impl Plugin for CommandsPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<RestartMatch>()
+            .add_console_command("players", Permission::Admin)
+            .add_console_command("kick", Permission::Admin)
+            .add_console_command("ban", Permission::Admin)
+            .add_console_command("map", Permission::Admin)
+            .add_console_command("restart", Permission::Admin)
+            .add_console_command("tickrate", Permission::Admin)
+            .add_console_command("spawn_asteroid", Permission::Admin)
+            .add_system(players_command)
+            .add_system(kick_command)
+            .add_system(map_command)
+            .add_system(tickrate_command)
+            .add_system(spawn_asteroid_command)
+            .add_system(restart_match);
+    }
+}
+
+/// Sent to start the match over on the current map. Ships go back to the spawn, capture
+/// points are reset and the asteroid field is regenerated.
+pub struct RestartMatch;
+
+fn players_command(
+    mut invoked_reader: EventReader<CommandInvoked>,
+    mut output_writer: EventWriter<CommandOutput>,
+    console: Res<Console>,
+) {
+    for invoked in invoked_reader
+        .iter()
+        .filter(|invoked| invoked.command == Command::Players)
+    {
+        let mut clients: Vec<_> = console.clients.iter().collect();
+        clients.sort_by_key(|(client_id, _)| **client_id);
+
+        let mut text = format!("{} connected:", clients.len());
+        for (client_id, client) in clients {
+            text += &format!(
+                "\n  {} {} (player {}{})",
+                client_id,
+                client.name,
+                client.player_id,
+                if client.spectator { ", spectating" } else { "" }
+            );
+        }
+        output_writer.send(CommandOutput::new(invoked.source, text));
+    }
+}
+
+fn kick_command(
+    mut invoked_reader: EventReader<CommandInvoked>,
+    mut output_writer: EventWriter<CommandOutput>,
+    mut server: ResMut<RenetServer>,
+    mut sessions: ResMut<Sessions>,
+    mut handshake_settings: ResMut<HandshakeSettings>,
+    settings: Res<ServerSettings>,
+) {
+    for invoked in invoked_reader.iter() {
+        let (client_id, banned) = match invoked.command {
+            Command::Kick { client_id } => (client_id, false),
+            Command::Ban { client_id } => (client_id, true),
+            _ => continue,
+        };
+
+        // Unsecure clients pick their own id, a banned one just picks another.
+        if banned && settings.is_unsecure() {
+            output_writer.send(CommandOutput::new(
+                invoked.source,
+                "Bans need a secure server, use kick instead.",
+            ));
+            continue;
+        }
+
+        // Banned ids are checked in the handshake, so they can be banned while away.
+        if banned {
+            handshake_settings.banned_ids.insert(client_id);
+        }
+        if !server.is_client_connected(client_id) {
+            let text = match banned {
+                true => format!(
+                    "Banned {} until the server restarts, they are not connected.",
+                    client_id
+                ),
+                false => format!("{} is not connected.", client_id),
+            };
+            output_writer.send(CommandOutput::new(invoked.source, text));
+            continue;
+        }
+
+        // Kicked players don't get to come back to their ship.
+        sessions.end(client_id);
+        server.disconnect(client_id);
+        let text = match banned {
+            true => format!("Banned {} until the server restarts.", client_id),
+            false => format!("Kicked {}.", client_id),
+        };
+        println!("{}", text);
+        output_writer.send(CommandOutput::new(invoked.source, text));
+    }
+}
+
+fn map_command(
+    mut invoked_reader: EventReader<CommandInvoked>,
+    mut output_writer: EventWriter<CommandOutput>,
+    mut restart_writer: EventWriter<RestartMatch>,
+    mut settings: ResMut<ServerSettings>,
+) {
+    for invoked in invoked_reader.iter() {
+        match &invoked.command {
+            Command::Map { name } => {
+                if !MAPS.contains(&name.as_str()) {
+                    output_writer.send(CommandOutput::new(
+                        invoked.source,
+                        format!(
+                            "Unknown map {}, available maps are: {}",
+                            name,
+                            MAPS.join(", ")
+                        ),
+                    ));
+                    continue;
+                }
+                println!("Changing the map to {}.", name);
+                settings.map = name.clone();
+                restart_writer.send(RestartMatch);
+                output_writer.send(CommandOutput::new(
+                    invoked.source,
+                    format!("Changed the map to {}.", name),
+                ));
+            }
+            Command::Restart => {
+                println!("Restarting the match.");
+                restart_writer.send(RestartMatch);
+                output_writer.send(CommandOutput::new(invoked.source, "Restarted the match."));
+            }
+            _ => (),
+        }
+    }
+}
+
+fn tickrate_command(
+    mut invoked_reader: EventReader<CommandInvoked>,
+    mut output_writer: EventWriter<CommandOutput>,
+    mut tick: ResMut<NetworkTick>,
+    mut rapier_config: ResMut<RapierConfiguration>,
+    mut server: ResMut<RenetServer>,
+    mut recorder: Option<ResMut<ReplayRecorder>>,
+) {
+    for invoked in invoked_reader.iter() {
+        let tick_rate = match invoked.command {
+            Command::TickRate { tick_rate } => tick_rate,
+            _ => continue,
+        };
+        if !(MIN_TICK_RATE..=MAX_TICK_RATE).contains(&tick_rate) {
+            output_writer.send(CommandOutput::new(
+                invoked.source,
+                format!(
+                    "Expected a tick rate from {} to {}.",
+                    MIN_TICK_RATE, MAX_TICK_RATE
+                ),
+            ));
+            continue;
+        }
+
+        tick.set_rate(tick_rate);
+        rapier_config.timestep_mode = TimestepMode::Fixed {
+            dt: tick.delta_seconds(),
+            substeps: 1,
+        };
+        broadcast_server_message(
+            &mut server,
+            &tick,
+            recorder.as_deref_mut(),
+            ServerMessages::TickRateChanged {
+                tick_rate,
+                epoch_tick: tick.epoch_tick,
+                epoch_seconds: tick.epoch_seconds,
+            },
+        );
+        println!("Changed the tick rate to {}.", tick_rate);
+        output_writer.send(CommandOutput::new(
+            invoked.source,
+            format!("Changed the tick rate to {}.", tick_rate),
+        ));
+    }
+}
+
+fn spawn_asteroid_command(
+    mut invoked_reader: EventReader<CommandInvoked>,
+    mut output_writer: EventWriter<CommandOutput>,
+    mut commands: Commands,
+    mut id_provider: ResMut<NetworkIdProvider>,
+) {
+    for invoked in invoked_reader.iter() {
+        let position = match invoked.command {
+            Command::SpawnAsteroid { position } => position,
+            _ => continue,
+        };
+        // Clients send commands already parsed, so this can't rely on the parser.
+        if position.is_some_and(|position| !position.is_finite()) {
+            output_writer.send(CommandOutput::new(
+                invoked.source,
+                "Expected a finite position.",
+            ));
+            continue;
+        }
+
+        let mut transform = random_asteroid_transform(&mut rand::thread_rng());
+        if let Some(position) = position {
+            transform.translation = position;
+        }
+        spawn_asteroid(&mut commands, &mut id_provider, transform);
+        output_writer.send(CommandOutput::new(
+            invoked.source,
+            format!("Spawned an asteroid at {}.", transform.translation),
+        ));
+    }
+}
+
+/// Replaces the asteroid field and clears the bullets in flight. Ships and capture
+/// points are reset by their own plugins.
+fn restart_match(
+    mut restart_reader: EventReader<RestartMatch>,
+    mut commands: Commands,
+    mut id_provider: ResMut<NetworkIdProvider>,
+    query: Query<Entity, With<Asteroid>>,
+    bullet_query: Query<Entity, With<Bullet>>,
+) {
+    if restart_reader.iter().count() == 0 {
+        return;
+    }
+
+    for entity in query.iter().chain(bullet_query.iter()) {
+        commands.entity(entity).despawn_recursive();
+    }
+    let mut rng = rand::thread_rng();
+    for _ in 0..ASTEROID_COUNT {
+        spawn_asteroid(
+            &mut commands,
+            &mut id_provider,
+            random_asteroid_transform(&mut rng),
+        );
+    }
+}
@@ -0,0 +1,450 @@
+use std::{
+    collections::{HashMap, HashSet},
+    io::{self, BufRead, BufReader, Write},
+    net::{Ipv4Addr, TcpListener, TcpStream},
+    sync::{
+        mpsc::{self, Receiver, Sender},
+        Arc, Mutex,
+    },
+    thread,
+};
+
+use bevy::{
+    prelude::{App, EventReader, EventWriter, Plugin, Res, ResMut, Resource},
+    time::Time,
+};
+use bevy_renet::renet::{RenetServer, ServerEvent};
+use spaaaace_shared::{
+    command::{find_command, Command, CommandError, COMMANDS},
+    handshake::ClientAccepted,
+    send_server_message,
+    tick::NetworkTick,
+    ClientMessages, ServerMessages,
+};
+
+use crate::ClientEvent;
+
+use self::commands::CommandsPlugin;
+
+pub mod commands;
+
+/// Connection id of stdin, socket connections count up from 1.
+const STDIN_CONNECTION: u64 = 0;
+
+/// Wrong admin passwords a client may send before it is locked out for a while.
+const MAX_LOGIN_FAILURES: u32 = 3;
+const LOGIN_LOCKOUT_SECS: f64 = 30.0;
+
+/// Runs commands sent by clients, typed on stdin or sent to a local TCP socket.
+pub struct ConsolePlugin {
+    /// Clients become admins by sending `login` with this password. Without one only
+    /// the server console can run admin commands.
+    pub admin_password: Option<String>,
+    /// Port on 127.0.0.1 that takes commands, one per line.
+    pub rcon_port: Option<u16>,
+}
+
+impl Plugin for ConsolePlugin {
+    fn build(&self, app: &mut App) {
+        let (sender, receiver) = mpsc::channel();
+        let connections = Arc::new(Mutex::new(HashMap::new()));
+
+        read_stdin(sender.clone());
+        if let Some(port) = self.rcon_port {
+            if let Err(error) = listen(
+                port,
+                self.admin_password.clone(),
+                sender,
+                connections.clone(),
+            ) {
+                println!(
+                    "Could not open the remote console on port {}: {}",
+                    port, error
+                );
+            }
+        }
+
+        app.init_resource::<CommandRegistry>()
+            .insert_resource(Console {
+                admin_password: self.admin_password.clone(),
+                admins: HashSet::new(),
+                login_failures: HashMap::new(),
+                clients: HashMap::new(),
+                lines: Mutex::new(receiver),
+                connections,
+            })
+            .add_event::<CommandInvoked>()
+            .add_event::<CommandOutput>()
+            .add_system(track_clients)
+            .add_system(dispatch_commands)
+            .add_system(send_command_output)
+            .add_plugin(CommandsPlugin);
+    }
+}
+
+/// Who may run a command, each level may run the commands of the levels below.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Permission {
+    Player,
+    Admin,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CommandSource {
+    /// A connected client, by client id.
+    Client(u64),
+    /// Stdin or a remote console connection, these are always admins.
+    Console(u64),
+}
+
+/// Permission each command needs, keyed by its name in [`COMMANDS`]. Commands nobody
+/// registered a handler for are rejected.
+#[derive(Resource, Default)]
+pub struct CommandRegistry {
+    commands: HashMap<&'static str, Permission>,
+}
+
+pub trait CommandAppExt {
+    /// Registers a command. Its handler reads [`CommandInvoked`] events for it, they are
+    /// only sent once the source is known to have the permission.
+    fn add_console_command(&mut self, name: &'static str, permission: Permission) -> &mut Self;
+}
+
+impl CommandAppExt for App {
+    fn add_console_command(&mut self, name: &'static str, permission: Permission) -> &mut Self {
+        if find_command(name).is_none() {
+            panic!("Command {} is not one of the shared commands.", name);
+        }
+        let mut registry = self
+            .world
+            .get_resource_or_insert_with(CommandRegistry::default);
+        if registry.commands.insert(name, permission).is_some() {
+            panic!("Command {} is registered twice.", name);
+        }
+        self
+    }
+}
+
+/// A command its source is allowed to run.
+pub struct CommandInvoked {
+    pub source: CommandSource,
+    pub command: Command,
+}
+
+/// Text sent back to whoever ran a command.
+pub struct CommandOutput {
+    pub source: CommandSource,
+    pub text: String,
+}
+
+impl CommandOutput {
+    pub fn new(source: CommandSource, text: impl Into<String>) -> Self {
+        Self {
+            source,
+            text: text.into(),
+        }
+    }
+}
+
+/// A client as the console knows it.
+pub struct ConsoleClient {
+    pub name: String,
+    pub player_id: u64,
+    pub spectator: bool,
+}
+
+#[derive(Default)]
+struct LoginFailures {
+    count: u32,
+    locked_until: f64,
+}
+
+#[derive(Resource)]
+pub struct Console {
+    admin_password: Option<String>,
+    /// Clients that logged in with the admin password.
+    admins: HashSet<u64>,
+    login_failures: HashMap<u64, LoginFailures>,
+    pub clients: HashMap<u64, ConsoleClient>,
+    /// Lines read from stdin and the remote console, with their connection.
+    lines: Mutex<Receiver<(u64, String)>>,
+    /// Remote console connections, to write the output of their commands to.
+    connections: Arc<Mutex<HashMap<u64, TcpStream>>>,
+}
+
+impl Console {
+    fn permission(&self, source: CommandSource) -> Permission {
+        match source {
+            CommandSource::Client(client_id) if !self.admins.contains(&client_id) => {
+                Permission::Player
+            }
+            _ => Permission::Admin,
+        }
+    }
+}
+
+fn read_stdin(sender: Sender<(u64, String)>) {
+    let spawned = thread::Builder::new()
+        .name("console stdin".to_string())
+        .spawn(move || {
+            for line in io::stdin().lock().lines() {
+                let line = match line {
+                    Ok(line) => line,
+                    Err(_) => return,
+                };
+                if sender.send((STDIN_CONNECTION, line)).is_err() {
+                    return;
+                }
+            }
+        });
+    if let Err(error) = spawned {
+        println!("Could not read commands from stdin: {}", error);
+    }
+}
+
+/// Accepts remote console connections on 127.0.0.1. With an admin password set, the
+/// first line of every connection has to be the password.
+fn listen(
+    port: u16,
+    password: Option<String>,
+    sender: Sender<(u64, String)>,
+    connections: Arc<Mutex<HashMap<u64, TcpStream>>>,
+) -> io::Result<()> {
+    let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, port))?;
+    println!("Remote console listening on {}.", listener.local_addr()?);
+
+    thread::Builder::new()
+        .name("remote console".to_string())
+        .spawn(move || {
+            for (connection, stream) in (STDIN_CONNECTION + 1..).zip(listener.incoming()) {
+                let stream = match stream {
+                    Ok(stream) => stream,
+                    Err(_) => continue,
+                };
+                let password = password.clone();
+                let sender = sender.clone();
+                let connections = connections.clone();
+                let spawned = thread::Builder::new()
+                    .name(format!("remote console {}", connection))
+                    .spawn(move || {
+                        serve_connection(connection, stream, password, sender, connections)
+                    });
+                if let Err(error) = spawned {
+                    println!("Could not serve remote console connection: {}", error);
+                }
+            }
+        })?;
+    Ok(())
+}
+
+fn serve_connection(
+    connection: u64,
+    mut stream: TcpStream,
+    password: Option<String>,
+    sender: Sender<(u64, String)>,
+    connections: Arc<Mutex<HashMap<u64, TcpStream>>>,
+) {
+    let mut lines = match stream.try_clone() {
+        Ok(reader) => BufReader::new(reader).lines(),
+        Err(_) => return,
+    };
+
+    if let Some(password) = password {
+        let _ = write!(stream, "Password: ");
+        match lines.next() {
+            Some(Ok(line)) if line.trim() == password => (),
+            _ => {
+                let _ = writeln!(stream, "Wrong password.");
+                return;
+            }
+        }
+    }
+
+    match stream.try_clone() {
+        Ok(writer) => connections.lock().unwrap().insert(connection, writer),
+        Err(_) => return,
+    };
+    let _ = writeln!(stream, "Logged in, type help for the commands.");
+
+    for line in lines {
+        let line = match line {
+            Ok(line) => line,
+            Err(_) => break,
+        };
+        if sender.send((connection, line)).is_err() {
+            break;
+        }
+    }
+    connections.lock().unwrap().remove(&connection);
+}
+
+fn track_clients(
+    mut accepted_reader: EventReader<ClientAccepted>,
+    mut event_reader: EventReader<ServerEvent>,
+    mut console: ResMut<Console>,
+) {
+    for event in accepted_reader.iter() {
+        console.clients.insert(
+            event.client_id,
+            ConsoleClient {
+                name: event.name.clone(),
+                player_id: event.player_id,
+                spectator: event.spectator,
+            },
+        );
+    }
+
+    for event in event_reader.iter() {
+        if let ServerEvent::ClientDisconnected(id) = event {
+            console.clients.remove(id);
+            console.admins.remove(id);
+            console.login_failures.remove(id);
+        }
+    }
+}
+
+fn dispatch_commands(
+    mut client_message_event_reader: EventReader<ClientEvent>,
+    mut invoked_writer: EventWriter<CommandInvoked>,
+    mut output_writer: EventWriter<CommandOutput>,
+    mut console: ResMut<Console>,
+    registry: Res<CommandRegistry>,
+    time: Res<Time>,
+) {
+    let mut commands: Vec<(CommandSource, Command)> = Vec::new();
+    for (connection, line) in console.lines.lock().unwrap().try_iter() {
+        let source = CommandSource::Console(connection);
+        match Command::parse(&line) {
+            Ok(command) => commands.push((source, command)),
+            Err(CommandError::Empty) => (),
+            Err(error) => output_writer.send(CommandOutput::new(source, error.to_string())),
+        }
+    }
+    for event in client_message_event_reader.iter() {
+        match &event.message {
+            // Only clients that made it through the handshake may run commands.
+            ClientMessages::Command { command }
+                if console.clients.contains_key(&event.client_id) =>
+            {
+                commands.push((CommandSource::Client(event.client_id), command.clone()))
+            }
+            _ => (),
+        }
+    }
+
+    for (source, command) in commands {
+        let permission = console.permission(source);
+
+        match command {
+            Command::Help => {
+                let mut text = String::from("Commands:");
+                for spec in COMMANDS {
+                    let allowed = match spec.name {
+                        "help" => true,
+                        "login" => {
+                            permission < Permission::Admin && console.admin_password.is_some()
+                        }
+                        name => registry
+                            .commands
+                            .get(name)
+                            .is_some_and(|required| *required <= permission),
+                    };
+                    if allowed {
+                        text += &format!("\n  {} {} - {}", spec.name, spec.usage, spec.help);
+                    }
+                }
+                output_writer.send(CommandOutput::new(source, text));
+            }
+            Command::Login { password: attempt } => {
+                let client_id = match source {
+                    CommandSource::Client(client_id) => client_id,
+                    CommandSource::Console(_) => {
+                        output_writer
+                            .send(CommandOutput::new(source, "The console is always admin."));
+                        continue;
+                    }
+                };
+                let now = time.elapsed_seconds_f64();
+                let locked = console
+                    .login_failures
+                    .get(&client_id)
+                    .is_some_and(|failures| failures.locked_until > now);
+                if locked {
+                    output_writer.send(CommandOutput::new(
+                        source,
+                        "Too many wrong passwords, try again later.",
+                    ));
+                    continue;
+                }
+
+                let correct = match &console.admin_password {
+                    Some(password) => *password == attempt,
+                    None => false,
+                };
+                if correct {
+                    println!("Player {} logged in as admin.", client_id);
+                    console.admins.insert(client_id);
+                    console.login_failures.remove(&client_id);
+                    output_writer.send(CommandOutput::new(source, "Logged in as admin."));
+                } else {
+                    println!("Player {} failed to log in as admin.", client_id);
+                    output_writer.send(CommandOutput::new(source, "Wrong password."));
+                    let failures = console.login_failures.entry(client_id).or_default();
+                    failures.count += 1;
+                    if failures.count >= MAX_LOGIN_FAILURES {
+                        println!(
+                            "Player {} can't log in for {} seconds.",
+                            client_id, LOGIN_LOCKOUT_SECS
+                        );
+                        failures.count = 0;
+                        failures.locked_until = now + LOGIN_LOCKOUT_SECS;
+                    }
+                }
+            }
+            command => match registry.commands.get(command.name()) {
+                Some(required) if *required <= permission => {
+                    if let CommandSource::Client(client_id) = source {
+                        if *required == Permission::Admin {
+                            println!("Player {} ran: {}", client_id, command);
+                        }
+                    }
+                    invoked_writer.send(CommandInvoked { source, command });
+                }
+                Some(_) => output_writer.send(CommandOutput::new(
+                    source,
+                    format!("{} needs admin rights, log in first.", command.name()),
+                )),
+                None => output_writer.send(CommandOutput::new(
+                    source,
+                    format!("This server does not support {}.", command.name()),
+                )),
+            },
+        }
+    }
+}
+
+fn send_command_output(
+    mut output_reader: EventReader<CommandOutput>,
+    mut server: ResMut<RenetServer>,
+    console: Res<Console>,
+    tick: Res<NetworkTick>,
+) {
+    for output in output_reader.iter() {
+        match output.source {
+            CommandSource::Client(client_id) => send_server_message(
+                &mut server,
+                client_id,
+                &tick,
+                ServerMessages::CommandOutput {
+                    text: output.text.clone(),
+                },
+            ),
+            CommandSource::Console(STDIN_CONNECTION) => println!("{}", output.text),
+            CommandSource::Console(connection) => {
+                if let Some(stream) = console.connections.lock().unwrap().get_mut(&connection) {
+                    let _ = writeln!(stream, "{}", output.text);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,149 @@
+use bevy::{
+    asset::AssetPlugin,
+    prelude::{
+        default, shape, Added, App, AssetServer, Assets, BuildChildren, Camera3d, Camera3dBundle,
+        Color, Commands, Entity, Mesh, PbrBundle, Plugin, PluginGroup, PointLight,
+        PointLightBundle, Query, Res, ResMut, Scene, StandardMaterial, Transform, Vec3, With,
+        Without,
+    },
+    scene::SceneBundle,
+    window::{PresentMode, WindowDescriptor, WindowPlugin},
+    DefaultPlugins,
+};
+use bevy_inspector_egui::quick::WorldInspectorPlugin;
+use bevy_rapier3d::render::RapierDebugRenderPlugin;
+use spaaaace_shared::{
+    player::Player,
+    ships::{PlayerShipType, ShipTypes},
+};
+
+use crate::capture_point::capture_point::CaptureSphere;
+
+/// Opens a window showing the server's view of the game. Needs X11 and a GPU, so it is
+/// only built with the `debug-view` feature and added with `--debug-view`.
+pub struct DebugViewPlugin;
+
+impl Plugin for DebugViewPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugins(
+            DefaultPlugins
+                .set(WindowPlugin {
+                    window: WindowDescriptor {
+                        title: "Spaaace Server".to_string(),
+                        width: 1280.,
+                        height: 720.,
+                        present_mode: PresentMode::AutoVsync,
+                        ..default()
+                    },
+                    ..default()
+                })
+                .set(AssetPlugin {
+                    watch_for_changes: true,
+                    ..default()
+                }),
+        )
+        .add_plugin(RapierDebugRenderPlugin::default())
+        .add_plugin(WorldInspectorPlugin)
+        .add_startup_system(setup)
+        .add_system(camera_follow_players)
+        .add_system(attach_ship_models)
+        .add_system(attach_capture_sphere_meshes);
+    }
+}
+
+fn setup(mut commands: Commands) {
+    // light
+    commands.spawn(PointLightBundle {
+        point_light: PointLight {
+            intensity: 1500.0,
+            shadows_enabled: true,
+            ..default()
+        },
+        transform: Transform::from_xyz(4.0, 8.0, 4.0),
+        ..default()
+    });
+    // camera
+    commands.spawn(Camera3dBundle {
+        transform: Transform::from_xyz(-2.0, 2.5, 5.0).looking_at(Vec3::ZERO, Vec3::Y),
+        ..default()
+    });
+}
+
+/// The simulation doesn't need the ship models, they are only loaded to be looked at.
+fn attach_ship_models(
+    mut commands: Commands,
+    query: Query<(Entity, &PlayerShipType), Added<PlayerShipType>>,
+    ship_types: Res<ShipTypes>,
+    ass: Res<AssetServer>,
+) {
+    for (entity, ship_type) in query.iter() {
+        let model_name = match ship_types.get(&ship_type.0) {
+            Some(ship) => &ship.model,
+            None => continue,
+        };
+        let model = commands
+            .spawn(SceneBundle {
+                scene: ass
+                    .load::<Scene, _>(format!("../../shared/assets/ships/{}#Scene0", model_name)),
+                ..default()
+            })
+            .id();
+        commands.entity(entity).add_child(model);
+    }
+}
+
+fn attach_capture_sphere_meshes(
+    mut commands: Commands,
+    query: Query<Entity, Added<CaptureSphere>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    for entity in query.iter() {
+        let sphere = commands
+            .spawn(PbrBundle {
+                mesh: meshes.add(Mesh::from(shape::UVSphere {
+                    radius: 1.0,
+                    ..default()
+                })),
+                material: materials.add(StandardMaterial {
+                    base_color: Color::GREEN,
+                    unlit: true,
+                    ..default()
+                }),
+                ..default()
+            })
+            .id();
+        commands.entity(entity).add_child(sphere);
+    }
+}
+
+fn camera_follow_players(
+    mut query_cam: Query<&mut Transform, (With<Camera3d>, Without<Player>)>,
+    query_players: Query<&Transform, With<Player>>,
+) {
+    if query_players.is_empty() {
+        return;
+    }
+    let mut player_count = 0;
+    let mut avg = Vec3::ZERO;
+    for player_transform in query_players.iter() {
+        avg += player_transform.translation;
+        player_count += 1;
+    }
+
+    avg /= Vec3::splat(player_count as f32);
+
+    let mut max_dist_from_avg: f32 = 0.0;
+
+    for player_transform in query_players.iter() {
+        let dist = player_transform.translation.distance(avg);
+        max_dist_from_avg = max_dist_from_avg.max(dist);
+    }
+
+    for mut transform in query_cam.iter_mut() {
+        transform.look_at(avg, Vec3::Y);
+
+        let back = transform.back();
+        transform.translation = avg + back * (max_dist_from_avg * 3.0 + 80.);
+    }
+}
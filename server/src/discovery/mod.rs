@@ -0,0 +1,115 @@
+use std::net::{IpAddr, Ipv4Addr, SocketAddr, UdpSocket};
+
+use bevy::prelude::{App, Plugin, Res, Resource};
+use spaaaace_shared::{
+    discovery::{DiscoveryPacket, ServerInfo, DISCOVERY_PORT},
+    handshake::PROTOCOL_VERSION,
+    Lobby,
+};
+
+use crate::config::ServerSettings;
+
+/// Queries are tiny, anything larger is not one.
+const MAX_QUERY_BYTES: usize = 256;
+
+/// Replies sent per frame at most, the rest of the queries wait for the next one.
+const MAX_REPLIES_PER_FRAME: usize = 16;
+
+/// Answers LAN discovery queries so clients can list the server in their browser.
+pub struct DiscoveryPlugin;
+
+impl Plugin for DiscoveryPlugin {
+    fn build(&self, app: &mut App) {
+        // Broadcasts only reach sockets bound to the unspecified address.
+        let bind_addr = SocketAddr::new(Ipv4Addr::UNSPECIFIED.into(), DISCOVERY_PORT);
+        // Only one server per machine can be discovered, the others still run fine.
+        let socket = match UdpSocket::bind(bind_addr).and_then(|socket| {
+            socket.set_nonblocking(true)?;
+            Ok(socket)
+        }) {
+            Ok(socket) => socket,
+            Err(error) => {
+                println!(
+                    "LAN discovery is off, could not bind {}: {}",
+                    bind_addr, error
+                );
+                return;
+            }
+        };
+        println!("Answering LAN discovery queries on {}.", bind_addr);
+
+        app.insert_resource(DiscoverySocket(socket))
+            .add_system(answer_discovery_queries);
+    }
+}
+
+#[derive(Resource)]
+struct DiscoverySocket(UdpSocket);
+
+fn answer_discovery_queries(
+    socket: Res<DiscoverySocket>,
+    settings: Res<ServerSettings>,
+    lobby: Res<Lobby>,
+) {
+    let mut buffer = [0; MAX_QUERY_BYTES];
+    let mut replies = 0;
+    while replies < MAX_REPLIES_PER_FRAME {
+        let (len, sender) = match socket.0.recv_from(&mut buffer) {
+            Ok(received) => received,
+            Err(_) => break,
+        };
+        if !is_lan_address(sender.ip()) {
+            continue;
+        }
+        let sent_at = match DiscoveryPacket::decode(&buffer[..len]) {
+            Some(DiscoveryPacket::Query { sent_at }) => sent_at,
+            _ => continue,
+        };
+
+        let reply = DiscoveryPacket::Reply {
+            sent_at,
+            info: ServerInfo {
+                name: settings.name.clone(),
+                map: settings.map.clone(),
+                players: lobby.players.len() as u32,
+                max_players: settings.max_players as u32,
+                protocol_version: PROTOCOL_VERSION,
+                port: settings.public_addr().port(),
+            },
+        };
+        // Nothing to do about a reply that can't be sent, the client asks again.
+        let _ = socket.0.send_to(&reply.encode(), sender);
+        replies += 1;
+    }
+}
+
+/// Replies are larger than queries, so answering anyone would let spoofed queries use
+/// the server as an amplifier. Discovery is only for the LAN anyway.
+fn is_lan_address(address: IpAddr) -> bool {
+    match address {
+        IpAddr::V4(ip) => ip.is_private() || ip.is_link_local() || ip.is_loopback(),
+        IpAddr::V6(ip) => ip.is_loopback(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_lan_addresses_are_answered() {
+        for address in [
+            "192.168.1.20",
+            "10.0.0.5",
+            "172.16.3.4",
+            "169.254.10.1",
+            "127.0.0.1",
+            "::1",
+        ] {
+            assert!(is_lan_address(address.parse().unwrap()), "{}", address);
+        }
+        for address in ["8.8.8.8", "172.32.0.1", "100.64.0.1", "2001:db8::1"] {
+            assert!(!is_lan_address(address.parse().unwrap()), "{}", address);
+        }
+    }
+}
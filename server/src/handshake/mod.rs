@@ -0,0 +1,382 @@
+use std::collections::{HashMap, HashSet};
+
+use bevy::{
+    prelude::{App, EventReader, EventWriter, Plugin, Res, ResMut, Resource},
+    time::Time,
+};
+use bevy_renet::renet::{RenetServer, ServerEvent};
+use spaaaace_shared::{
+    handshake::{is_valid_name, ClientAccepted, ConnectRejectReason, BUILD_HASH, PROTOCOL_VERSION},
+    send_server_message,
+    ships::ShipTypes,
+    tick::NetworkTick,
+    ClientMessages, NetworkIdProvider, ServerMessages,
+};
+
+use crate::{session::Sessions, ClientEvent};
+
+/// Seconds a rejected client is kept connected so the reason reaches it.
+const REJECT_DISCONNECT_DELAY: f64 = 1.0;
+
+#[derive(Resource)]
+pub struct HandshakeSettings {
+    pub max_players: usize,
+    pub max_spectators: usize,
+    pub banned_ids: HashSet<u64>,
+}
+
+impl Default for HandshakeSettings {
+    fn default() -> Self {
+        Self {
+            max_players: 16,
+            max_spectators: 8,
+            banned_ids: HashSet::new(),
+        }
+    }
+}
+
+#[derive(Resource, Default)]
+struct HandshakeState {
+    accepted: HashSet<u64>,
+    /// The accepted clients that are spectating, with their player ids.
+    spectators: HashMap<u64, u64>,
+    /// Rejected clients and the time they should be disconnected at.
+    pending_disconnects: Vec<(u64, f64)>,
+}
+
+/// Raised instead of a [`ClientEvent`] for a hello from another protocol version, whose
+/// other fields can't be decoded.
+pub struct OutdatedHello {
+    pub client_id: u64,
+    pub protocol_version: u32,
+}
+
+pub struct HandshakePlugin;
+
+impl Plugin for HandshakePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<HandshakeSettings>()
+            .init_resource::<HandshakeState>()
+            .add_event::<ClientAccepted>()
+            .add_event::<OutdatedHello>()
+            .add_system(handle_hello)
+            .add_system(reject_outdated_hellos)
+            .add_system(on_client_disconnected)
+            .add_system(disconnect_rejected);
+    }
+}
+
+fn check_hello(
+    settings: &HandshakeSettings,
+    client_id: u64,
+    protocol_version: u32,
+    known_ship_type: bool,
+    name: &str,
+    spectator: bool,
+    has_free_slot: bool,
+) -> Result<(), ConnectRejectReason> {
+    if protocol_version != PROTOCOL_VERSION {
+        return Err(ConnectRejectReason::VersionMismatch {
+            server_version: PROTOCOL_VERSION,
+            client_version: protocol_version,
+        });
+    }
+    if settings.banned_ids.contains(&client_id) {
+        return Err(ConnectRejectReason::Banned);
+    }
+    if !has_free_slot {
+        return Err(ConnectRejectReason::ServerFull);
+    }
+    if !is_valid_name(name) {
+        return Err(ConnectRejectReason::BadName);
+    }
+    if !spectator && !known_ship_type {
+        return Err(ConnectRejectReason::UnknownShipType);
+    }
+    Ok(())
+}
+
+#[allow(clippy::too_many_arguments)]
+fn handle_hello(
+    mut client_message_event_reader: EventReader<ClientEvent>,
+    mut accepted_writer: EventWriter<ClientAccepted>,
+    mut server: ResMut<RenetServer>,
+    mut state: ResMut<HandshakeState>,
+    mut sessions: ResMut<Sessions>,
+    mut id_provider: ResMut<NetworkIdProvider>,
+    settings: Res<HandshakeSettings>,
+    ship_types: Res<ShipTypes>,
+    tick: Res<NetworkTick>,
+    time: Res<Time>,
+) {
+    for event in client_message_event_reader.iter() {
+        if let ClientMessages::Hello {
+            protocol_version,
+            build_hash,
+            ship_type,
+            name,
+            spectator,
+            session_token,
+        } = &event.message
+        {
+            let client_id = event.client_id;
+            if state.accepted.contains(&client_id) {
+                continue;
+            }
+
+            if build_hash != BUILD_HASH {
+                println!(
+                    "Player {} runs build {}, server runs {}.",
+                    client_id, build_hash, BUILD_HASH
+                );
+            }
+
+            // An unknown token just means the ship is gone, the client gets a new one.
+            let resuming =
+                !*spectator && session_token.is_some_and(|token| sessions.can_resume(token));
+            // Ships waiting for a reconnect keep their slot.
+            let has_free_slot = if *spectator {
+                state.spectators.len() < settings.max_spectators
+            } else {
+                resuming
+                    || state.accepted.len() - state.spectators.len() + sessions.held()
+                        < settings.max_players
+            };
+
+            match check_hello(
+                &settings,
+                client_id,
+                *protocol_version,
+                ship_types.get(ship_type).is_some(),
+                name,
+                *spectator,
+                has_free_slot,
+            ) {
+                Ok(()) => {
+                    let (player_id, session_token) = if *spectator {
+                        println!("Player {} accepted as {}, spectating.", client_id, name);
+                        // Spectators have no ship, but their id must not match one.
+                        let player_id = id_provider.new_id().id;
+                        state.spectators.insert(client_id, player_id);
+                        (player_id, None)
+                    } else {
+                        let (player_id, token, previous_client) =
+                            sessions.accept(client_id, *session_token, &mut id_provider);
+                        if resuming {
+                            println!(
+                                "Player {} accepted as {}, resuming player {}.",
+                                client_id, name, player_id
+                            );
+                        } else {
+                            println!("Player {} accepted as {}.", client_id, name);
+                        }
+                        // The old connection is dead, the server just hasn't noticed yet.
+                        if let Some(previous_client) = previous_client {
+                            server.disconnect(previous_client);
+                        }
+                        (player_id, Some(token))
+                    };
+                    state.accepted.insert(client_id);
+                    send_server_message(
+                        &mut server,
+                        client_id,
+                        &tick,
+                        ServerMessages::ConnectAccepted {
+                            protocol_version: PROTOCOL_VERSION,
+                            player_id,
+                            tick_rate: tick.rate,
+                            session_token,
+                            resume_window: sessions.grace_period,
+                        },
+                    );
+                    // Tick times only line up if the client knows when the rate changed.
+                    if tick.epoch_tick != 0 {
+                        send_server_message(
+                            &mut server,
+                            client_id,
+                            &tick,
+                            ServerMessages::TickRateChanged {
+                                tick_rate: tick.rate,
+                                epoch_tick: tick.epoch_tick,
+                                epoch_seconds: tick.epoch_seconds,
+                            },
+                        );
+                    }
+                    accepted_writer.send(ClientAccepted {
+                        client_id,
+                        player_id,
+                        resumed: resuming,
+                        name: name.clone(),
+                        ship_type: ship_type.clone(),
+                        spectator: *spectator,
+                    });
+                }
+                Err(reason) => reject(&mut server, &mut state, client_id, reason, &tick, &time),
+            }
+        }
+    }
+}
+
+fn reject_outdated_hellos(
+    mut outdated_hello_reader: EventReader<OutdatedHello>,
+    mut server: ResMut<RenetServer>,
+    mut state: ResMut<HandshakeState>,
+    tick: Res<NetworkTick>,
+    time: Res<Time>,
+) {
+    for hello in outdated_hello_reader.iter() {
+        if state.accepted.contains(&hello.client_id) {
+            continue;
+        }
+        let reason = ConnectRejectReason::VersionMismatch {
+            server_version: PROTOCOL_VERSION,
+            client_version: hello.protocol_version,
+        };
+        reject(
+            &mut server,
+            &mut state,
+            hello.client_id,
+            reason,
+            &tick,
+            &time,
+        );
+    }
+}
+
+fn reject(
+    server: &mut RenetServer,
+    state: &mut HandshakeState,
+    client_id: u64,
+    reason: ConnectRejectReason,
+    tick: &NetworkTick,
+    time: &Time,
+) {
+    println!("Player {} rejected: {}", client_id, reason);
+    send_server_message(
+        server,
+        client_id,
+        tick,
+        ServerMessages::ConnectRejected { reason },
+    );
+    state.pending_disconnects.push((
+        client_id,
+        time.elapsed_seconds_f64() + REJECT_DISCONNECT_DELAY,
+    ));
+}
+
+fn on_client_disconnected(
+    mut event_reader: EventReader<ServerEvent>,
+    mut state: ResMut<HandshakeState>,
+    mut id_provider: ResMut<NetworkIdProvider>,
+) {
+    for event in event_reader.iter() {
+        if let ServerEvent::ClientDisconnected(id) = event {
+            state.accepted.remove(id);
+            if let Some(player_id) = state.spectators.remove(id) {
+                id_provider.free(player_id);
+            }
+            state
+                .pending_disconnects
+                .retain(|(client_id, _)| client_id != id);
+        }
+    }
+}
+
+fn disconnect_rejected(
+    mut server: ResMut<RenetServer>,
+    mut state: ResMut<HandshakeState>,
+    time: Res<Time>,
+) {
+    let now = time.elapsed_seconds_f64();
+    state
+        .pending_disconnects
+        .retain(|(client_id, disconnect_at)| {
+            if *disconnect_at > now {
+                return true;
+            }
+            server.disconnect(*client_id);
+            false
+        });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn check(
+        settings: &HandshakeSettings,
+        protocol_version: u32,
+        known_ship_type: bool,
+        name: &str,
+        spectator: bool,
+        has_free_slot: bool,
+    ) -> Result<(), ConnectRejectReason> {
+        check_hello(
+            settings,
+            1,
+            protocol_version,
+            known_ship_type,
+            name,
+            spectator,
+            has_free_slot,
+        )
+    }
+
+    #[test]
+    fn valid_hello_is_accepted() {
+        let settings = HandshakeSettings::default();
+        assert_eq!(
+            check(&settings, PROTOCOL_VERSION, true, "Pilot", false, true),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn rejections() {
+        let mut settings = HandshakeSettings::default();
+        assert_eq!(
+            check(&settings, PROTOCOL_VERSION + 1, true, "Pilot", false, true),
+            Err(ConnectRejectReason::VersionMismatch {
+                server_version: PROTOCOL_VERSION,
+                client_version: PROTOCOL_VERSION + 1,
+            })
+        );
+        assert_eq!(
+            check(&settings, PROTOCOL_VERSION, true, "Pilot", false, false),
+            Err(ConnectRejectReason::ServerFull)
+        );
+        assert_eq!(
+            check(&settings, PROTOCOL_VERSION, true, " ", false, true),
+            Err(ConnectRejectReason::BadName)
+        );
+        assert_eq!(
+            check(&settings, PROTOCOL_VERSION, false, "Pilot", false, true),
+            Err(ConnectRejectReason::UnknownShipType)
+        );
+
+        settings.banned_ids.insert(1);
+        assert_eq!(
+            check(&settings, PROTOCOL_VERSION, true, "Pilot", false, true),
+            Err(ConnectRejectReason::Banned)
+        );
+    }
+
+    #[test]
+    fn spectators_need_no_ship_type() {
+        let settings = HandshakeSettings::default();
+        assert_eq!(
+            check(&settings, PROTOCOL_VERSION, false, "Pilot", true, true),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn version_is_checked_first() {
+        let mut settings = HandshakeSettings::default();
+        settings.banned_ids.insert(1);
+        assert!(matches!(
+            check(&settings, 0, false, "", false, false),
+            Err(ConnectRejectReason::VersionMismatch { .. })
+        ));
+    }
+}
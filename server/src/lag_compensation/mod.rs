@@ -0,0 +1,21 @@
+use bevy::prelude::{App, Plugin};
+use spaaaace_shared::lag_compensation::{
+    record_collider_history, ColliderHistory, LagCompensationSettings,
+};
+
+use crate::tick::{TickAppExt, TickStage};
+
+pub struct LagCompensationPlugin {
+    /// Seconds.
+    pub max_rewind: f32,
+}
+
+impl Plugin for LagCompensationPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(LagCompensationSettings {
+            max_rewind: self.max_rewind,
+        })
+        .init_resource::<ColliderHistory>()
+        .add_tick_system(TickStage::Broadcast, record_collider_history);
+    }
+}
@@ -1,20 +1,21 @@
-use std::{net::UdpSocket, time::SystemTime};
+use std::{
+    collections::HashMap,
+    net::UdpSocket,
+    process,
+    time::{Duration, SystemTime},
+};
 
 use bevy::{
-    prelude::{
-        default, info, App, Camera3d, Camera3dBundle, Commands, EventWriter, PluginGroup,
-        PointLight, PointLightBundle, Query, ResMut, StageLabel, Transform, Vec3, With, Without,
-    },
-    window::{PresentMode, WindowDescriptor, WindowPlugin},
-    DefaultPlugins,
+    app::ScheduleRunnerSettings,
+    asset::AssetPlugin,
+    hierarchy::HierarchyPlugin,
+    log::LogPlugin,
+    prelude::{default, info, AddAsset, App, Commands, EventWriter, Mesh, Res, ResMut, Resource},
+    scene::ScenePlugin,
+    transform::TransformPlugin,
+    MinimalPlugins,
 };
 
-use bevy_inspector_egui::quick::WorldInspectorPlugin;
-use bevy_mod_gizmos::GizmosPlugin;
-use bevy_rapier3d::{
-    prelude::{NoUserData, RapierPhysicsPlugin},
-    render::RapierDebugRenderPlugin,
-};
 use bevy_renet::{
     renet::{
         DefaultChannel, RenetConnectionConfig, RenetServer, ServerAuthentication, ServerConfig,
@@ -23,40 +24,108 @@ use bevy_renet::{
 };
 
 use spaaaace_shared::{
-    asteroid::AsteroidPlugin, cooldown::CooldownPlugin, health::HealthPlugin, player::Player,
-    weapons::WeaponsPlugin, ClientMessages, Lobby, NetworkContext, NetworkIdProvider, PROTOCOL_ID,
+    asteroid::AsteroidPlugin,
+    auth::read_private_key,
+    codec::decode,
+    cooldown::CooldownPlugin,
+    handshake::{HelloPrefix, BUILD_HASH, PROTOCOL_VERSION},
+    health::HealthPlugin,
+    netsim::{condition_server_socket, NetworkConditioner},
+    replay::{ReplayHeader, ReplayRecorder},
+    replication::ReplicationPlugin,
+    ships::ShipsPlugin,
+    tick::NetworkTick,
+    weapons::WeaponsPlugin,
+    ClientMessages, Lobby, NetworkContext, NetworkIdProvider, PROTOCOL_ID,
 };
 
-use crate::{capture_point::CapturePointPlugin, player::PlayerPlugin};
+use crate::{
+    capture_point::ServerCapturePointPlugin,
+    chat::ChatPlugin,
+    config::ServerSettings,
+    console::{commands::MAX_TICK_RATE, ConsolePlugin},
+    discovery::DiscoveryPlugin,
+    handshake::{HandshakePlugin, HandshakeSettings, OutdatedHello},
+    lag_compensation::LagCompensationPlugin,
+    player::PlayerPlugin,
+    session::SessionPlugin,
+    snapshot::SnapshotPlugin,
+    tick::ServerTickPlugin,
+    weapons::ServerWeaponsPlugin,
+};
 
 pub mod capture_point;
+pub mod chat;
+pub mod config;
+pub mod console;
+#[cfg(feature = "debug-view")]
+pub mod debug;
+pub mod discovery;
+pub mod handshake;
+pub mod lag_compensation;
 pub mod player;
-
-#[derive(Debug, Hash, PartialEq, Eq, Clone, StageLabel)]
-struct FixedUpdateStage;
+pub mod session;
+pub mod snapshot;
+pub mod tick;
+pub mod weapons;
 
 fn main() {
     info!("Naia Bevy Server Demo starting up");
 
+    let settings = ServerSettings::load().unwrap_or_else(|error| {
+        eprintln!("{}", error);
+        process::exit(1);
+    });
+
     // Build App
-    App::default()
-        // Plugins
-        .add_plugins(DefaultPlugins.set(WindowPlugin {
-            window: WindowDescriptor {
-                title: "Spaaace Server".to_string(),
-                width: 1280.,
-                height: 720.,
-                present_mode: PresentMode::AutoVsync,
-                ..default()
-            },
+    let mut app = App::default();
+
+    if settings.debug_view {
+        #[cfg(feature = "debug-view")]
+        app.add_plugin(debug::DebugViewPlugin);
+    } else {
+        // The loop can't follow the tick rate, the runner reads it once on startup. It runs
+        // as fast as the highest tick rate instead, so the `tickrate` command still gets
+        // one tick per frame.
+        let frame_rate = settings.tick_rate.max(MAX_TICK_RATE);
+        app.insert_resource(ScheduleRunnerSettings::run_loop(Duration::from_secs_f32(
+            1.0 / frame_rate,
+        )))
+        .add_plugins(MinimalPlugins)
+        .add_plugin(LogPlugin::default())
+        .add_plugin(TransformPlugin)
+        .add_plugin(HierarchyPlugin)
+        // Ship definitions are reloaded when they change
+        .add_plugin(AssetPlugin {
+            watch_for_changes: true,
             ..default()
-        }))
-        .add_startup_system(setup)
+        })
+        .add_plugin(ScenePlugin)
+        // Rapier looks meshes up for colliders built from them
+        .add_asset::<Mesh>();
+    }
+
+    if !settings.hidden {
+        app.add_plugin(DiscoveryPlugin);
+    }
+
+    if settings.record.is_some() {
+        app.add_startup_system(start_recording);
+    }
+
+    let (server, conditioner) = new_renet_server(&settings);
+    // The relay stops when the last handle to it is dropped.
+    if let Some(conditioner) = conditioner {
+        app.insert_resource(conditioner);
+    }
+
+    app
         // ------------------
         // Third party
         // ------------------
-        .add_plugin(RapierPhysicsPlugin::<NoUserData>::default())
-        .add_plugin(RapierDebugRenderPlugin::default())
+        .add_plugin(ServerTickPlugin {
+            tick_rate: settings.tick_rate,
+        })
         // ------------------
         // Networking stuff
         // ------------------
@@ -64,60 +133,117 @@ fn main() {
         .insert_resource(NetworkIdProvider::new())
         .insert_resource(NetworkContext { is_server: true })
         .add_event::<ClientEvent>()
+        .init_resource::<DecodeErrors>()
         .add_plugin(RenetServerPlugin::default())
-        .insert_resource(new_renet_server())
+        .insert_resource(server)
+        .insert_resource(HandshakeSettings {
+            max_players: settings.max_players,
+            max_spectators: settings.max_spectators,
+            ..default()
+        })
+        .add_plugin(HandshakePlugin)
+        .add_plugin(SessionPlugin {
+            grace_period: settings.session_grace_secs as f32,
+        })
+        .add_plugin(ConsolePlugin {
+            admin_password: settings.admin_password.clone(),
+            rcon_port: settings.rcon_port,
+        })
+        .add_plugin(ChatPlugin)
+        .add_plugin(ReplicationPlugin)
+        .add_plugin(SnapshotPlugin)
+        .add_plugin(LagCompensationPlugin {
+            max_rewind: settings.max_rewind_ms as f32 / 1000.0,
+        })
         .add_system(server_update_system)
         // ------------------
         // Gameplay stuff
         // ------------------
         .add_plugin(HealthPlugin)
         .add_plugin(WeaponsPlugin {})
+        .add_plugin(ServerWeaponsPlugin)
         .add_plugin(AsteroidPlugin {})
+        .add_plugin(ShipsPlugin)
         .add_plugin(PlayerPlugin)
-        .add_plugin(CapturePointPlugin)
+        .add_plugin(ServerCapturePointPlugin)
         .add_plugin(CooldownPlugin)
-        // ------------------
-        // Debugging stuff
-        // ------------------
-        .add_plugin(GizmosPlugin)
-        .add_plugin(WorldInspectorPlugin)
-        // .add_plugin(InputPlugin::default())
-        // .add_plugin(ScenePlugin::default())
-        // .add_plugin(WindowPlugin::default())
-        // .add_plugin(WinitPlugin::default())
-        // .add_plugin(RenderPlugin::default())
-        .add_system(camera_follow_players)
+        .insert_resource(settings)
         .run();
 }
 
-fn setup(mut commands: Commands) {
-    // light
-    commands.spawn(PointLightBundle {
-        point_light: PointLight {
-            intensity: 1500.0,
-            shadows_enabled: true,
-            ..default()
-        },
-        transform: Transform::from_xyz(4.0, 8.0, 4.0),
-        ..default()
-    });
-    // camera
-    commands.spawn(Camera3dBundle {
-        transform: Transform::from_xyz(-2.0, 2.5, 5.0).looking_at(Vec3::ZERO, Vec3::Y),
-        ..default()
+/// Starts recording to the `record` path. Recording starts with the server, before
+/// anyone has connected, so every player is recorded as they join.
+fn start_recording(mut commands: Commands, settings: Res<ServerSettings>, tick: Res<NetworkTick>) {
+    let path = match &settings.record {
+        Some(path) => path,
+        None => return,
+    };
+    let header = ReplayHeader {
+        protocol_version: PROTOCOL_VERSION,
+        build_hash: BUILD_HASH.to_string(),
+        map: settings.map.clone(),
+        tick_rate: tick.rate,
+        start_time: tick.tick_to_seconds(tick.tick),
+        players: Vec::new(),
+    };
+    let recorder = ReplayRecorder::create(path, &header).unwrap_or_else(|error| {
+        eprintln!("Could not create replay {}: {}", path.display(), error);
+        process::exit(1);
     });
+    println!("Recording replay to {}.", path.display());
+    commands.insert_resource(recorder);
 }
 
-fn new_renet_server() -> RenetServer {
-    let server_addr = "127.0.0.1:5000".parse().unwrap();
-    let socket = UdpSocket::bind(server_addr).unwrap();
+/// Connection slots on top of the players and spectators, so clients connecting to a
+/// full server can still be told why they are rejected.
+const EXTRA_CONNECTION_SLOTS: usize = 4;
+
+fn new_renet_server(settings: &ServerSettings) -> (RenetServer, Option<NetworkConditioner>) {
+    let mut socket = UdpSocket::bind(settings.bind_addr()).unwrap();
+    let mut conditioner = None;
+    if let Some(conditions) = settings.network_conditions {
+        println!("Simulating network conditions: {:?}", conditions);
+        let (inner, relay) = condition_server_socket(socket, conditions).unwrap();
+        socket = inner;
+        conditioner = Some(relay);
+    }
     let connection_config = RenetConnectionConfig::default();
-    let server_config =
-        ServerConfig::new(64, PROTOCOL_ID, server_addr, ServerAuthentication::Unsecure);
+    let server_config = ServerConfig::new(
+        settings.max_players + settings.max_spectators + EXTRA_CONNECTION_SLOTS,
+        PROTOCOL_ID,
+        settings.public_addr(),
+        server_authentication(settings),
+    );
     let current_time = SystemTime::now()
         .duration_since(SystemTime::UNIX_EPOCH)
         .unwrap();
-    RenetServer::new(current_time, server_config, connection_config, socket).unwrap()
+    println!(
+        "Listening on {}, clients connect to {}.",
+        settings.bind_addr(),
+        settings.public_addr()
+    );
+    let server = RenetServer::new(current_time, server_config, connection_config, socket).unwrap();
+    (server, conditioner)
+}
+
+/// Clients need a connect token signed with the server's private key, unless the
+/// server runs in unsecure mode.
+fn server_authentication(settings: &ServerSettings) -> ServerAuthentication {
+    if settings.is_unsecure() {
+        println!("Running in unsecure mode, clients can connect with any id.");
+        return ServerAuthentication::Unsecure;
+    }
+
+    let key_path = &settings.private_key;
+    let private_key = read_private_key(key_path).unwrap_or_else(|error| {
+        eprintln!(
+            "Could not read private key: {}. Create one with `cargo run --bin issue_token -- --generate-key {}` or start the server with --unsecure.",
+            error,
+            key_path.display()
+        );
+        process::exit(1);
+    });
+    ServerAuthentication::Secure { private_key }
 }
 
 #[derive(Clone)]
@@ -126,45 +252,62 @@ struct ClientEvent {
     pub client_id: u64,
 }
 
+/// Malformed messages a client may send before it is disconnected.
+const MAX_DECODE_ERRORS: u32 = 10;
+
+/// Malformed messages received from each connected client.
+#[derive(Resource, Default)]
+struct DecodeErrors(HashMap<u64, u32>);
+
 fn server_update_system(
     mut server: ResMut<RenetServer>,
+    mut decode_errors: ResMut<DecodeErrors>,
     mut client_message_event_writer: EventWriter<ClientEvent>,
+    mut outdated_hello_writer: EventWriter<OutdatedHello>,
 ) {
-    for client_id in server.clients_id().into_iter() {
-        while let Some(message) = server.receive_message(client_id, DefaultChannel::Reliable) {
-            let message: ClientMessages = bincode::deserialize(&message).unwrap();
-            client_message_event_writer.send(ClientEvent { message, client_id });
-        }
-    }
-}
-
-fn camera_follow_players(
-    mut query_cam: Query<&mut Transform, (With<Camera3d>, Without<Player>)>,
-    query_players: Query<&Transform, With<Player>>,
-) {
-    if query_players.is_empty() {
-        return;
-    }
-    let mut player_count = 0;
-    let mut avg = Vec3::ZERO;
-    for player_transform in query_players.iter() {
-        avg += player_transform.translation;
-        player_count += 1;
-    }
+    let clients_id = server.clients_id();
+    decode_errors
+        .0
+        .retain(|client_id, _| clients_id.contains(client_id));
 
-    avg /= Vec3::splat(player_count as f32);
+    for client_id in clients_id.into_iter() {
+        'channels: for channel in
+            [DefaultChannel::Reliable, DefaultChannel::Unreliable].map(u8::from)
+        {
+            while let Some(message) = server.receive_message(client_id, channel) {
+                // The rest of a hello from another version may not decode, or decode wrong.
+                if let Ok(HelloPrefix::Hello { protocol_version }) = decode(&message) {
+                    if protocol_version != PROTOCOL_VERSION {
+                        outdated_hello_writer.send(OutdatedHello {
+                            client_id,
+                            protocol_version,
+                        });
+                        continue;
+                    }
+                }
+                match decode::<ClientMessages>(&message) {
+                    Ok(message) => {
+                        client_message_event_writer.send(ClientEvent { message, client_id })
+                    }
+                    Err(error) => {
+                        let errors = decode_errors.0.entry(client_id).or_insert(0);
+                        *errors += 1;
+                        println!(
+                            "Bad message from player {} ({} so far): {}",
+                            client_id, errors, error
+                        );
 
-    let mut max_dist_from_avg: f32 = 0.0;
-
-    for player_transform in query_players.iter() {
-        let dist = player_transform.translation.distance(avg);
-        max_dist_from_avg = max_dist_from_avg.max(dist);
-    }
-
-    for mut transform in query_cam.iter_mut() {
-        transform.look_at(avg, Vec3::Y);
-
-        let back = transform.back();
-        transform.translation = avg + back * (max_dist_from_avg * 3.0 + 80.);
+                        if *errors >= MAX_DECODE_ERRORS {
+                            println!(
+                                "Disconnecting player {} for sending bad messages.",
+                                client_id
+                            );
+                            server.disconnect(client_id);
+                            break 'channels;
+                        }
+                    }
+                }
+            }
+        }
     }
 }
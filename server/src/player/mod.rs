@@ -1,328 +1,471 @@
-use std::path::Path;
+use std::collections::VecDeque;
 
 use bevy::{
-    gltf::{Gltf, GltfNode},
     math::vec3,
     prelude::{
-        default, App, AssetServer, Assets, BuildChildren, Color, Commands, DespawnRecursiveExt,
-        Entity, EventReader, PbrBundle, Plugin, Quat, Query, Res, ResMut, SpatialBundle, Transform,
-        Vec3,
+        default, App, BuildChildren, Commands, Component, DespawnRecursiveExt, EventReader,
+        EventWriter, Plugin, Quat, Query, Res, ResMut, SpatialBundle, Transform,
     },
-    scene::SceneBundle,
     time::Time,
     transform::TransformBundle,
-    utils::{HashMap, Instant},
-};
-use bevy_mod_gizmos::{draw_gizmo, Gizmo};
-use bevy_rapier3d::prelude::{
-    Collider, ColliderMassProperties, CollisionGroups, Damping, ExternalImpulse, GravityScale,
-    Group, RigidBody, Sleeping,
 };
+use bevy_rapier3d::prelude::{Collider, CollisionGroups, Group, RigidBody};
 
-use bevy_renet::renet::{DefaultChannel, RenetServer, ServerEvent};
+use bevy_renet::renet::{RenetServer, ServerEvent};
 use spaaaace_shared::{
-    player::{player_input::PlayerInput, Player},
-    ships::{ShipModelLoadHandle, SHIP_TYPES},
+    broadcast_server_message,
+    command::Command,
+    handshake::ClientAccepted,
+    health::Health,
+    player::{
+        movement::{step_ship, ShipVelocity},
+        player_input::PlayerInput,
+        Player,
+    },
+    replay::{ReplayFrame, ReplayPlayer, ReplayRecorder},
+    send_server_message,
+    ships::{PlayerShipType, ShipTypes},
     team::team_enum::Team,
+    tick::NetworkTick,
     weapons::{Barrel, Turret, TurretOwner},
-    ClientMessages, Lobby, NetworkedId, ServerMessages, TranslationRotation,
+    ClientMessages, Lobby, NetworkedId, ServerMessages,
+};
+
+use crate::{
+    console::{
+        commands::RestartMatch, CommandAppExt, CommandInvoked, CommandOutput, CommandSource,
+        Permission,
+    },
+    session::{SessionExpired, Sessions},
+    snapshot::SnapshotSettings,
+    tick::{TickAppExt, TickStage},
+    ClientEvent,
 };
 
-use crate::ClientEvent;
+use self::validation::InputValidator;
+
+pub mod validation;
 
 pub struct PlayerPlugin;
 
 impl Plugin for PlayerPlugin {
     fn build(&self, app: &mut App) {
-        app.add_system(update_players_system)
+        app.add_tick_system(TickStage::Simulate, update_players_system)
+            .add_tick_system(TickStage::Simulate, respawn_destroyed_ships)
+            .add_console_command("swap_team", Permission::Player)
             .add_system(swap_team_command)
+            .add_system(restart_ships)
+            .add_system(update_ship_colliders)
             .add_system(player_input)
             .add_system(on_client_disconnected)
-            .add_system(on_client_connected)
-            .add_system(on_client_model_loaded)
-            .add_system(server_sync_players);
+            .add_system(on_session_expired)
+            .add_system(on_client_accepted);
     }
 }
 
-const PLAYER_MOVE_SPEED: f32 = 2.0;
-
-fn update_players_system(mut query: Query<(&mut ExternalImpulse, &Transform, &PlayerInput)>) {
-    for (mut rigidbody, transform, input) in query.iter_mut() {
-        let rotation = (input.rotate_right as i8 - input.rotate_left as i8) as f32;
-        let thrust_longitudal = (input.thrust_forward as i8 - input.thrust_reverse as i8) as f32;
-        let thrust_lateral = (input.thrust_left as i8 - input.thrust_right as i8) as f32;
-        let thrust_vertical = (input.thrust_up as i8 - input.thrust_down as i8) as f32;
-
-        let forward = transform.forward();
-        let projected_forward = (forward - Vec3::new(0.0, forward.y, 0.0)).normalize();
-        let rotated_forward =
-            (Quat::from_axis_angle(transform.left(), -0.3 * thrust_vertical)) * projected_forward;
-
-        let left = transform.left();
-        let projected_left = (left - Vec3::new(0.0, left.y, 0.0)).normalize();
-
-        let longitudal_force = thrust_longitudal * PLAYER_MOVE_SPEED * 50.0 * projected_forward;
-        let lateral_force = thrust_lateral * PLAYER_MOVE_SPEED * 30.0 * projected_left;
-        let vertical_force = thrust_vertical * PLAYER_MOVE_SPEED * 30.0 * Vec3::Y;
-
-        draw_gizmo(Gizmo::new(
-            transform.translation + rotated_forward * 2.0,
-            0.3,
-            Color::PURPLE,
-        ));
-
-        draw_gizmo(Gizmo::new(
-            transform.translation + transform.forward() * 2.5,
-            0.3,
-            Color::GREEN,
-        ));
-
-        rigidbody.impulse = longitudal_force + lateral_force + vertical_force;
-        rigidbody.torque_impulse = rotation * Vec3::NEG_Y * PLAYER_MOVE_SPEED * 60.0;
-
-        {
-            let (axis, angle) =
-                Quat::from_rotation_arc(transform.forward(), rotated_forward).to_axis_angle();
-            rigidbody.torque_impulse += axis.normalize_or_zero() * angle * 200.0;
-        }
+/// Inputs received from a client that haven't been simulated yet.
+#[derive(Component, Default)]
+pub struct InputQueue {
+    pub inputs: VecDeque<PlayerInput>,
+    pub last_processed: u32,
+}
 
-        {
-            let (axis, angle) = Quat::from_rotation_arc(transform.up(), Vec3::Y).to_axis_angle();
-            rigidbody.torque_impulse += axis.normalize_or_zero() * angle * 300.0;
-        }
+/// Where ships start out, and go back to when the match restarts.
+fn spawn_transform() -> Transform {
+    Transform {
+        translation: vec3(0.0, 5.0, 0.0),
+        rotation: Quat::from_rotation_x(0.5),
+        ..Default::default()
     }
 }
 
-fn server_sync_players(
-    mut server: ResMut<RenetServer>,
-    mut query: Query<(&Transform, &mut NetworkedId, Option<&Sleeping>)>,
-    time: Res<Time>,
+/// How many ticks of input a client may buffer before old inputs are dropped.
+const MAX_QUEUED_INPUTS: usize = 8;
+
+fn update_players_system(
+    mut query: Query<(
+        &mut Transform,
+        &mut ShipVelocity,
+        &mut PlayerInput,
+        &mut InputQueue,
+        &PlayerShipType,
+    )>,
+    ship_types: Res<ShipTypes>,
+    tick: Res<NetworkTick>,
 ) {
-    let mut entries: Vec<(&NetworkedId, TranslationRotation)> = Vec::new();
+    for (mut transform, mut velocity, mut input, mut queue, ship_type) in query.iter_mut() {
+        if let Some(next_input) = queue.inputs.pop_front() {
+            queue.last_processed = next_input.sequence;
+            *input = next_input;
+        }
 
-    for (transform, network_id, sleeping) in query.iter() {
-        if sleeping.is_some() && sleeping.unwrap().sleeping {
-            continue;
+        // Only missing if its file was removed while the server runs.
+        if let Some(ship) = ship_types.get(&ship_type.0) {
+            step_ship(
+                &mut transform,
+                &mut velocity,
+                &input,
+                ship,
+                tick.delta_seconds(),
+            );
         }
-        entries.push((
-            network_id,
-            TranslationRotation {
-                translation: transform.translation,
-                rotation: transform.rotation,
-            },
-        ));
     }
+}
 
-    entries.sort_by(|(a, _), (b, _)| a.last_sent.cmp(&b.last_sent));
-
-    let mut messages: HashMap<u64, TranslationRotation> = HashMap::new();
+/// Ship definitions are reloaded when their files change. Movement picks the changes
+/// up on the next tick and colliders here, hull and hardpoints only apply to ships
+/// spawned afterwards.
+fn update_ship_colliders(
+    ship_types: Res<ShipTypes>,
+    mut query: Query<(&PlayerShipType, &mut Collider)>,
+) {
+    if !ship_types.is_changed() {
+        return;
+    }
 
-    for (id, tr) in entries {
-        if messages.len() < 70 {
-            messages.insert(id.id, tr);
-        } else {
-            break;
+    for (ship_type, mut collider) in query.iter_mut() {
+        if let Some(ship) = ship_types.get(&ship_type.0) {
+            *collider = ship.collider.collider();
         }
     }
+}
 
-    let sync_message = bincode::serialize(&messages).unwrap();
-    server.broadcast_message(DefaultChannel::Unreliable, sync_message);
-
-    for (_, mut network_id, sleeping) in query.iter_mut() {
-        if sleeping.is_some() && sleeping.unwrap().sleeping {
+/// Ships that run out of hull go back to the spawn with a fresh one, they stay in the
+/// lobby and keep their session.
+fn respawn_destroyed_ships(
+    mut query: Query<(
+        &NetworkedId,
+        &PlayerShipType,
+        &mut Health,
+        &mut Transform,
+        &mut ShipVelocity,
+        &mut InputQueue,
+    )>,
+    ship_types: Res<ShipTypes>,
+) {
+    for (id, ship_type, mut health, mut transform, mut velocity, mut queue) in query.iter_mut() {
+        if health.health > 0.0 {
             continue;
         }
-        if messages.contains_key(&network_id.id) {
-            network_id.last_sent = Instant::now().duration_since(time.startup()).as_nanos();
-        }
+        println!("The ship of player {} was destroyed, respawning it.", id.id);
+        health.health = full_hull(&ship_types, ship_type);
+        *transform = spawn_transform();
+        *velocity = ShipVelocity::default();
+        queue.inputs.clear();
+    }
+}
+
+/// Health a ship spawns with. Its definition is only missing if the file was removed
+/// while the server runs.
+fn full_hull(ship_types: &ShipTypes, ship_type: &PlayerShipType) -> f32 {
+    match ship_types.get(&ship_type.0) {
+        Some(ship) => ship.hull,
+        None => 1.0,
     }
 }
 
 fn swap_team_command(
-    mut client_message_event_reader: EventReader<ClientEvent>,
+    mut invoked_reader: EventReader<CommandInvoked>,
+    mut output_writer: EventWriter<CommandOutput>,
     lobby: ResMut<Lobby>,
+    sessions: Res<Sessions>,
     mut player_query: Query<&mut Player>,
 ) {
-    for event in client_message_event_reader.iter() {
-        match event.message.clone() {
-            ClientMessages::Command { command } => {
-                let args_split = command.split(" ");
-                let args: Vec<&str> = args_split.collect();
-
-                match args[0] {
-                    "swap_team" => {
-                        let entity = lobby.players[&event.client_id];
-
-                        match player_query.get_mut(entity) {
-                            Ok(mut player) => match args[1] {
-                                "1" => player.team = Team::Red,
-                                "2" => player.team = Team::Blue,
-                                _ => (),
-                            },
-                            Err(_) => (),
-                        }
-                    }
-                    _ => (),
-                }
-            }
-            _ => (),
+    for invoked in invoked_reader.iter() {
+        let team = match &invoked.command {
+            Command::SwapTeam { team } => team,
+            _ => continue,
+        };
+
+        let entity = match invoked.source {
+            CommandSource::Client(client_id) => sessions
+                .player_id(client_id)
+                .and_then(|player_id| lobby.players.get(&player_id)),
+            CommandSource::Console(_) => None,
+        };
+        match entity.map(|entity| player_query.get_mut(*entity)) {
+            Some(Ok(mut player)) => player.team = team.clone(),
+            _ => output_writer.send(CommandOutput::new(invoked.source, "You have no ship.")),
         }
     }
 }
 
+/// Puts every ship back at the spawn.
+fn restart_ships(
+    mut restart_reader: EventReader<RestartMatch>,
+    mut query: Query<(
+        &PlayerShipType,
+        &mut Health,
+        &mut Transform,
+        &mut ShipVelocity,
+    )>,
+    ship_types: Res<ShipTypes>,
+) {
+    if restart_reader.iter().count() == 0 {
+        return;
+    }
+
+    for (ship_type, mut health, mut transform, mut velocity) in query.iter_mut() {
+        health.health = full_hull(&ship_types, ship_type);
+        *transform = spawn_transform();
+        *velocity = ShipVelocity::default();
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
 fn player_input(
     mut client_message_event_reader: EventReader<ClientEvent>,
-    mut commands: Commands,
+    mut queue_query: Query<(&Transform, &mut InputQueue, &mut InputValidator)>,
+    transform_query: Query<&Transform>,
     lobby: ResMut<Lobby>,
+    sessions: Res<Sessions>,
+    snapshot_settings: Res<SnapshotSettings>,
+    tick: Res<NetworkTick>,
+    time: Res<Time>,
 ) {
     for event in client_message_event_reader.iter() {
-        match event.message.clone() {
-            ClientMessages::PlayerInput { input } => {
-                if let Some(player_entity) = lobby.players.get(&event.client_id) {
-                    commands.entity(*player_entity).insert(input);
+        if let ClientMessages::PlayerInputs { inputs } = event.message.clone() {
+            let player_entity = match sessions
+                .player_id(event.client_id)
+                .and_then(|player_id| lobby.players.get(&player_id))
+            {
+                Some(entity) => *entity,
+                None => continue,
+            };
+            let (transform, mut queue, mut validator) = match queue_query.get_mut(player_entity) {
+                Ok(components) => components,
+                Err(_) => continue,
+            };
+
+            for input in inputs {
+                // Inputs are repeated until acknowledged, skip the ones we already have.
+                if input.sequence <= validator.last_sequence() {
+                    continue;
+                }
+
+                // Players can only target what they are sent snapshots of.
+                let target_visible = lobby
+                    .networked_entities
+                    .get(&input.target_network_id)
+                    .or_else(|| lobby.players.get(&input.target_network_id))
+                    .and_then(|entity| transform_query.get(*entity).ok())
+                    .is_some_and(|target| {
+                        target.translation.distance(transform.translation)
+                            <= snapshot_settings.cull_radius
+                    });
+
+                if let Some(input) = validator.validate(
+                    event.client_id,
+                    input,
+                    transform.translation,
+                    target_visible,
+                    time.elapsed_seconds_f64(),
+                    tick.rate,
+                ) {
+                    queue.inputs.push_back(input);
+                    while queue.inputs.len() > MAX_QUEUED_INPUTS {
+                        queue.inputs.pop_front();
+                    }
                 }
             }
-            _ => (),
         }
     }
 }
 
 fn on_client_disconnected(
     mut event_reader: EventReader<ServerEvent>,
+    mut sessions: ResMut<Sessions>,
+    mut query: Query<(&mut PlayerInput, &mut InputQueue)>,
+    lobby: Res<Lobby>,
+    time: Res<Time>,
+) {
+    for event in event_reader.iter() {
+        if let ServerEvent::ClientDisconnected(id) = event {
+            println!("Player {} disconnected.", id);
+            // Spectators and rejected clients never had a ship.
+            let player_id = match sessions.disconnect(*id, time.elapsed_seconds_f64()) {
+                Some(player_id) => player_id,
+                None => continue,
+            };
+            println!(
+                "Keeping the ship of player {} for {} seconds.",
+                player_id, sessions.grace_period
+            );
+
+            // The ship drifts on without anyone at the controls.
+            if let Some(Ok((mut input, mut queue))) = lobby
+                .players
+                .get(&player_id)
+                .map(|entity| query.get_mut(*entity))
+            {
+                *input = PlayerInput::default();
+                queue.inputs.clear();
+            }
+        }
+    }
+}
+
+fn on_session_expired(
+    mut event_reader: EventReader<SessionExpired>,
     mut commands: Commands,
     mut lobby: ResMut<Lobby>,
     mut server: ResMut<RenetServer>,
+    mut recorder: Option<ResMut<ReplayRecorder>>,
+    tick: Res<NetworkTick>,
 ) {
     for event in event_reader.iter() {
-        match event {
-            ServerEvent::ClientDisconnected(id) => {
-                println!("Player {} disconnected.", id);
-                if let Some(player_entity) = lobby.players.remove(id) {
-                    commands.entity(player_entity).despawn_recursive();
-                }
-
-                let message =
-                    bincode::serialize(&ServerMessages::PlayerDisconnected { id: *id }).unwrap();
-                server.broadcast_message(DefaultChannel::Reliable, message);
+        let id = event.player_id;
+        println!("Player {} did not come back, removing their ship.", id);
+        if let Some(player_entity) = lobby.players.remove(&id) {
+            // Don't take the server down if something else despawned it already.
+            if let Some(entity_commands) = commands.get_entity(player_entity) {
+                entity_commands.despawn_recursive();
             }
-            _ => (),
+            broadcast_server_message(
+                &mut server,
+                &tick,
+                recorder.as_deref_mut(),
+                ServerMessages::PlayerDisconnected { id },
+            );
         }
     }
 }
 
-fn on_client_connected(
-    mut event_reader: EventReader<ServerEvent>,
+#[allow(clippy::too_many_arguments)]
+fn on_client_accepted(
+    mut event_reader: EventReader<ClientAccepted>,
     mut commands: Commands,
     mut lobby: ResMut<Lobby>,
     mut server: ResMut<RenetServer>,
-    ass: Res<AssetServer>,
+    mut recorder: Option<ResMut<ReplayRecorder>>,
+    mut resumed_query: Query<(&mut InputQueue, &mut InputValidator)>,
+    ship_type_query: Query<&PlayerShipType>,
+    ship_types: Res<ShipTypes>,
+    tick: Res<NetworkTick>,
+    time: Res<Time>,
 ) {
     for event in event_reader.iter() {
-        match event {
-            ServerEvent::ClientConnected(id, _) => {
-                let ship_type = SHIP_TYPES["TEST_SHIP"];
-
-                let ship_gltf_handle = ass.load(
-                    Path::new("../../shared/assets/ships").join(Path::new(ship_type.model_name)),
-                );
-
-                println!("Player {} connected.", id);
-                // Spawn player cube
-                let player_entity = commands
-                    .spawn(SpatialBundle {
-                        transform: Transform {
-                            translation: vec3(0.0, 5.0, 0.0),
-                            rotation: Quat::from_rotation_x(0.5),
-                            ..Default::default()
-                        },
-                        ..Default::default()
-                    })
-                    .insert(ShipModelLoadHandle(ship_gltf_handle))
-                    .insert(PlayerInput::default())
-                    .insert(NetworkedId {
-                        id: *id,
-                        last_sent: 0,
-                    })
-                    .insert(ColliderMassProperties::Density(3.0))
-                    .insert(Player { team: Team::Red })
-                    .insert(Collider::cuboid(2.0, 1.0, 12.0))
-                    .insert(CollisionGroups::new(Group::GROUP_1, Group::GROUP_1))
-                    .insert(RigidBody::Dynamic)
-                    // .insert(LockedAxes::ROTATION_LOCKED_Z)
-                    .insert(GravityScale(0.0))
-                    .insert(ExternalImpulse::default())
-                    .insert(Damping {
-                        linear_damping: 0.5,
-                        angular_damping: 1.0,
-                    })
-                    .insert(PbrBundle { ..default() })
-                    .id();
-
-                // We could send an InitState with all the players id and positions for the client
-                // but this is easier to do.
-                for &player_id in lobby.players.keys() {
-                    let message =
-                        bincode::serialize(&ServerMessages::PlayerConnected { id: player_id })
-                            .unwrap();
-                    server.send_message(*id, DefaultChannel::Reliable, message);
-                }
+        let id = &event.client_id;
+
+        // Everyone else still has the ship, only the new connection needs to hear about
+        // the players. Validation starts over for the new connection.
+        if event.resumed {
+            if let Some(Ok((mut queue, mut validator))) = lobby
+                .players
+                .get(&event.player_id)
+                .map(|entity| resumed_query.get_mut(*entity))
+            {
+                queue.inputs.clear();
+                *validator = InputValidator::new(time.elapsed_seconds_f64());
+            }
+            send_players(&mut server, *id, &tick, &lobby, &ship_type_query);
+            continue;
+        }
 
-                lobby.players.insert(*id, player_entity);
+        // Spectators only need to know who is playing, they get the world like everyone
+        // else but stay out of the lobby so no team or capture logic sees them.
+        if event.spectator {
+            send_players(&mut server, *id, &tick, &lobby, &ship_type_query);
+            continue;
+        }
 
-                let message =
-                    bincode::serialize(&ServerMessages::PlayerConnected { id: *id }).unwrap();
-                server.broadcast_message(DefaultChannel::Reliable, message);
+        // The handshake only accepts known ship types, but the file may have been removed
+        // since.
+        let ship = match ship_types.get(&event.ship_type) {
+            Some(ship) => ship,
+            None => {
+                println!("Player {} flies unknown ship type {}.", id, event.ship_type);
+                continue;
             }
-            _ => (),
+        };
+
+        println!("Player {} connected.", id);
+        // Spawn the ship as its definition describes it
+        let player_entity = commands
+            .spawn(SpatialBundle {
+                transform: spawn_transform(),
+                ..Default::default()
+            })
+            .insert(PlayerShipType(event.ship_type.clone()))
+            .insert(PlayerInput::default())
+            .insert(InputQueue::default())
+            .insert(InputValidator::new(time.elapsed_seconds_f64()))
+            .insert(ShipVelocity::default())
+            .insert(NetworkedId {
+                id: event.player_id,
+            })
+            .insert(Player { team: Team::Red })
+            .insert(Health { health: ship.hull })
+            .insert(ship.collider.collider())
+            .insert(CollisionGroups::new(Group::GROUP_1, Group::GROUP_1))
+            // Moved by step_ship so clients can predict it exactly
+            .insert(RigidBody::KinematicPositionBased)
+            .with_children(|parent| {
+                for hardpoint in ship.hardpoints.iter() {
+                    parent
+                        .spawn((
+                            TransformBundle::from(hardpoint.transform()),
+                            TurretOwner::new(parent.parent_entity()),
+                            Turret {
+                                fire_rate: 1.0 / 10.,
+                                ..default()
+                            },
+                        ))
+                        .with_children(|parent| {
+                            parent.spawn((TransformBundle::default(), Barrel {}));
+                        });
+                }
+            })
+            .id();
+
+        // We could send an InitState with all the players id and positions for the client
+        // but this is easier to do.
+        send_players(&mut server, *id, &tick, &lobby, &ship_type_query);
+
+        lobby.players.insert(event.player_id, player_entity);
+
+        if let Some(recorder) = recorder.as_deref_mut() {
+            recorder.record(
+                tick.tick,
+                tick.tick_to_seconds(tick.tick),
+                ReplayFrame::PlayerJoined(ReplayPlayer {
+                    id: event.player_id,
+                    name: event.name.clone(),
+                    ship_type: event.ship_type.clone(),
+                }),
+            );
         }
+        broadcast_server_message(
+            &mut server,
+            &tick,
+            recorder.as_deref_mut(),
+            ServerMessages::PlayerConnected {
+                id: event.player_id,
+                ship_type: event.ship_type.clone(),
+            },
+        );
     }
 }
 
-fn on_client_model_loaded(
-    mut commands: Commands,
-    query: Query<(Entity, &ShipModelLoadHandle)>,
-    assets_gltf: Res<Assets<Gltf>>,
-    assets_gltfnode: Res<Assets<GltfNode>>,
+/// Tells a client about every ship in the lobby.
+fn send_players(
+    server: &mut RenetServer,
+    client_id: u64,
+    tick: &NetworkTick,
+    lobby: &Lobby,
+    ship_type_query: &Query<&PlayerShipType>,
 ) {
-    for (entity, handle) in query.iter() {
-        if let Some(gltf) = assets_gltf.get(&handle.0) {
-            println!("Loaded GLTF, spawning model and turrets");
-            // spawn the first scene in the file
-            let model = commands
-                .spawn(SceneBundle {
-                    scene: gltf.scenes[0].clone(),
-                    ..Default::default()
-                })
-                .id();
-            let mut turrets: Vec<Entity> = vec![];
-
-            for node_name in gltf.named_nodes.keys().into_iter() {
-                if node_name.contains("turret_pad_large") {
-                    if let Some(node) = assets_gltfnode.get(&gltf.named_nodes[node_name]) {
-                        println!("turret transform: {}", node.transform.translation);
-                        let thruster = commands
-                            .spawn((
-                                TransformBundle::from(node.transform),
-                                TurretOwner::new(entity),
-                                Turret {
-                                    fire_rate: 1.0 / 10.,
-                                    ..default()
-                                },
-                            ))
-                            .with_children(|parent| {
-                                parent.spawn((TransformBundle::default(), Barrel {}));
-                            })
-                            .id();
-                        turrets.push(thruster);
-                    }
-                }
-            }
-
-            commands
-                .entity(entity)
-                .push_children(&[model])
-                .push_children(&turrets)
-                .remove::<ShipModelLoadHandle>();
+    for (&player_id, entity) in lobby.players.iter() {
+        if let Ok(ship_type) = ship_type_query.get(*entity) {
+            send_server_message(
+                server,
+                client_id,
+                tick,
+                ServerMessages::PlayerConnected {
+                    id: player_id,
+                    ship_type: ship_type.0.clone(),
+                },
+            );
         }
     }
 }
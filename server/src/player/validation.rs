@@ -0,0 +1,228 @@
+use bevy::prelude::{Component, Vec3};
+use spaaaace_shared::player::player_input::PlayerInput;
+
+/// Furthest from its ship a player can aim.
+const MAX_AIM_DISTANCE: f32 = 2000.0;
+/// How much faster than the tick rate a client may send inputs, to absorb jitter.
+const INPUT_RATE_TOLERANCE: f32 = 1.5;
+/// Inputs a client may send in a burst before the rate limit kicks in.
+const INPUT_BURST: f32 = 8.0;
+/// Violations a client can accumulate before it is flagged. One is forgiven per second.
+const FLAG_THRESHOLD: f32 = 20.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InputViolation {
+    RateLimited,
+    StaleSequence,
+    InvalidAimPoint,
+    InvisibleTarget,
+}
+
+/// Checks the inputs a client sends before they are simulated.
+#[derive(Component)]
+pub struct InputValidator {
+    tokens: f32,
+    last_refill: f64,
+    last_sequence: u32,
+    last_target: u64,
+    violations: f32,
+    /// Set once a client has broken the limits too often.
+    pub flagged: bool,
+}
+
+impl InputValidator {
+    pub fn new(now: f64) -> Self {
+        Self {
+            tokens: INPUT_BURST,
+            last_refill: now,
+            last_sequence: 0,
+            last_target: 0,
+            violations: 0.0,
+            flagged: false,
+        }
+    }
+
+    /// Sequence of the newest input that was accepted.
+    pub fn last_sequence(&self) -> u32 {
+        self.last_sequence
+    }
+
+    /// Returns the input to simulate, with impossible values clamped, or `None` if it
+    /// should be dropped.
+    pub fn validate(
+        &mut self,
+        client_id: u64,
+        mut input: PlayerInput,
+        ship_translation: Vec3,
+        target_visible: bool,
+        now: f64,
+        tick_rate: f32,
+    ) -> Option<PlayerInput> {
+        let elapsed = (now - self.last_refill) as f32;
+        self.last_refill = now;
+        self.tokens = (self.tokens + elapsed * tick_rate * INPUT_RATE_TOLERANCE).min(INPUT_BURST);
+        self.violations = (self.violations - elapsed).max(0.0);
+
+        if self.tokens < 1.0 {
+            self.record(client_id, InputViolation::RateLimited);
+            return None;
+        }
+        self.tokens -= 1.0;
+
+        if input.sequence <= self.last_sequence {
+            self.record(client_id, InputViolation::StaleSequence);
+            return None;
+        }
+        self.last_sequence = input.sequence;
+
+        let aim_offset = input.aim_point - ship_translation;
+        if !aim_offset.is_finite() {
+            input.aim_point = ship_translation;
+            self.record(client_id, InputViolation::InvalidAimPoint);
+        } else if aim_offset.length() > MAX_AIM_DISTANCE {
+            input.aim_point = ship_translation + aim_offset.normalize() * MAX_AIM_DISTANCE;
+            self.record(client_id, InputViolation::InvalidAimPoint);
+        }
+
+        if input.target_network_id != self.last_target && !target_visible {
+            input.target_network_id = self.last_target;
+            self.record(client_id, InputViolation::InvisibleTarget);
+        }
+        self.last_target = input.target_network_id;
+
+        Some(input)
+    }
+
+    fn record(&mut self, client_id: u64, violation: InputViolation) {
+        self.violations += 1.0;
+        if !self.flagged && self.violations > FLAG_THRESHOLD {
+            self.flagged = true;
+            println!(
+                "Player {} flagged for breaking input limits, last violation: {:?}",
+                client_id, violation
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TICK_RATE: f32 = 10.0;
+
+    fn input(sequence: u32) -> PlayerInput {
+        PlayerInput {
+            sequence,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn allows_a_burst_then_rate_limits() {
+        let mut validator = InputValidator::new(0.0);
+        for sequence in 1..=INPUT_BURST as u32 {
+            assert!(validator
+                .validate(1, input(sequence), Vec3::ZERO, true, 0.0, TICK_RATE)
+                .is_some());
+        }
+        assert!(validator
+            .validate(1, input(100), Vec3::ZERO, true, 0.0, TICK_RATE)
+            .is_none());
+    }
+
+    #[test]
+    fn refills_tokens_over_time() {
+        let mut validator = InputValidator::new(0.0);
+        for sequence in 1..=INPUT_BURST as u32 {
+            validator.validate(1, input(sequence), Vec3::ZERO, true, 0.0, TICK_RATE);
+        }
+        // One tick at 1.5x the tick rate refills 1.5 tokens.
+        let now = 1.0 / TICK_RATE as f64;
+        assert!(validator
+            .validate(1, input(9), Vec3::ZERO, true, now, TICK_RATE)
+            .is_some());
+        assert!(validator
+            .validate(1, input(10), Vec3::ZERO, true, now, TICK_RATE)
+            .is_none());
+    }
+
+    #[test]
+    fn drops_stale_sequences() {
+        let mut validator = InputValidator::new(0.0);
+        assert!(validator
+            .validate(1, input(5), Vec3::ZERO, true, 0.0, TICK_RATE)
+            .is_some());
+        assert!(validator
+            .validate(1, input(5), Vec3::ZERO, true, 0.0, TICK_RATE)
+            .is_none());
+        assert!(validator
+            .validate(1, input(3), Vec3::ZERO, true, 0.0, TICK_RATE)
+            .is_none());
+        assert_eq!(validator.last_sequence(), 5);
+    }
+
+    #[test]
+    fn clamps_aim_point() {
+        let mut validator = InputValidator::new(0.0);
+        let ship = Vec3::new(10.0, 0.0, 0.0);
+
+        let mut far = input(1);
+        far.aim_point = ship + Vec3::X * 5000.0;
+        let far = validator
+            .validate(1, far, ship, true, 0.0, TICK_RATE)
+            .unwrap();
+        assert!((far.aim_point - (ship + Vec3::X * MAX_AIM_DISTANCE)).length() < 1e-3);
+
+        let mut invalid = input(2);
+        invalid.aim_point = Vec3::new(f32::NAN, 0.0, 0.0);
+        let invalid = validator
+            .validate(1, invalid, ship, true, 0.0, TICK_RATE)
+            .unwrap();
+        assert_eq!(invalid.aim_point, ship);
+
+        let mut near = input(3);
+        near.aim_point = ship + Vec3::Y * 100.0;
+        let near = validator
+            .validate(1, near, ship, true, 0.0, TICK_RATE)
+            .unwrap();
+        assert_eq!(near.aim_point, ship + Vec3::Y * 100.0);
+    }
+
+    #[test]
+    fn keeps_previous_target_when_new_one_is_invisible() {
+        let mut validator = InputValidator::new(0.0);
+        let mut visible = input(1);
+        visible.target_network_id = 7;
+        let visible = validator
+            .validate(1, visible, Vec3::ZERO, true, 0.0, TICK_RATE)
+            .unwrap();
+        assert_eq!(visible.target_network_id, 7);
+
+        let mut hidden = input(2);
+        hidden.target_network_id = 9;
+        let hidden = validator
+            .validate(1, hidden, Vec3::ZERO, false, 0.0, TICK_RATE)
+            .unwrap();
+        assert_eq!(hidden.target_network_id, 7);
+    }
+
+    #[test]
+    fn flags_after_repeated_violations() {
+        let mut validator = InputValidator::new(0.0);
+        for _ in 0..FLAG_THRESHOLD as u32 + 1 {
+            validator.validate(1, input(0), Vec3::ZERO, true, 0.0, TICK_RATE);
+        }
+        assert!(validator.flagged);
+    }
+
+    #[test]
+    fn forgives_violations_over_time() {
+        let mut validator = InputValidator::new(0.0);
+        for _ in 0..FLAG_THRESHOLD as u32 {
+            validator.validate(1, input(0), Vec3::ZERO, true, 0.0, TICK_RATE);
+        }
+        validator.validate(1, input(0), Vec3::ZERO, true, 5.0, TICK_RATE);
+        assert!(!validator.flagged);
+    }
+}
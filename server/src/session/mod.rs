@@ -0,0 +1,154 @@
+use std::collections::HashMap;
+
+use bevy::{
+    prelude::{App, EventWriter, Plugin, Res, ResMut, Resource},
+    time::Time,
+};
+use spaaaace_shared::NetworkIdProvider;
+
+/// Keeps the ships of disconnected players around for a while so they can reconnect
+/// and take them back.
+pub struct SessionPlugin {
+    /// Seconds a ship is kept after its client disconnects.
+    pub grace_period: f32,
+}
+
+impl Plugin for SessionPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(Sessions::new(self.grace_period))
+            .add_event::<SessionExpired>()
+            .add_system(expire_sessions);
+    }
+}
+
+/// Raised once a disconnected player's grace period is over and their ship should go.
+pub struct SessionExpired {
+    pub player_id: u64,
+}
+
+struct Session {
+    player_id: u64,
+    /// Client currently flying the ship, `None` while it waits for a reconnect.
+    client_id: Option<u64>,
+    disconnected_at: f64,
+}
+
+/// Client ids are derived from the time a client connected, so a reconnecting client
+/// gets a new one. Ships keep the player id they were spawned with, which comes from the
+/// [`NetworkIdProvider`], and are claimed back with the session token handed out on
+/// joining.
+#[derive(Resource)]
+pub struct Sessions {
+    pub grace_period: f32,
+    /// Keyed by session token.
+    sessions: HashMap<u64, Session>,
+    /// Player id of every connected client that has a ship.
+    players: HashMap<u64, u64>,
+}
+
+impl Sessions {
+    pub fn new(grace_period: f32) -> Self {
+        Self {
+            grace_period,
+            sessions: HashMap::new(),
+            players: HashMap::new(),
+        }
+    }
+
+    /// The ship a connected client flies.
+    pub fn player_id(&self, client_id: u64) -> Option<u64> {
+        self.players.get(&client_id).copied()
+    }
+
+    pub fn can_resume(&self, token: u64) -> bool {
+        self.sessions.contains_key(&token)
+    }
+
+    /// Ships waiting for their client to reconnect, they still take up a player slot.
+    pub fn held(&self) -> usize {
+        self.sessions
+            .values()
+            .filter(|session| session.client_id.is_none())
+            .count()
+    }
+
+    /// Starts a session for `client_id`, or hands it the ship of the session `token`
+    /// belongs to. Returns the player id, the token to resume with next time and the
+    /// client that was flying the ship until now, if its connection hasn't timed out yet.
+    pub fn accept(
+        &mut self,
+        client_id: u64,
+        token: Option<u64>,
+        id_provider: &mut NetworkIdProvider,
+    ) -> (u64, u64, Option<u64>) {
+        if let Some(session) = token.and_then(|token| self.sessions.get_mut(&token)) {
+            let previous_client = session.client_id.replace(client_id);
+            if let Some(previous_client) = previous_client {
+                self.players.remove(&previous_client);
+            }
+            self.players.insert(client_id, session.player_id);
+            return (session.player_id, token.unwrap(), previous_client);
+        }
+
+        let mut token = rand::random();
+        while self.sessions.contains_key(&token) {
+            token = rand::random();
+        }
+        // Freed once the ship is despawned, see `track_networked_entities`.
+        let player_id = id_provider.new_id().id;
+        self.sessions.insert(
+            token,
+            Session {
+                player_id,
+                client_id: Some(client_id),
+                disconnected_at: 0.0,
+            },
+        );
+        self.players.insert(client_id, player_id);
+        (player_id, token, None)
+    }
+
+    /// Starts the grace period of the client's ship. Returns the player id, or `None`
+    /// if the client had no ship.
+    pub fn disconnect(&mut self, client_id: u64, now: f64) -> Option<u64> {
+        let player_id = self.players.remove(&client_id)?;
+        for session in self.sessions.values_mut() {
+            if session.client_id == Some(client_id) {
+                session.client_id = None;
+                session.disconnected_at = now;
+            }
+        }
+        Some(player_id)
+    }
+
+    /// Ends the client's session right away, its ship goes on the next update instead
+    /// of waiting for a reconnect. Returns the player id, or `None` if the client had
+    /// no ship.
+    pub fn end(&mut self, client_id: u64) -> Option<u64> {
+        let player_id = self.players.remove(&client_id)?;
+        for session in self.sessions.values_mut() {
+            if session.client_id == Some(client_id) {
+                session.client_id = None;
+                session.disconnected_at = f64::NEG_INFINITY;
+            }
+        }
+        Some(player_id)
+    }
+}
+
+fn expire_sessions(
+    mut sessions: ResMut<Sessions>,
+    mut expired_writer: EventWriter<SessionExpired>,
+    time: Res<Time>,
+) {
+    let expire_before = time.elapsed_seconds_f64() - sessions.grace_period as f64;
+    sessions.sessions.retain(|_, session| {
+        if session.client_id.is_some() || session.disconnected_at > expire_before {
+            return true;
+        }
+        expired_writer.send(SessionExpired {
+            player_id: session.player_id,
+        });
+        false
+    });
+}
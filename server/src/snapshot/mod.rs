@@ -0,0 +1,241 @@
+use std::collections::HashMap;
+
+use bevy::prelude::{App, EventReader, Plugin, Query, Res, ResMut, Resource, Transform, Vec3};
+use bevy_rapier3d::prelude::Sleeping;
+use bevy_renet::renet::{DefaultChannel, RenetServer, ServerEvent};
+use spaaaace_shared::{
+    handshake::ClientAccepted,
+    player::{movement::ShipVelocity, player_input::PlayerInput},
+    replay::{ReplayFrame, ReplayRecorder},
+    snapshot::SnapshotEncoder,
+    tick::NetworkTick,
+    ClientMessages, Lobby, NetworkedId, PlayerState, Snapshot, TranslationRotation,
+};
+
+use crate::{
+    player::InputQueue,
+    tick::{TickAppExt, TickStage},
+    ClientEvent,
+};
+
+#[derive(Resource)]
+pub struct SnapshotSettings {
+    /// Positions are rounded to a multiple of this many units.
+    pub precision: f32,
+    /// Most entities sent to a client in a single snapshot.
+    pub max_entities: usize,
+    /// Entities within this distance of a client's ship are updated at full priority.
+    pub interest_radius: f32,
+    /// Entities further than this from a client's ship are not sent to it at all.
+    pub cull_radius: f32,
+}
+
+impl Default for SnapshotSettings {
+    fn default() -> Self {
+        Self {
+            precision: 0.01,
+            max_entities: 70,
+            interest_radius: 250.0,
+            cull_radius: 600.0,
+        }
+    }
+}
+
+/// How much more often the entity a player is targeting is updated.
+const TARGET_PRIORITY: f32 = 4.0;
+
+pub struct SnapshotClient {
+    /// Id of the client's ship, see [`crate::session::Sessions`].
+    pub player_id: u64,
+    /// Each client acknowledges different snapshots, so each gets its own encoder.
+    pub encoder: SnapshotEncoder,
+    /// Grows every tick an entity is relevant but not sent, and is reset once it is.
+    pub priorities: HashMap<u64, f32>,
+}
+
+#[derive(Resource, Default)]
+pub struct SnapshotClients(pub HashMap<u64, SnapshotClient>);
+pub struct SnapshotPlugin;
+
+impl Plugin for SnapshotPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<SnapshotSettings>()
+            .init_resource::<SnapshotClients>()
+            .add_system(track_clients)
+            .add_system(snapshot_acknowledged)
+            .add_tick_system(TickStage::Broadcast, server_sync_players);
+    }
+}
+
+fn track_clients(
+    mut accepted_reader: EventReader<ClientAccepted>,
+    mut event_reader: EventReader<ServerEvent>,
+    mut clients: ResMut<SnapshotClients>,
+    settings: Res<SnapshotSettings>,
+) {
+    for event in accepted_reader.iter() {
+        clients.0.insert(
+            event.client_id,
+            SnapshotClient {
+                player_id: event.player_id,
+                encoder: SnapshotEncoder::new(settings.precision),
+                priorities: HashMap::new(),
+            },
+        );
+    }
+
+    for event in event_reader.iter() {
+        if let ServerEvent::ClientDisconnected(id) = event {
+            clients.0.remove(id);
+        }
+    }
+}
+
+fn snapshot_acknowledged(
+    mut client_message_event_reader: EventReader<ClientEvent>,
+    mut clients: ResMut<SnapshotClients>,
+) {
+    for event in client_message_event_reader.iter() {
+        if let ClientMessages::SnapshotAck { tick } = event.message {
+            if let Some(client) = clients.0.get_mut(&event.client_id) {
+                client.encoder.acknowledge(tick);
+            }
+        }
+    }
+}
+
+/// How much an entity at `translation` matters to a client whose ship is at `origin`.
+fn relevance(settings: &SnapshotSettings, origin: Option<Vec3>, translation: Vec3) -> f32 {
+    let origin = match origin {
+        Some(origin) => origin,
+        // Without a ship everything is equally relevant.
+        None => return 1.0,
+    };
+
+    let distance = origin.distance(translation);
+    if distance > settings.cull_radius {
+        0.0
+    } else if distance > settings.interest_radius {
+        (settings.interest_radius / distance).powi(2)
+    } else {
+        2.0 - distance / settings.interest_radius
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn server_sync_players(
+    mut server: ResMut<RenetServer>,
+    mut clients: ResMut<SnapshotClients>,
+    settings: Res<SnapshotSettings>,
+    lobby: Res<Lobby>,
+    query: Query<(&Transform, &NetworkedId, Option<&Sleeping>)>,
+    player_query: Query<(&NetworkedId, &ShipVelocity, &InputQueue, &PlayerInput)>,
+    recorder: Option<ResMut<ReplayRecorder>>,
+    tick: Res<NetworkTick>,
+) {
+    let mut entities: HashMap<u64, TranslationRotation> = HashMap::new();
+
+    for (transform, network_id, sleeping) in query.iter() {
+        if sleeping.is_some() && sleeping.unwrap().sleeping {
+            continue;
+        }
+        entities.insert(
+            network_id.id,
+            TranslationRotation {
+                translation: transform.translation,
+                rotation: transform.rotation,
+            },
+        );
+    }
+
+    let mut players: HashMap<u64, PlayerState> = HashMap::new();
+
+    for (network_id, velocity, queue, _) in player_query.iter() {
+        players.insert(
+            network_id.id,
+            PlayerState {
+                velocity: *velocity,
+                last_processed_input: queue.last_processed,
+            },
+        );
+    }
+
+    for (client_id, client) in clients.0.iter_mut() {
+        let player_id = client.player_id;
+        let ship = lobby.players.get(&player_id);
+        let origin = ship
+            .and_then(|entity| query.get(*entity).ok())
+            .map(|(transform, _, _)| transform.translation);
+        let target = ship
+            .and_then(|entity| player_query.get(*entity).ok())
+            .map(|(_, _, _, input)| input.target_network_id);
+
+        client.priorities.retain(|id, _| entities.contains_key(id));
+
+        let mut candidates: Vec<(u64, f32)> = Vec::new();
+
+        for (id, translation_rotation) in entities.iter() {
+            // The client reconciles its own ship against every snapshot.
+            if *id == player_id {
+                continue;
+            }
+
+            let mut weight = relevance(&settings, origin, translation_rotation.translation);
+            if weight <= 0.0 {
+                client.priorities.remove(id);
+                continue;
+            }
+            if target == Some(*id) {
+                weight *= TARGET_PRIORITY;
+            }
+
+            let priority = client.priorities.entry(*id).or_insert(0.0);
+            *priority += weight;
+            candidates.push((*id, *priority));
+        }
+
+        candidates.sort_by(|(_, a), (_, b)| b.total_cmp(a));
+
+        let mut snapshot = Snapshot {
+            tick: tick.tick,
+            ..Default::default()
+        };
+
+        if let Some(translation_rotation) = entities.get(&player_id) {
+            snapshot
+                .entities
+                .insert(player_id, translation_rotation.clone());
+        }
+
+        for (id, _) in candidates {
+            if snapshot.entities.len() >= settings.max_entities {
+                break;
+            }
+            snapshot.entities.insert(id, entities[&id].clone());
+            client.priorities.insert(id, 0.0);
+        }
+
+        for (id, state) in players.iter() {
+            if snapshot.entities.contains_key(id) {
+                snapshot.players.insert(*id, *state);
+            }
+        }
+
+        let sync_message = client.encoder.encode(&snapshot);
+        server.send_message(*client_id, DefaultChannel::Unreliable, sync_message);
+    }
+
+    // Replays get everything, whoever watches them may look anywhere.
+    if let Some(mut recorder) = recorder {
+        recorder.record(
+            tick.tick,
+            tick.tick_to_seconds(tick.tick),
+            ReplayFrame::Snapshot(Snapshot {
+                tick: tick.tick,
+                entities,
+                players,
+            }),
+        );
+        recorder.flush();
+    }
+}
@@ -0,0 +1,99 @@
+use bevy::prelude::{
+    App, CoreStage, IntoSystemDescriptor, Plugin, Schedule, StageLabel, SystemStage,
+};
+use bevy_rapier3d::prelude::{
+    NoUserData, PhysicsStages, RapierConfiguration, RapierPhysicsPlugin, TimestepMode,
+};
+use spaaaace_shared::tick::{run_on_tick, NetworkTick};
+
+/// Runs once per server tick, see [`run_on_tick`].
+#[derive(Debug, Hash, PartialEq, Eq, Clone, StageLabel)]
+pub struct FixedUpdateStage;
+
+/// Stages inside [`FixedUpdateStage`]. Physics steps between the two.
+#[derive(Debug, Hash, PartialEq, Eq, Clone, StageLabel)]
+pub enum TickStage {
+    Simulate,
+    Broadcast,
+}
+
+pub struct ServerTickPlugin {
+    /// Ticks per second.
+    pub tick_rate: f32,
+}
+
+impl Plugin for ServerTickPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(NetworkTick::new(self.tick_rate))
+            .add_plugin(
+                RapierPhysicsPlugin::<NoUserData>::default().with_default_system_setup(false),
+            )
+            .insert_resource(RapierConfiguration {
+                timestep_mode: TimestepMode::Fixed {
+                    dt: 1.0 / self.tick_rate,
+                    substeps: 1,
+                },
+                ..Default::default()
+            })
+            .add_stage_after(
+                CoreStage::Update,
+                FixedUpdateStage,
+                Schedule::default()
+                    .with_run_criteria(run_on_tick)
+                    .with_stage(TickStage::Simulate, SystemStage::parallel())
+                    .with_stage(
+                        PhysicsStages::SyncBackend,
+                        SystemStage::parallel().with_system_set(
+                            RapierPhysicsPlugin::<NoUserData>::get_systems(
+                                PhysicsStages::SyncBackend,
+                            ),
+                        ),
+                    )
+                    .with_stage(
+                        PhysicsStages::StepSimulation,
+                        SystemStage::parallel().with_system_set(
+                            RapierPhysicsPlugin::<NoUserData>::get_systems(
+                                PhysicsStages::StepSimulation,
+                            ),
+                        ),
+                    )
+                    .with_stage(
+                        PhysicsStages::Writeback,
+                        SystemStage::parallel().with_system_set(
+                            RapierPhysicsPlugin::<NoUserData>::get_systems(
+                                PhysicsStages::Writeback,
+                            ),
+                        ),
+                    )
+                    .with_stage(TickStage::Broadcast, SystemStage::parallel()),
+            )
+            .add_stage_before(
+                CoreStage::Last,
+                PhysicsStages::DetectDespawn,
+                SystemStage::parallel().with_system_set(
+                    RapierPhysicsPlugin::<NoUserData>::get_systems(PhysicsStages::DetectDespawn),
+                ),
+            );
+    }
+}
+
+pub trait TickAppExt {
+    /// Adds a system that runs once per server tick.
+    fn add_tick_system<Params>(
+        &mut self,
+        stage: TickStage,
+        system: impl IntoSystemDescriptor<Params>,
+    ) -> &mut Self;
+}
+
+impl TickAppExt for App {
+    fn add_tick_system<Params>(
+        &mut self,
+        stage: TickStage,
+        system: impl IntoSystemDescriptor<Params>,
+    ) -> &mut Self {
+        self.stage(FixedUpdateStage, |schedule: &mut Schedule| {
+            schedule.add_system_to_stage(stage, system)
+        })
+    }
+}
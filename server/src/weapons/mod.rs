@@ -0,0 +1,22 @@
+use bevy::prelude::{App, IntoSystemDescriptor, Plugin};
+use spaaaace_shared::{
+    health::death,
+    weapons::{fire_weapons_server, trigger_weapons, turn_turrets},
+};
+
+use crate::tick::{TickAppExt, TickStage};
+
+/// Runs the shared weapon systems and [`death`] once per server tick.
+pub struct ServerWeaponsPlugin;
+
+impl Plugin for ServerWeaponsPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_tick_system(TickStage::Simulate, trigger_weapons)
+            .add_tick_system(TickStage::Simulate, turn_turrets)
+            .add_tick_system(
+                TickStage::Simulate,
+                fire_weapons_server.after(trigger_weapons),
+            )
+            .add_tick_system(TickStage::Simulate, death);
+    }
+}
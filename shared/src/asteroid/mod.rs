@@ -1,30 +1,38 @@
 use bevy::{
     prelude::{
-        default, App, AssetServer, Commands, Component, EventReader, Plugin, Quat, Query, Res,
-        ResMut, SystemSet, Transform, Vec3, With,
+        Added, App, AssetServer, Commands, Component, Entity, Plugin, Quat, Query, Res, ResMut,
+        Scene, SystemSet, Transform, Vec3,
     },
-    scene::SceneBundle,
     transform::TransformBundle,
 };
 use bevy_rapier3d::prelude::{
     Collider, ColliderMassProperties, Damping, GravityScale, RigidBody, Sleeping,
 };
-use bevy_renet::renet::{DefaultChannel, RenetServer, ServerEvent};
 use rand::Rng;
+use serde::{Deserialize, Serialize};
 
 use crate::{
-    health::Health, run_if_client, run_if_server, targeting::Targetable, util::Random, Lobby,
-    NetworkIdProvider, NetworkedId, ServerMessages,
+    health::Health,
+    replication::{Replicated, ReplicationAppExt},
+    run_if_client, run_if_server,
+    targeting::Targetable,
+    util::Random,
+    NetworkIdProvider,
 };
 
-#[derive(Component)]
+#[derive(Component, Clone, Serialize, Deserialize)]
 pub struct Asteroid;
 
+impl Replicated for Asteroid {
+    const NAME: &'static str = "asteroid";
+}
+
 pub struct AsteroidPlugin;
 
 impl Plugin for AsteroidPlugin {
     fn build(&self, app: &mut App) {
         //Both
+        app.replicate::<Asteroid>();
 
         //Client
         app.add_system_set(
@@ -34,12 +42,7 @@ impl Plugin for AsteroidPlugin {
         );
 
         // Server
-        app.add_system_set(
-            SystemSet::new()
-                .with_run_criteria(run_if_server)
-                .with_system(on_client_connected),
-        )
-        .add_startup_system_set(
+        app.add_startup_system_set(
             SystemSet::new()
                 .with_run_criteria(run_if_server)
                 .with_system(spawn_asteroids),
@@ -47,103 +50,71 @@ impl Plugin for AsteroidPlugin {
     }
 }
 
+/// Asteroids scattered over the map at the start of a match.
+pub const ASTEROID_COUNT: usize = 20;
+
 fn spawn_asteroids(
     mut commands: Commands, //
     mut id_provider: ResMut<NetworkIdProvider>,
 ) {
     let mut rng = rand::thread_rng();
-    for _ in 0..20 {
-        let x = Transform {
-            translation: Vec3 {
-                x: rng.gen::<f32>() * 250.0,
-                y: rng.gen::<f32>() * 250.0,
-                z: rng.gen::<f32>() * 250.0,
-            },
-            scale: Vec3::splat(2.0 + rng.gen::<f32>() * 8.0),
-            rotation: Quat::random(),
-        };
+    for _ in 0..ASTEROID_COUNT {
+        spawn_asteroid(
+            &mut commands,
+            &mut id_provider,
+            random_asteroid_transform(&mut rng),
+        );
+    }
+}
 
-        commands
-            .spawn(Collider::ball(1.0))
-            .insert(RigidBody::Dynamic)
-            .insert(GravityScale(0.0))
-            .insert(TransformBundle::from(x))
-            .insert(Damping {
-                angular_damping: 1.,
-                linear_damping: 1.,
-            })
-            .insert(Sleeping {
-                angular_threshold: 100.0,
-                linear_threshold: 100.0,
-                sleeping: true,
-            })
-            .insert(ColliderMassProperties::Density(1.0))
-            .insert(id_provider.new_id())
-            .insert(Asteroid)
-            .insert(Health { health: 10.0 });
+/// Somewhere in the asteroid field, with a random size and rotation.
+pub fn random_asteroid_transform(rng: &mut impl Rng) -> Transform {
+    Transform {
+        translation: Vec3 {
+            x: rng.gen::<f32>() * 250.0,
+            y: rng.gen::<f32>() * 250.0,
+            z: rng.gen::<f32>() * 250.0,
+        },
+        scale: Vec3::splat(2.0 + rng.gen::<f32>() * 8.0),
+        rotation: Quat::random(),
     }
 }
 
-fn on_client_connected(
-    mut event_reader: EventReader<ServerEvent>,
-    mut server: ResMut<RenetServer>,
-    query: Query<(&Transform, &NetworkedId), With<Asteroid>>,
+pub fn spawn_asteroid(
+    commands: &mut Commands,
+    id_provider: &mut NetworkIdProvider,
+    transform: Transform,
 ) {
-    for event in event_reader.iter() {
-        match event {
-            ServerEvent::ClientConnected(id, _) => {
-                for (transform, network_id) in query.iter() {
-                    let message = bincode::serialize(&ServerMessages::AsteroidSpawned {
-                        id: network_id.id,
-                        position: transform.translation,
-                        scale: transform.scale,
-                        rotation: transform.rotation,
-                    })
-                    .unwrap();
-                    server.send_message(*id, DefaultChannel::Reliable, message);
-                }
-            }
-            _ => (),
-        }
-    }
+    commands
+        .spawn(Collider::ball(1.0))
+        .insert(RigidBody::Dynamic)
+        .insert(GravityScale(0.0))
+        .insert(TransformBundle::from(transform))
+        .insert(Damping {
+            angular_damping: 1.,
+            linear_damping: 1.,
+        })
+        .insert(Sleeping {
+            angular_threshold: 100.0,
+            linear_threshold: 100.0,
+            sleeping: true,
+        })
+        .insert(ColliderMassProperties::Density(1.0))
+        .insert(id_provider.new_id())
+        .insert(Asteroid)
+        .insert(Health { health: 10.0 });
 }
 
 fn on_asteroid_spawned(
     mut commands: Commands,
-    mut lobby: ResMut<Lobby>,
-    mut event_reader: EventReader<ServerMessages>,
+    query: Query<Entity, Added<Asteroid>>,
     ass: Res<AssetServer>,
 ) {
-    for event in event_reader.iter() {
-        match event {
-            ServerMessages::AsteroidSpawned {
-                id,
-                position,
-                scale,
-                rotation,
-            } => {
-                let x = commands
-                    .spawn(SceneBundle {
-                        scene: ass.load("asteroid.glb#Scene0"),
-                        transform: Transform {
-                            translation: *position,
-                            scale: *scale,
-                            rotation: *rotation,
-                            ..default()
-                        },
-                        ..default()
-                    })
-                    .insert(Targetable {})
-                    .insert(NetworkedId {
-                        id: *id,
-                        last_sent: 0,
-                    })
-                    .insert(Collider::ball(1.0))
-                    .id();
-
-                lobby.networked_entities.insert(*id, x);
-            }
-            _ => {}
-        }
+    for entity in query.iter() {
+        commands
+            .entity(entity)
+            .insert(ass.load::<Scene, _>("asteroid.glb#Scene0"))
+            .insert(Targetable {})
+            .insert(Collider::ball(1.0));
     }
 }
@@ -0,0 +1,132 @@
+use std::{
+    fs::{self, File},
+    io,
+    net::SocketAddr,
+    path::Path,
+    time::SystemTime,
+};
+
+use bevy_renet::renet::{ConnectToken, NETCODE_KEY_BYTES};
+
+use crate::PROTOCOL_ID;
+
+pub const DEFAULT_PRIVATE_KEY_PATH: &str = "private.key";
+pub const DEFAULT_CONNECT_TOKEN_PATH: &str = "connect.token";
+
+/// Seconds a client may go without hearing from the server before a token based
+/// connection times out.
+const TOKEN_TIMEOUT_SECONDS: i32 = 15;
+
+/// Private keys are stored as a single line of hex.
+pub fn read_private_key(path: &Path) -> io::Result<[u8; NETCODE_KEY_BYTES]> {
+    let contents = fs::read_to_string(path)?;
+    let hex = contents.trim();
+    let invalid = || {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!(
+                "expected {} hex characters in {}",
+                NETCODE_KEY_BYTES * 2,
+                path.display()
+            ),
+        )
+    };
+
+    // Slicing below is by byte, anything but ASCII could split a character.
+    if !hex.is_ascii() || hex.len() != NETCODE_KEY_BYTES * 2 {
+        return Err(invalid());
+    }
+
+    let mut key = [0; NETCODE_KEY_BYTES];
+    for (index, byte) in key.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(&hex[index * 2..index * 2 + 2], 16).map_err(|_| invalid())?;
+    }
+    Ok(key)
+}
+
+pub fn write_private_key(path: &Path, key: &[u8; NETCODE_KEY_BYTES]) -> io::Result<()> {
+    let hex: String = key.iter().map(|byte| format!("{:02x}", byte)).collect();
+    fs::write(path, hex + "\n")
+}
+
+pub fn generate_private_key() -> [u8; NETCODE_KEY_BYTES] {
+    rand::random()
+}
+
+/// Creates a token that lets `client_id` connect to `server_addr` for the next
+/// `expire_seconds` seconds.
+pub fn generate_connect_token(
+    private_key: &[u8; NETCODE_KEY_BYTES],
+    client_id: u64,
+    server_addr: SocketAddr,
+    expire_seconds: u64,
+) -> ConnectToken {
+    let current_time = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap();
+    ConnectToken::generate(
+        current_time,
+        PROTOCOL_ID,
+        expire_seconds,
+        client_id,
+        TOKEN_TIMEOUT_SECONDS,
+        vec![server_addr],
+        None,
+        private_key,
+    )
+    .unwrap()
+}
+
+pub fn write_connect_token(path: &Path, token: &ConnectToken) -> io::Result<()> {
+    let mut file = File::create(path)?;
+    token.write(&mut file)
+}
+
+pub fn read_connect_token(path: &Path) -> io::Result<ConnectToken> {
+    let mut file = File::open(path)?;
+    ConnectToken::read(&mut file)
+        .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+
+    fn key_file(name: &str, contents: &str) -> PathBuf {
+        let path =
+            std::env::temp_dir().join(format!("spaaaace-{}-{}.key", name, std::process::id()));
+        fs::write(&path, contents).unwrap();
+        path
+    }
+
+    #[test]
+    fn private_key_round_trip() {
+        let key = generate_private_key();
+        let path = key_file("round-trip", "");
+        write_private_key(&path, &key).unwrap();
+        assert_eq!(read_private_key(&path).unwrap(), key);
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn bad_private_keys_are_errors() {
+        let hex_length = NETCODE_KEY_BYTES * 2;
+        for (name, contents) in [
+            ("short", "ab".repeat(NETCODE_KEY_BYTES - 1)),
+            ("not-hex", "zz".repeat(NETCODE_KEY_BYTES)),
+            // The right number of bytes, but a multi-byte character where a pair starts.
+            ("non-ascii", format!("é{}", "a".repeat(hex_length - 2))),
+            (
+                "split-character",
+                format!("a€{}", "a".repeat(hex_length - 4)),
+            ),
+        ] {
+            let path = key_file(name, &contents);
+            let error = read_private_key(&path).unwrap_err();
+            assert_eq!(error.kind(), io::ErrorKind::InvalidData, "{}", name);
+            fs::remove_file(path).unwrap();
+        }
+    }
+}
@@ -0,0 +1,27 @@
+use bevy::prelude::{App, Component, Plugin};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    replication::{Replicated, ReplicationAppExt},
+    team::team_enum::Team,
+};
+
+#[derive(Component, Clone, Serialize, Deserialize)]
+pub struct CapturePoint {
+    pub radius: f32,
+    pub progress: f32,
+    pub owner: Team,
+    pub attacker: Team,
+}
+
+impl Replicated for CapturePoint {
+    const NAME: &'static str = "capture_point";
+}
+
+pub struct CapturePointPlugin;
+
+impl Plugin for CapturePointPlugin {
+    fn build(&self, app: &mut App) {
+        app.replicate::<CapturePoint>();
+    }
+}
@@ -0,0 +1,67 @@
+use serde::{Deserialize, Serialize};
+
+/// Longest chat message in characters, longer ones are cut off.
+pub const MAX_CHAT_LENGTH: usize = 160;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ChatChannel {
+    /// Everyone on the server, spectators included.
+    All,
+    /// Only the players on the sender's team.
+    Team,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ChatSender {
+    /// An announcement, or the server telling a player something.
+    Server,
+    Player {
+        player_id: u64,
+        name: String,
+    },
+}
+
+/// Removes control characters, trims the message and cuts it to [`MAX_CHAT_LENGTH`].
+/// `None` if nothing is left to send.
+pub fn clean_chat_message(text: &str) -> Option<String> {
+    let text: String = text.chars().filter(|c| !c.is_control()).collect();
+    let text: String = text.trim().chars().take(MAX_CHAT_LENGTH).collect();
+    match text.is_empty() {
+        true => None,
+        false => Some(text),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn messages_are_cleaned() {
+        assert_eq!(clean_chat_message("  hello  "), Some("hello".to_string()));
+        assert_eq!(
+            clean_chat_message("gg\u{7} wp\r\n"),
+            Some("gg wp".to_string())
+        );
+        assert_eq!(
+            clean_chat_message("\u{1b}[31m red"),
+            Some("[31m red".to_string())
+        );
+        assert_eq!(clean_chat_message("\u{7} hi"), Some("hi".to_string()));
+        assert_eq!(clean_chat_message("héllo 🚀"), Some("héllo 🚀".to_string()));
+    }
+
+    #[test]
+    fn empty_messages_are_dropped() {
+        assert_eq!(clean_chat_message(""), None);
+        assert_eq!(clean_chat_message("   "), None);
+        assert_eq!(clean_chat_message("\n\t\u{0}\u{7f}"), None);
+    }
+
+    #[test]
+    fn long_messages_are_cut_by_characters() {
+        let long = "é".repeat(MAX_CHAT_LENGTH + 10);
+        let cleaned = clean_chat_message(&long).unwrap();
+        assert_eq!(cleaned.chars().count(), MAX_CHAT_LENGTH);
+    }
+}
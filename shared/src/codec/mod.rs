@@ -0,0 +1,50 @@
+use std::{error::Error, fmt};
+
+use bincode::Options;
+use serde::de::DeserializeOwned;
+
+/// Largest message we are willing to decode, so a bogus length prefix can't make us
+/// allocate gigabytes.
+const MAX_MESSAGE_BYTES: u64 = 64 * 1024;
+
+#[derive(Debug)]
+pub enum DecodeError {
+    /// Truncated, corrupt or from a different protocol version.
+    Malformed(bincode::Error),
+    /// A delta snapshot is relative to a snapshot we no longer have.
+    UnknownBaseline { tick: u64 },
+    /// A delta snapshot entry refers to an entity its baseline doesn't have.
+    MissingBaselineEntity { id: u64 },
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DecodeError::Malformed(error) => write!(f, "malformed message: {}", error),
+            DecodeError::UnknownBaseline { tick } => {
+                write!(f, "snapshot baseline {} is unknown", tick)
+            }
+            DecodeError::MissingBaselineEntity { id } => {
+                write!(f, "entity {} is not in the snapshot baseline", id)
+            }
+        }
+    }
+}
+
+impl Error for DecodeError {}
+
+impl From<bincode::Error> for DecodeError {
+    fn from(error: bincode::Error) -> Self {
+        DecodeError::Malformed(error)
+    }
+}
+
+/// Same encoding as `bincode::deserialize`, but with a size limit and an error
+/// instead of a panic for bad input.
+pub fn decode<T: DeserializeOwned>(bytes: &[u8]) -> Result<T, DecodeError> {
+    Ok(bincode::options()
+        .with_fixint_encoding()
+        .allow_trailing_bytes()
+        .with_limit(MAX_MESSAGE_BYTES)
+        .deserialize(bytes)?)
+}
@@ -0,0 +1,331 @@
+use std::fmt;
+
+use bevy::prelude::Vec3;
+use serde::{Deserialize, Serialize};
+
+use crate::team::team_enum::Team;
+
+/// Something a player or admin asks the server to do, typed into the console as a line
+/// like `swap_team 1` and sent already parsed.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Command {
+    Help,
+    Login { password: String },
+    SwapTeam { team: Team },
+    Players,
+    Kick { client_id: u64 },
+    Ban { client_id: u64 },
+    Map { name: String },
+    Restart,
+    TickRate { tick_rate: f32 },
+    SpawnAsteroid { position: Option<Vec3> },
+    Say { text: String },
+}
+
+/// How a command is typed, for help texts and autocompletion.
+#[derive(Debug)]
+pub struct CommandSpec {
+    pub name: &'static str,
+    pub usage: &'static str,
+    pub help: &'static str,
+}
+
+/// Every command, in the order `help` lists them.
+pub const COMMANDS: &[CommandSpec] = &[
+    CommandSpec {
+        name: "help",
+        usage: "",
+        help: "Lists the commands you can run.",
+    },
+    CommandSpec {
+        name: "login",
+        usage: "<password>",
+        help: "Unlocks the admin commands.",
+    },
+    CommandSpec {
+        name: "swap_team",
+        usage: "<1 or 2>",
+        help: "Switches your ship to the red (1) or blue (2) team.",
+    },
+    CommandSpec {
+        name: "players",
+        usage: "",
+        help: "Lists the connected clients.",
+    },
+    CommandSpec {
+        name: "kick",
+        usage: "<client id>",
+        help: "Disconnects a client and removes their ship.",
+    },
+    CommandSpec {
+        name: "ban",
+        usage: "<client id>",
+        help: "Kicks a client and keeps them from joining until the server restarts.",
+    },
+    CommandSpec {
+        name: "map",
+        usage: "<name>",
+        help: "Restarts the match on another map.",
+    },
+    CommandSpec {
+        name: "restart",
+        usage: "",
+        help: "Restarts the match.",
+    },
+    CommandSpec {
+        name: "tickrate",
+        usage: "<ticks per second>",
+        help: "Changes the simulation rate.",
+    },
+    CommandSpec {
+        name: "spawn_asteroid",
+        usage: "[x y z]",
+        help: "Spawns an asteroid, somewhere random without a position.",
+    },
+    CommandSpec {
+        name: "say",
+        usage: "<message>",
+        help: "Announces a message to everyone.",
+    },
+];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CommandError {
+    Empty,
+    Unknown {
+        name: String,
+    },
+    BadArguments {
+        name: &'static str,
+        usage: &'static str,
+    },
+}
+
+impl fmt::Display for CommandError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CommandError::Empty => write!(f, "Type a command, try help."),
+            CommandError::Unknown { name } => write!(f, "Unknown command {}, try help.", name),
+            CommandError::BadArguments { name, usage } => {
+                write!(f, "Usage: {} {}", name, usage)
+            }
+        }
+    }
+}
+
+impl Command {
+    /// Parses a console line. The arguments are checked here, whether the values make
+    /// sense is up to the server.
+    pub fn parse(line: &str) -> Result<Command, CommandError> {
+        let mut words = line.split_whitespace();
+        let name = words.next().ok_or(CommandError::Empty)?;
+        let args: Vec<&str> = words.collect();
+        let spec = find_command(name).ok_or_else(|| CommandError::Unknown {
+            name: name.to_string(),
+        })?;
+        let bad_arguments = CommandError::BadArguments {
+            name: spec.name,
+            usage: spec.usage,
+        };
+
+        let command = match (spec.name, &args[..]) {
+            ("help", []) => Command::Help,
+            ("login", [password]) => Command::Login {
+                password: password.to_string(),
+            },
+            ("swap_team", [team]) => match *team {
+                "1" | "red" => Command::SwapTeam { team: Team::Red },
+                "2" | "blue" => Command::SwapTeam { team: Team::Blue },
+                _ => return Err(bad_arguments),
+            },
+            ("players", []) => Command::Players,
+            ("kick", [client_id]) => match client_id.parse() {
+                Ok(client_id) => Command::Kick { client_id },
+                Err(_) => return Err(bad_arguments),
+            },
+            ("ban", [client_id]) => match client_id.parse() {
+                Ok(client_id) => Command::Ban { client_id },
+                Err(_) => return Err(bad_arguments),
+            },
+            ("map", [name]) => Command::Map {
+                name: name.to_string(),
+            },
+            ("restart", []) => Command::Restart,
+            ("tickrate", [tick_rate]) => match tick_rate.parse::<f32>() {
+                Ok(tick_rate) if tick_rate.is_finite() && tick_rate > 0.0 => {
+                    Command::TickRate { tick_rate }
+                }
+                _ => return Err(bad_arguments),
+            },
+            ("spawn_asteroid", []) => Command::SpawnAsteroid { position: None },
+            ("spawn_asteroid", [x, y, z]) => match (x.parse(), y.parse(), z.parse()) {
+                (Ok(x), Ok(y), Ok(z)) if Vec3::new(x, y, z).is_finite() => Command::SpawnAsteroid {
+                    position: Some(Vec3::new(x, y, z)),
+                },
+                _ => return Err(bad_arguments),
+            },
+            ("say", [_, ..]) => Command::Say {
+                text: args.join(" "),
+            },
+            _ => return Err(bad_arguments),
+        };
+        Ok(command)
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Command::Help => "help",
+            Command::Login { .. } => "login",
+            Command::SwapTeam { .. } => "swap_team",
+            Command::Players => "players",
+            Command::Kick { .. } => "kick",
+            Command::Ban { .. } => "ban",
+            Command::Map { .. } => "map",
+            Command::Restart => "restart",
+            Command::TickRate { .. } => "tickrate",
+            Command::SpawnAsteroid { .. } => "spawn_asteroid",
+            Command::Say { .. } => "say",
+        }
+    }
+}
+
+/// Writes the command the way it is typed. Passwords are left out, so this is safe to
+/// log.
+impl fmt::Display for Command {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name())?;
+        match self {
+            Command::Login { .. } => write!(f, " ***"),
+            Command::SwapTeam { team } => match team {
+                Team::Blue => write!(f, " 2"),
+                _ => write!(f, " 1"),
+            },
+            Command::Kick { client_id } | Command::Ban { client_id } => {
+                write!(f, " {}", client_id)
+            }
+            Command::Map { name } => write!(f, " {}", name),
+            Command::Say { text } => write!(f, " {}", text),
+            Command::TickRate { tick_rate } => write!(f, " {}", tick_rate),
+            Command::SpawnAsteroid {
+                position: Some(position),
+            } => write!(f, " {} {} {}", position.x, position.y, position.z),
+            _ => Ok(()),
+        }
+    }
+}
+
+pub fn find_command(name: &str) -> Option<&'static CommandSpec> {
+    COMMANDS.iter().find(|spec| spec.name == name)
+}
+
+/// Commands whose name starts with `prefix`.
+pub fn complete(prefix: &str) -> impl Iterator<Item = &'static CommandSpec> + '_ {
+    COMMANDS
+        .iter()
+        .filter(move |spec| spec.name.starts_with(prefix))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_commands() {
+        assert_eq!(Command::parse("help"), Ok(Command::Help));
+        assert_eq!(
+            Command::parse("  swap_team   blue "),
+            Ok(Command::SwapTeam { team: Team::Blue })
+        );
+        assert_eq!(
+            Command::parse("kick 42"),
+            Ok(Command::Kick { client_id: 42 })
+        );
+        assert_eq!(
+            Command::parse("tickrate 30"),
+            Ok(Command::TickRate { tick_rate: 30.0 })
+        );
+        assert_eq!(
+            Command::parse("spawn_asteroid"),
+            Ok(Command::SpawnAsteroid { position: None })
+        );
+        assert_eq!(
+            Command::parse("spawn_asteroid 1 -2 3.5"),
+            Ok(Command::SpawnAsteroid {
+                position: Some(Vec3::new(1.0, -2.0, 3.5))
+            })
+        );
+        assert_eq!(
+            Command::parse("say hello   there"),
+            Ok(Command::Say {
+                text: "hello there".to_string()
+            })
+        );
+    }
+
+    #[test]
+    fn rejects_bad_lines() {
+        assert_eq!(Command::parse("   "), Err(CommandError::Empty));
+        assert_eq!(
+            Command::parse("fly"),
+            Err(CommandError::Unknown {
+                name: "fly".to_string()
+            })
+        );
+
+        let bad_arguments = |name: &'static str| {
+            let spec = find_command(name).unwrap();
+            Err(CommandError::BadArguments {
+                name: spec.name,
+                usage: spec.usage,
+            })
+        };
+        assert_eq!(Command::parse("swap_team 3"), bad_arguments("swap_team"));
+        assert_eq!(Command::parse("kick -1"), bad_arguments("kick"));
+        assert_eq!(Command::parse("help me"), bad_arguments("help"));
+        assert_eq!(Command::parse("say"), bad_arguments("say"));
+        for tick_rate in ["0", "-5", "NaN", "inf"] {
+            assert_eq!(
+                Command::parse(&format!("tickrate {}", tick_rate)),
+                bad_arguments("tickrate")
+            );
+        }
+        for position in ["1 2", "1 2 x", "NaN 0 0", "0 inf 0", "0 0 -infinity"] {
+            assert_eq!(
+                Command::parse(&format!("spawn_asteroid {}", position)),
+                bad_arguments("spawn_asteroid")
+            );
+        }
+    }
+
+    #[test]
+    fn display_parses_back() {
+        for line in [
+            "swap_team 2",
+            "kick 7",
+            "tickrate 20",
+            "spawn_asteroid 1 2 3",
+            "say hi all",
+        ] {
+            let command = Command::parse(line).unwrap();
+            assert_eq!(command.to_string(), line);
+            assert_eq!(Command::parse(&command.to_string()), Ok(command));
+        }
+        assert_eq!(
+            Command::Login {
+                password: "secret".to_string()
+            }
+            .to_string(),
+            "login ***"
+        );
+    }
+
+    #[test]
+    fn completes_names() {
+        let names = |prefix| complete(prefix).map(|spec| spec.name).collect::<Vec<_>>();
+        assert_eq!(names("s"), ["swap_team", "spawn_asteroid", "say"]);
+        assert_eq!(names("ki"), ["kick"]);
+        assert_eq!(names("tickrate"), ["tickrate"]);
+        assert!(names("x").is_empty());
+        assert_eq!(names("").len(), COMMANDS.len());
+    }
+}
@@ -17,7 +17,7 @@ impl Plugin for CooldownPlugin {
 
 fn cooldown(mut query: Query<(Entity, &mut Cooldown)>, time: Res<Time>, mut commands: Commands) {
     for (entity, mut cooldown) in query.iter_mut() {
-        cooldown.value = cooldown.value - time.delta_seconds();
+        cooldown.value -= time.delta_seconds();
 
         if cooldown.value < 0.0 {
             commands.entity(entity).remove::<Cooldown>();
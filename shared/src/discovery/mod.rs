@@ -0,0 +1,108 @@
+use serde::{Deserialize, Serialize};
+
+use crate::codec::decode;
+
+/// Servers listen on this port for discovery queries, clients broadcast them to it.
+pub const DISCOVERY_PORT: u16 = 5001;
+
+/// First bytes of every discovery packet, anything else sent to the port is ignored.
+const DISCOVERY_MAGIC: [u8; 4] = *b"SPDS";
+
+/// What a server tells clients looking for a game on the LAN.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ServerInfo {
+    pub name: String,
+    pub map: String,
+    pub players: u32,
+    pub max_players: u32,
+    /// Clients speaking another version would be rejected, see
+    /// [`crate::handshake::PROTOCOL_VERSION`].
+    pub protocol_version: u32,
+    /// Port the game runs on, replies come from [`DISCOVERY_PORT`].
+    pub port: u16,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub enum DiscoveryPacket {
+    /// Broadcast by clients.
+    Query {
+        /// Client time the query was sent at, echoed back to measure the ping.
+        sent_at: f64,
+    },
+    Reply {
+        sent_at: f64,
+        info: ServerInfo,
+    },
+}
+
+impl DiscoveryPacket {
+    pub fn encode(&self) -> Vec<u8> {
+        let mut bytes = DISCOVERY_MAGIC.to_vec();
+        bytes.extend(bincode::serialize(self).unwrap());
+        bytes
+    }
+
+    /// `None` for packets that aren't discovery packets or are malformed.
+    pub fn decode(bytes: &[u8]) -> Option<Self> {
+        match bytes.strip_prefix(&DISCOVERY_MAGIC) {
+            Some(bytes) => decode(bytes).ok(),
+            None => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn info() -> ServerInfo {
+        ServerInfo {
+            name: "Test".to_string(),
+            map: "default".to_string(),
+            players: 3,
+            max_players: 16,
+            protocol_version: 1,
+            port: 5000,
+        }
+    }
+
+    #[test]
+    fn packets_round_trip() {
+        let bytes = DiscoveryPacket::Query { sent_at: 2.25 }.encode();
+        assert!(bytes.starts_with(&DISCOVERY_MAGIC));
+        assert!(matches!(
+            DiscoveryPacket::decode(&bytes),
+            Some(DiscoveryPacket::Query { sent_at }) if sent_at == 2.25
+        ));
+
+        let bytes = DiscoveryPacket::Reply {
+            sent_at: 1.5,
+            info: info(),
+        }
+        .encode();
+        match DiscoveryPacket::decode(&bytes) {
+            Some(DiscoveryPacket::Reply {
+                sent_at,
+                info: decoded,
+            }) => {
+                assert_eq!(sent_at, 1.5);
+                assert_eq!(decoded, info());
+            }
+            other => panic!("decoded {:?}", other),
+        }
+    }
+
+    #[test]
+    fn other_packets_are_ignored() {
+        let bytes = DiscoveryPacket::Reply {
+            sent_at: 1.5,
+            info: info(),
+        }
+        .encode();
+        assert!(DiscoveryPacket::decode(&bytes[DISCOVERY_MAGIC.len()..]).is_none());
+        assert!(DiscoveryPacket::decode(&bytes[..bytes.len() - 1]).is_none());
+        assert!(DiscoveryPacket::decode(&DISCOVERY_MAGIC).is_none());
+        assert!(DiscoveryPacket::decode(b"SPRP").is_none());
+        assert!(DiscoveryPacket::decode(&[]).is_none());
+    }
+}
@@ -0,0 +1,173 @@
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+/// Bumped whenever the messages sent between client and server change.
+///
+/// Unlike [`crate::PROTOCOL_ID`], which renet silently drops mismatched clients for,
+/// this is checked by the server so the client can be told why it was rejected.
+pub const PROTOCOL_VERSION: u32 = 11;
+
+/// Identifies the build, set through the `SPAAAACE_BUILD_HASH` environment variable at
+/// compile time.
+pub const BUILD_HASH: &str = match option_env!("SPAAAACE_BUILD_HASH") {
+    Some(hash) => hash,
+    None => "dev",
+};
+
+pub const MAX_NAME_LENGTH: usize = 24;
+
+/// How [`crate::ClientMessages::Hello`] starts in every protocol version. The server
+/// decodes this and checks the version before decoding the rest of the hello.
+#[derive(Debug, Serialize, Deserialize)]
+pub enum HelloPrefix {
+    Hello { protocol_version: u32 },
+}
+
+/// How the handshake replies start in every protocol version, the client checks the
+/// version of a [`crate::ServerMessages::ConnectAccepted`] before decoding the rest.
+#[derive(Debug, Serialize, Deserialize)]
+pub enum ConnectReplyPrefix {
+    ConnectAccepted { protocol_version: u32 },
+    ConnectRejected,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ConnectRejectReason {
+    VersionMismatch {
+        server_version: u32,
+        client_version: u32,
+    },
+    ServerFull,
+    Banned,
+    BadName,
+    UnknownShipType,
+}
+
+impl fmt::Display for ConnectRejectReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConnectRejectReason::VersionMismatch {
+                server_version,
+                client_version,
+            } => write!(
+                f,
+                "Version mismatch, the server runs protocol {} but you have {}.",
+                server_version, client_version
+            ),
+            ConnectRejectReason::ServerFull => write!(f, "The server is full."),
+            ConnectRejectReason::Banned => write!(f, "You are banned from this server."),
+            ConnectRejectReason::BadName => write!(f, "That name is not allowed."),
+            ConnectRejectReason::UnknownShipType => {
+                write!(f, "The server does not know that ship type.")
+            }
+        }
+    }
+}
+
+/// Event raised on the server once a client's handshake has been accepted. Only then
+/// is its ship spawned and the game state sent to it.
+pub struct ClientAccepted {
+    pub client_id: u64,
+    /// Id of the ship, it stays the same when the player reconnects.
+    pub player_id: u64,
+    /// The client took over a ship left behind by an earlier connection.
+    pub resumed: bool,
+    pub name: String,
+    pub ship_type: String,
+    /// Spectators get the game state but no ship.
+    pub spectator: bool,
+}
+
+pub fn is_valid_name(name: &str) -> bool {
+    let trimmed = name.trim();
+    !trimmed.is_empty()
+        && trimmed.len() == name.len()
+        && name.chars().count() <= MAX_NAME_LENGTH
+        && name
+            .chars()
+            .all(|c| c.is_alphanumeric() || c == ' ' || c == '_' || c == '-')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{codec::decode, ClientMessages, ServerMessages, ServerPacket};
+
+    #[test]
+    fn names() {
+        assert!(is_valid_name("Pilot"));
+        assert!(is_valid_name("Space Cadet_2-b"));
+        assert!(is_valid_name("Åsa"));
+        assert!(is_valid_name(&"a".repeat(MAX_NAME_LENGTH)));
+
+        assert!(!is_valid_name(""));
+        assert!(!is_valid_name("   "));
+        assert!(!is_valid_name(" Pilot"));
+        assert!(!is_valid_name("Pilot "));
+        assert!(!is_valid_name(&"a".repeat(MAX_NAME_LENGTH + 1)));
+        assert!(!is_valid_name("Pi\nlot"));
+        assert!(!is_valid_name("<script>"));
+    }
+
+    #[test]
+    fn hello_prefix_reads_the_version() {
+        let hello = ClientMessages::Hello {
+            protocol_version: 42,
+            build_hash: "abc".to_string(),
+            ship_type: "TEST_SHIP".to_string(),
+            name: "Pilot".to_string(),
+            spectator: false,
+            session_token: Some(7),
+        };
+        let bytes = bincode::serialize(&hello).unwrap();
+        assert!(matches!(
+            decode(&bytes),
+            Ok(HelloPrefix::Hello {
+                protocol_version: 42
+            })
+        ));
+
+        let other = bincode::serialize(&ClientMessages::SnapshotAck { tick: 1 }).unwrap();
+        assert!(decode::<HelloPrefix>(&other).is_err());
+    }
+
+    #[test]
+    fn connect_reply_prefix_reads_the_version() {
+        let accepted = bincode::serialize(&ServerPacket {
+            tick: 3,
+            message: ServerMessages::ConnectAccepted {
+                protocol_version: 42,
+                player_id: 1,
+                tick_rate: 10.0,
+                session_token: None,
+                resume_window: 30.0,
+            },
+        })
+        .unwrap();
+        assert!(matches!(
+            decode(&accepted),
+            Ok(ServerPacket {
+                tick: 3,
+                message: ConnectReplyPrefix::ConnectAccepted {
+                    protocol_version: 42
+                },
+            })
+        ));
+
+        let rejected = bincode::serialize(&ServerPacket {
+            tick: 3,
+            message: ServerMessages::ConnectRejected {
+                reason: ConnectRejectReason::ServerFull,
+            },
+        })
+        .unwrap();
+        assert!(matches!(
+            decode(&rejected),
+            Ok(ServerPacket {
+                message: ConnectReplyPrefix::ConnectRejected,
+                ..
+            })
+        ));
+    }
+}
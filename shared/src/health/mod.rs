@@ -1,8 +1,12 @@
-use bevy::prelude::{App, Commands, Component, Entity, EventReader, Plugin, Query, ResMut};
+use bevy::prelude::{
+    App, Commands, Component, DespawnRecursiveExt, Entity, EventReader, Plugin, Query, Without,
+};
 use bevy_rapier3d::prelude::CollisionEvent;
-use bevy_renet::renet::{DefaultChannel, RenetServer};
 
-use crate::{weapons::bullet::Bullet, NetworkedId, ServerMessages};
+use crate::{
+    player::Player,
+    weapons::bullet::{Bullet, BULLET_DAMAGE},
+};
 
 #[derive(Component)]
 pub struct Health {
@@ -13,8 +17,8 @@ pub struct HealthPlugin;
 
 impl Plugin for HealthPlugin {
     fn build(&self, app: &mut App) {
+        // The server runs `death` on its tick.
         app.add_system(handle_collisions);
-        app.add_system(death);
     }
 }
 
@@ -24,43 +28,31 @@ fn handle_collisions(
     bullet_query: Query<&Bullet>,
 ) {
     for event in collision_event_reader.iter() {
-        match event {
-            CollisionEvent::Started(e1, e2, _) => {
-                let result = health_query.iter_many([*e1, *e2]);
-                let mut health_entity: Option<Entity> = Option::None;
-                for (entity, _) in result {
-                    health_entity = Some(entity);
-                }
+        if let CollisionEvent::Started(e1, e2, _) = event {
+            let mut health_entity: Option<Entity> = Option::None;
+            for (entity, _) in health_query.iter_many([*e1, *e2]) {
+                health_entity = Some(entity);
+            }
 
-                let result = bullet_query.iter_many([*e1, *e2]);
-                let mut bullet: Option<&Bullet> = Option::None;
-                for b in result.into_iter() {
-                    bullet = Some(b);
-                }
+            let hit_by_bullet = bullet_query.iter_many([*e1, *e2]).next().is_some();
 
-                if health_entity.is_some() && bullet.is_some() {
-                    let (_, mut health) = health_query.get_mut(health_entity.unwrap()).unwrap();
-                    health.health -= 1.0;
-                }
+            if let (Some(entity), true) = (health_entity, hit_by_bullet) {
+                let (_, mut health) = health_query.get_mut(entity).unwrap();
+                health.health -= BULLET_DAMAGE;
             }
-            _ => (),
         }
     }
 }
 
-fn death(
+/// Despawns whatever ran out of health. Ships are left to the server, which respawns
+/// them.
+pub fn death(
     mut commands: Commands, //
-    health_query: Query<(Entity, &mut Health, &NetworkedId)>,
-    mut server: ResMut<RenetServer>,
+    health_query: Query<(Entity, &Health), Without<Player>>,
 ) {
-    for (entity, health, networked_id) in health_query.iter() {
+    for (entity, health) in health_query.iter() {
         if health.health <= 0.0 {
-            commands.entity(entity).despawn();
-            let message = bincode::serialize(&ServerMessages::EntityDespawn {
-                id: networked_id.id,
-            })
-            .unwrap();
-            server.broadcast_message(DefaultChannel::Reliable, message);
+            commands.entity(entity).despawn_recursive();
         }
     }
 }
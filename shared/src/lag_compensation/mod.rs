@@ -0,0 +1,144 @@
+use std::collections::{HashMap, VecDeque};
+
+use bevy::prelude::{Entity, Or, Quat, Query, Res, ResMut, Resource, Transform, Vec3, With};
+use bevy_rapier3d::prelude::Collider;
+
+use crate::{asteroid::Asteroid, player::Player, tick::NetworkTick};
+
+/// How far back the server may rewind colliders to match what a shooter saw.
+#[derive(Resource, Debug, Clone, Copy)]
+pub struct LagCompensationSettings {
+    /// Seconds.
+    pub max_rewind: f32,
+}
+
+impl Default for LagCompensationSettings {
+    fn default() -> Self {
+        Self { max_rewind: 0.2 }
+    }
+}
+
+impl LagCompensationSettings {
+    /// Clamps the time a client was looking at to the rewind window ending at `now`.
+    pub fn rewind_time(&self, view_time: f64, now: f64) -> f64 {
+        view_time.clamp(now - self.max_rewind as f64, now)
+    }
+}
+
+#[derive(Clone)]
+struct ColliderRecord {
+    translation: Vec3,
+    rotation: Quat,
+    collider: Collider,
+}
+
+/// Transforms of player and asteroid colliders over the last few ticks, as they were
+/// sent to clients.
+#[derive(Resource, Default)]
+pub struct ColliderHistory {
+    records: VecDeque<(f64, HashMap<Entity, ColliderRecord>)>,
+}
+
+impl ColliderHistory {
+    /// Colliders as they were at `time`, interpolated between the recorded ticks
+    /// around it.
+    fn sample(&self, time: f64) -> HashMap<Entity, ColliderRecord> {
+        let (before, after) = match self
+            .records
+            .iter()
+            .position(|(tick_time, _)| *tick_time >= time)
+        {
+            Some(0) => return self.records[0].1.clone(),
+            Some(index) => (&self.records[index - 1], &self.records[index]),
+            None => {
+                return self
+                    .records
+                    .back()
+                    .map(|(_, colliders)| colliders.clone())
+                    .unwrap_or_default()
+            }
+        };
+
+        let span = after.0 - before.0;
+        let t = if span > 0.0 {
+            ((time - before.0) / span) as f32
+        } else {
+            1.0
+        };
+
+        let mut colliders = after.1.clone();
+        for (entity, record) in colliders.iter_mut() {
+            if let Some(previous) = before.1.get(entity) {
+                record.translation = previous.translation.lerp(record.translation, t);
+                record.rotation = previous.rotation.slerp(record.rotation, t);
+            }
+        }
+        colliders
+    }
+
+    /// Casts a ray against the colliders as they were at `time`. Returns the closest
+    /// entity hit and the distance along `direction`, which must be normalized.
+    ///
+    /// Hitscan weapons resolve their hits with this.
+    pub fn cast_ray(
+        &self,
+        time: f64,
+        origin: Vec3,
+        direction: Vec3,
+        max_distance: f32,
+        exclude: Option<Entity>,
+    ) -> Option<(Entity, f32)> {
+        self.sample(time)
+            .into_iter()
+            .filter(|(entity, _)| Some(*entity) != exclude)
+            .filter_map(|(entity, record)| {
+                record
+                    .collider
+                    .cast_ray(
+                        record.translation,
+                        record.rotation,
+                        origin,
+                        direction,
+                        max_distance,
+                        true,
+                    )
+                    .map(|distance| (entity, distance))
+            })
+            .min_by(|(_, a), (_, b)| a.total_cmp(b))
+    }
+}
+
+/// Entities shots can hit, and so the ones whose colliders are recorded.
+type Rewindable = Or<(With<Player>, With<Asteroid>)>;
+
+/// Records the colliders at the end of every server tick. The server runs this after
+/// physics, at the same point snapshots are taken. Players and asteroids are top level
+/// entities, so their `Transform` is already up to date where `GlobalTransform` isn't.
+pub fn record_collider_history(
+    mut history: ResMut<ColliderHistory>,
+    settings: Res<LagCompensationSettings>,
+    tick: Res<NetworkTick>,
+    query: Query<(Entity, &Transform, &Collider), Rewindable>,
+) {
+    let colliders = query
+        .iter()
+        .map(|(entity, transform, collider)| {
+            (
+                entity,
+                ColliderRecord {
+                    translation: transform.translation,
+                    rotation: transform.rotation,
+                    collider: collider.clone(),
+                },
+            )
+        })
+        .collect();
+
+    let now = tick.tick_to_seconds(tick.tick);
+    history.records.push_back((now, colliders));
+
+    // Keep one record older than the window so its start can still be interpolated.
+    while history.records.len() > 2 && history.records[1].0 < now - settings.max_rewind as f64 {
+        history.records.pop_front();
+    }
+}
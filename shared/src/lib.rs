@@ -7,101 +7,252 @@ pub mod health;
 pub mod asteroid;
 pub mod targeting;
 pub mod cooldown;
+pub mod replication;
+pub mod capture_point;
+pub mod tick;
+pub mod snapshot;
+pub mod handshake;
+pub mod auth;
+pub mod codec;
+pub mod lag_compensation;
+pub mod netsim;
+pub mod replay;
+pub mod discovery;
+pub mod command;
+pub mod chat;
 
-use std::collections::HashMap;
+use std::collections::{HashMap, VecDeque};
 
 use bevy::{
     ecs::schedule::ShouldRun,
     prelude::{Component, Entity, Quat, Res, Resource, Vec3},
 };
-use player::player_input::PlayerInput;
+use bevy_renet::renet::{DefaultChannel, RenetServer};
+use chat::{ChatChannel, ChatSender};
+use command::Command;
+use handshake::ConnectRejectReason;
+use player::{movement::ShipVelocity, player_input::PlayerInput};
+use replay::{ReplayFrame, ReplayRecorder};
 use serde::{Deserialize, Serialize};
-use team::team_enum::Team;
+use tick::NetworkTick;
 
 #[derive(Debug, Serialize, Deserialize, Component, Clone)]
 pub enum ClientMessages {
-    PlayerInput { input: PlayerInput },
-    Command { command: String },
+    /// First message a client sends after connecting. Kept as the first variant with
+    /// the version as its first field, so [`handshake::HelloPrefix`] reads it from any
+    /// protocol version.
+    Hello {
+        protocol_version: u32,
+        build_hash: String,
+        ship_type: String,
+        name: String,
+        /// Join without a ship, only watching the match. `ship_type` is ignored.
+        spectator: bool,
+        /// Token from an earlier [`ServerMessages::ConnectAccepted`], to take back the
+        /// ship left behind when the connection dropped.
+        session_token: Option<u64>,
+    },
+    /// Sent unreliably every tick. Holds the newest input last, preceded by older ones
+    /// the server may not have acknowledged yet, so a lost packet doesn't lose input.
+    PlayerInputs {
+        inputs: Vec<PlayerInput>,
+    },
+    Command {
+        command: Command,
+    },
+    SnapshotAck {
+        tick: u64,
+    },
+    Chat {
+        channel: ChatChannel,
+        text: String,
+    },
 }
 
+/// Renet drops clients with a different id without telling them, so this should not
+/// change. Use [`handshake::PROTOCOL_VERSION`] instead.
 pub const PROTOCOL_ID: u64 = 7;
 
 #[derive(Debug, Default, Resource)]
 pub struct Lobby {
     pub networked_entities: HashMap<u64, Entity>,
     pub players: HashMap<u64, Entity>,
-    pub capture_points: HashMap<u64, Entity>,
 }
 
-#[derive(Debug, Serialize, Deserialize, Component)]
+#[derive(Debug, Serialize, Deserialize, Component, Clone)]
 pub enum ServerMessages {
+    // The handshake replies come first so [`handshake::ConnectReplyPrefix`] reads them from
+    // any protocol version.
+    ConnectAccepted {
+        protocol_version: u32,
+        player_id: u64,
+        /// Server ticks per second, the client sends its inputs at the same rate.
+        tick_rate: f32,
+        /// Sent back in the hello of a later connection to resume this one. Spectators
+        /// have nothing to resume and get none.
+        session_token: Option<u64>,
+        /// Seconds the server keeps the ship after the connection drops.
+        resume_window: f32,
+    },
+    ConnectRejected {
+        reason: ConnectRejectReason,
+    },
     PlayerConnected {
         id: u64,
+        /// Name of the ship's [`ships::ShipDefinition`].
+        ship_type: String,
     },
     PlayerDisconnected {
         id: u64,
     },
-    BulletSpawned {
-        id: u64,
-        position: Vec3,
-        rotation: Quat,
-    },
     EntityDespawn {
         id: u64,
     },
-    CapturePointSpawned {
+    ComponentInserted {
         id: u64,
-        owner: Team,
-        progress: f32,
-        position: Vec3,
+        component: String,
+        data: Vec<u8>,
+        translation: Vec3,
         rotation: Quat,
+        scale: Vec3,
     },
-    CapturePointUpdate {
+    ComponentUpdated {
         id: u64,
-        owner: Team,
-        attacker: Team,
-        progress: f32,
+        component: String,
+        data: Vec<u8>,
     },
-    AsteroidSpawned {
-        id: u64,
-        position: Vec3,
-        scale: Vec3,
-        rotation: Quat,
+    /// Reply to a [`ClientMessages::Command`].
+    CommandOutput {
+        text: String,
     },
-    BalloonSpawned {
-        id: u64,
-        position: Vec3,
-        scale: Vec3,
-        rotation: Quat,
+    /// The server changed its tick rate, see [`NetworkTick::set_rate`].
+    TickRateChanged {
+        tick_rate: f32,
+        epoch_tick: u64,
+        epoch_seconds: f64,
     },
+    Chat {
+        sender: ChatSender,
+        channel: ChatChannel,
+        text: String,
+    },
+}
+
+/// A [`ServerMessages`] stamped with the server tick it was sent on.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ServerPacket<M = ServerMessages> {
+    pub tick: u64,
+    pub message: M,
+}
+
+pub fn send_server_message(
+    server: &mut RenetServer,
+    client_id: u64,
+    tick: &NetworkTick,
+    message: ServerMessages,
+) {
+    let packet = bincode::serialize(&ServerPacket {
+        tick: tick.tick,
+        message,
+    })
+    .unwrap();
+    server.send_message(client_id, DefaultChannel::Reliable, packet);
+}
+
+/// Sends `message` to every client, and to the replay if one is being recorded.
+pub fn broadcast_server_message(
+    server: &mut RenetServer,
+    tick: &NetworkTick,
+    recorder: Option<&mut ReplayRecorder>,
+    message: ServerMessages,
+) {
+    let packet = ServerPacket {
+        tick: tick.tick,
+        message,
+    };
+    server.broadcast_message(
+        DefaultChannel::Reliable,
+        bincode::serialize(&packet).unwrap(),
+    );
+
+    if let Some(recorder) = recorder {
+        recorder.record(
+            packet.tick,
+            tick.tick_to_seconds(packet.tick),
+            ReplayFrame::Message(packet.message),
+        );
+    }
 }
 
 #[derive(Component)]
 pub struct NetworkedId {
     pub id: u64,
-    pub last_sent: u128,
 }
 
-#[derive(Resource, Debug, Clone)]
-#[repr(transparent)]
-pub struct NetworkIdProvider(u64);
+/// Hands out the ids of everything the server replicates, and of every player.
+///
+/// The low 32 bits of an id index a slot, the high 32 bits count how often the slot
+/// was handed out before. Freed slots are reused with the next generation, so an id
+/// held on to after its entity is gone never refers to whatever took over the slot.
+/// Index 0 is never handed out, so an id of 0 can mean no entity.
+#[derive(Resource, Debug, Default)]
+pub struct NetworkIdProvider {
+    /// Current generation of each slot, slot `n` has index `n + 1`.
+    generations: Vec<u32>,
+    /// Whether the current id of each slot is handed out.
+    in_use: Vec<bool>,
+    /// Freed slots, oldest first so a slot rests as long as possible before reuse.
+    free: VecDeque<usize>,
+}
 
 impl NetworkIdProvider {
     pub fn new() -> Self {
-        Self(0)
+        Self::default()
     }
 
-    /// Creates a new, unique [`NetworkId`].
+    /// Creates a new, unique [`NetworkedId`].
     pub fn new_id(&mut self) -> NetworkedId {
-        let id = NetworkedId {
-            id: self.0,
-            last_sent: 0,
+        let slot = match self.free.pop_front() {
+            Some(slot) => slot,
+            None => {
+                assert!(
+                    self.generations.len() < u32::MAX as usize,
+                    "NetworkId has overflowed u32::MAX."
+                );
+                self.generations.push(0);
+                self.in_use.push(false);
+                self.generations.len() - 1
+            }
         };
-        self.0 = self
-            .0
-            .checked_add(1)
-            .expect("NetworkId has overflowed u32::MAX.");
-        id
+        self.in_use[slot] = true;
+        NetworkedId {
+            id: ((self.generations[slot] as u64) << 32) | (slot as u64 + 1),
+        }
+    }
+
+    /// Makes the id's slot available again. Freeing an id that isn't in use means two
+    /// owners thought they had it, which would mix up their entities, so it panics.
+    pub fn free(&mut self, id: u64) {
+        let slot = self
+            .slot_in_use(id)
+            .unwrap_or_else(|| panic!("Network id {:#x} was freed but is not in use.", id));
+        self.in_use[slot] = false;
+        self.generations[slot] = self.generations[slot].wrapping_add(1);
+        self.free.push_back(slot);
+    }
+
+    /// Whether `id` was handed out and not freed since.
+    pub fn is_in_use(&self, id: u64) -> bool {
+        self.slot_in_use(id).is_some()
+    }
+
+    fn slot_in_use(&self, id: u64) -> Option<usize> {
+        let slot = ((id & 0xffff_ffff) as usize).checked_sub(1)?;
+        let generation = (id >> 32) as u32;
+        match (self.in_use.get(slot), self.generations.get(slot)) {
+            (Some(true), Some(current)) if *current == generation => Some(slot),
+            _ => None,
+        }
     }
 }
 
@@ -110,12 +261,28 @@ pub struct NetworkedTransform {
     pub send_rate: f32,
 }
 
-#[derive(Debug, Default, Serialize, Deserialize)]
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
 pub struct TranslationRotation {
     pub translation: Vec3,
     pub rotation: Quat,
 }
 
+/// Authoritative movement state of a player's ship.
+#[derive(Debug, Default, Clone, Copy, Serialize, Deserialize)]
+pub struct PlayerState {
+    pub velocity: ShipVelocity,
+    pub last_processed_input: u32,
+}
+
+/// Transforms of networked entities as they were at the end of `tick`.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Snapshot {
+    pub tick: u64,
+    pub entities: HashMap<u64, TranslationRotation>,
+    pub players: HashMap<u64, PlayerState>,
+}
+
+/// Default server tick rate, the server can be configured to run at another one.
 pub const SERVER_TICKRATE: f32 = 10.0;
 
 #[derive(Resource)]
@@ -136,3 +303,75 @@ pub fn run_if_client(ctx: Res<NetworkContext>) -> ShouldRun {
         false => ShouldRun::Yes,
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ids_are_unique_and_never_zero() {
+        let mut provider = NetworkIdProvider::new();
+        let ids: Vec<u64> = (0..100).map(|_| provider.new_id().id).collect();
+        assert!(!ids.contains(&0));
+        let unique: std::collections::HashSet<_> = ids.iter().collect();
+        assert_eq!(unique.len(), ids.len());
+        assert!(ids.iter().all(|id| provider.is_in_use(*id)));
+    }
+
+    #[test]
+    fn freed_slots_come_back_with_a_new_generation() {
+        let mut provider = NetworkIdProvider::new();
+        let first = provider.new_id().id;
+        let second = provider.new_id().id;
+
+        provider.free(first);
+        assert!(!provider.is_in_use(first));
+        assert!(provider.is_in_use(second));
+
+        let reused = provider.new_id().id;
+        assert_ne!(reused, first);
+        assert_eq!(reused & 0xffff_ffff, first & 0xffff_ffff);
+        assert_eq!(reused >> 32, (first >> 32) + 1);
+        // The stale id doesn't count as the new one.
+        assert!(!provider.is_in_use(first));
+        assert!(provider.is_in_use(reused));
+    }
+
+    #[test]
+    fn oldest_freed_slot_is_reused_first() {
+        let mut provider = NetworkIdProvider::new();
+        let ids: Vec<u64> = (0..3).map(|_| provider.new_id().id).collect();
+        provider.free(ids[2]);
+        provider.free(ids[0]);
+        assert_eq!(provider.new_id().id & 0xffff_ffff, ids[2] & 0xffff_ffff);
+        assert_eq!(provider.new_id().id & 0xffff_ffff, ids[0] & 0xffff_ffff);
+    }
+
+    #[test]
+    fn unknown_ids_are_not_in_use() {
+        let mut provider = NetworkIdProvider::new();
+        let id = provider.new_id().id;
+        assert!(!provider.is_in_use(0));
+        assert!(!provider.is_in_use(id + 1));
+        assert!(!provider.is_in_use(id + (1 << 32)));
+    }
+
+    #[test]
+    #[should_panic(expected = "is not in use")]
+    fn freeing_twice_panics() {
+        let mut provider = NetworkIdProvider::new();
+        let id = provider.new_id().id;
+        provider.free(id);
+        provider.free(id);
+    }
+
+    #[test]
+    #[should_panic(expected = "is not in use")]
+    fn freeing_a_stale_id_panics() {
+        let mut provider = NetworkIdProvider::new();
+        let id = provider.new_id().id;
+        provider.free(id);
+        provider.new_id();
+        provider.free(id);
+    }
+}
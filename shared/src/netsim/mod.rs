@@ -0,0 +1,336 @@
+use std::{
+    collections::{hash_map::Entry, HashMap},
+    io,
+    net::{SocketAddr, UdpSocket},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+use bevy::prelude::Resource;
+use rand::Rng;
+use serde::Deserialize;
+
+/// Larger than any packet renet sends.
+const MAX_DATAGRAM_BYTES: usize = 2048;
+
+/// Longest the relay sleeps between checks for new packets.
+const IDLE_SLEEP: Duration = Duration::from_millis(1);
+
+/// Reordered packets are held back at least this long so later packets overtake them.
+const MIN_REORDER_DELAY_MS: u32 = 20;
+
+/// Bad network conditions to apply to packets, in both directions.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LinkConditions {
+    /// One way latency added to every packet, in milliseconds.
+    pub latency_ms: u32,
+    /// Random extra latency of up to this many milliseconds. Packets still arrive in
+    /// order unless `reorder` says otherwise.
+    pub jitter_ms: u32,
+    /// Chance from 0 to 1 that a packet is dropped.
+    pub packet_loss: f32,
+    /// Chance from 0 to 1 that a packet arrives twice.
+    pub duplicate: f32,
+    /// Chance from 0 to 1 that a packet is held back so later packets overtake it.
+    pub reorder: f32,
+}
+
+impl LinkConditions {
+    pub fn validate(&self) -> Result<(), String> {
+        for (name, chance) in [
+            ("packet_loss", self.packet_loss),
+            ("duplicate", self.duplicate),
+            ("reorder", self.reorder),
+        ] {
+            if !(0.0..=1.0).contains(&chance) {
+                return Err(format!("{} must be between 0 and 1", name));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Command line flags for the network simulator. Any of them turns the simulator on.
+#[derive(clap::Args, Debug)]
+pub struct LinkConditionArgs {
+    /// One way latency in milliseconds added by the network simulator.
+    #[arg(long)]
+    latency_ms: Option<u32>,
+    /// Random extra latency of up to this many milliseconds.
+    #[arg(long)]
+    jitter_ms: Option<u32>,
+    /// Chance from 0 to 1 that a packet is dropped.
+    #[arg(long)]
+    packet_loss: Option<f32>,
+    /// Chance from 0 to 1 that a packet arrives twice.
+    #[arg(long)]
+    duplicate: Option<f32>,
+    /// Chance from 0 to 1 that a packet arrives after later ones.
+    #[arg(long)]
+    reorder: Option<f32>,
+}
+
+impl LinkConditionArgs {
+    /// Overrides the conditions from the config file with the flags that were given.
+    pub fn apply(&self, conditions: &mut Option<LinkConditions>) {
+        if self.latency_ms.is_none()
+            && self.jitter_ms.is_none()
+            && self.packet_loss.is_none()
+            && self.duplicate.is_none()
+            && self.reorder.is_none()
+        {
+            return;
+        }
+
+        let conditions = conditions.get_or_insert_with(LinkConditions::default);
+        if let Some(latency_ms) = self.latency_ms {
+            conditions.latency_ms = latency_ms;
+        }
+        if let Some(jitter_ms) = self.jitter_ms {
+            conditions.jitter_ms = jitter_ms;
+        }
+        if let Some(packet_loss) = self.packet_loss {
+            conditions.packet_loss = packet_loss;
+        }
+        if let Some(duplicate) = self.duplicate {
+            conditions.duplicate = duplicate;
+        }
+        if let Some(reorder) = self.reorder {
+            conditions.reorder = reorder;
+        }
+    }
+}
+
+/// Handle to a running relay, the conditions can be changed while it runs. The relay
+/// stops once the last handle is dropped.
+#[derive(Resource, Clone)]
+pub struct NetworkConditioner {
+    conditions: Arc<Mutex<LinkConditions>>,
+    /// Only held so the relay runs as long as a handle does.
+    _thread: Arc<RelayThread>,
+}
+
+impl NetworkConditioner {
+    pub fn conditions(&self) -> LinkConditions {
+        *self.conditions.lock().unwrap()
+    }
+
+    pub fn set_conditions(&self, conditions: LinkConditions) {
+        *self.conditions.lock().unwrap() = conditions;
+    }
+}
+
+/// Puts a relay in front of a server socket. Returns the socket to hand to renet in
+/// place of `socket`.
+///
+/// Every remote address gets a local socket of its own, so renet still sees one
+/// address per client.
+pub fn condition_server_socket(
+    socket: UdpSocket,
+    conditions: LinkConditions,
+) -> io::Result<(UdpSocket, NetworkConditioner)> {
+    let inner = UdpSocket::bind("127.0.0.1:0")?;
+    let relay = Relay::new(socket, inner.local_addr()?, conditions)?;
+    let conditioner = relay.start()?;
+    Ok((inner, conditioner))
+}
+
+/// Puts a relay between a client and `server_addr`. Returns the socket to hand to
+/// renet and the address it has to send to instead of `server_addr`.
+pub fn condition_client_socket(
+    server_addr: SocketAddr,
+    conditions: LinkConditions,
+) -> io::Result<(UdpSocket, SocketAddr, NetworkConditioner)> {
+    let inner = UdpSocket::bind("127.0.0.1:0")?;
+    let mut relay = Relay::new(
+        UdpSocket::bind("0.0.0.0:0")?,
+        inner.local_addr()?,
+        conditions,
+    )?;
+    let relayed_server_addr = relay.peer_socket(server_addr)?.local_addr()?;
+    let conditioner = relay.start()?;
+    Ok((inner, relayed_server_addr, conditioner))
+}
+
+struct RelayThread {
+    stop: Arc<AtomicBool>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl Drop for RelayThread {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Direction {
+    /// From a remote address to the local renet socket.
+    Inbound,
+    /// From the local renet socket to a remote address.
+    Outbound,
+}
+
+struct DelayedPacket {
+    deliver_at: Instant,
+    remote: SocketAddr,
+    direction: Direction,
+    bytes: Vec<u8>,
+}
+
+struct Relay {
+    /// Talks to the remote addresses.
+    outer: UdpSocket,
+    /// The socket renet owns.
+    target: SocketAddr,
+    /// A local socket per remote address, renet talks to these.
+    peers: HashMap<SocketAddr, UdpSocket>,
+    queue: Vec<DelayedPacket>,
+    /// Latest delivery time scheduled per link, jitter never moves a packet before it.
+    last_scheduled: HashMap<(SocketAddr, Direction), Instant>,
+    conditions: Arc<Mutex<LinkConditions>>,
+    stop: Arc<AtomicBool>,
+}
+
+impl Relay {
+    fn new(outer: UdpSocket, target: SocketAddr, conditions: LinkConditions) -> io::Result<Self> {
+        outer.set_nonblocking(true)?;
+        Ok(Self {
+            outer,
+            target,
+            peers: HashMap::new(),
+            queue: Vec::new(),
+            last_scheduled: HashMap::new(),
+            conditions: Arc::new(Mutex::new(conditions)),
+            stop: Arc::new(AtomicBool::new(false)),
+        })
+    }
+
+    fn start(self) -> io::Result<NetworkConditioner> {
+        let conditions = self.conditions.clone();
+        let stop = self.stop.clone();
+        let handle = thread::Builder::new()
+            .name("network conditioner".to_string())
+            .spawn(move || self.run())?;
+        Ok(NetworkConditioner {
+            conditions,
+            _thread: Arc::new(RelayThread {
+                stop,
+                handle: Some(handle),
+            }),
+        })
+    }
+
+    fn peer_socket(&mut self, remote: SocketAddr) -> io::Result<&UdpSocket> {
+        match self.peers.entry(remote) {
+            Entry::Occupied(entry) => Ok(entry.into_mut()),
+            Entry::Vacant(entry) => {
+                let socket = UdpSocket::bind("127.0.0.1:0")?;
+                socket.set_nonblocking(true)?;
+                Ok(entry.insert(socket))
+            }
+        }
+    }
+
+    fn run(mut self) {
+        let mut buffer = [0; MAX_DATAGRAM_BYTES];
+        while !self.stop.load(Ordering::Relaxed) {
+            let mut received = Vec::new();
+            while let Ok((len, remote)) = self.outer.recv_from(&mut buffer) {
+                received.push((remote, Direction::Inbound, buffer[..len].to_vec()));
+            }
+            for (remote, socket) in self.peers.iter() {
+                while let Ok((len, _)) = socket.recv_from(&mut buffer) {
+                    received.push((*remote, Direction::Outbound, buffer[..len].to_vec()));
+                }
+            }
+
+            let idle = received.is_empty();
+            for (remote, direction, bytes) in received {
+                self.schedule(remote, direction, bytes);
+            }
+            self.deliver_due();
+
+            if idle {
+                // Wake up for the next delivery, but keep polling for new packets.
+                let now = Instant::now();
+                let sleep = self
+                    .queue
+                    .iter()
+                    .map(|packet| packet.deliver_at.saturating_duration_since(now))
+                    .min()
+                    .map_or(IDLE_SLEEP, |until_due| until_due.min(IDLE_SLEEP));
+                thread::sleep(sleep);
+            }
+        }
+    }
+
+    fn schedule(&mut self, remote: SocketAddr, direction: Direction, bytes: Vec<u8>) {
+        let conditions = *self.conditions.lock().unwrap();
+        let mut rng = rand::thread_rng();
+
+        if rng.gen::<f32>() < conditions.packet_loss {
+            return;
+        }
+        let copies = if rng.gen::<f32>() < conditions.duplicate {
+            2
+        } else {
+            1
+        };
+
+        let now = Instant::now();
+        for _ in 0..copies {
+            let jitter = rng.gen_range(0..=conditions.jitter_ms);
+            let mut deliver_at =
+                now + Duration::from_millis((conditions.latency_ms + jitter) as u64);
+
+            if rng.gen::<f32>() < conditions.reorder {
+                let hold = MIN_REORDER_DELAY_MS.max(conditions.jitter_ms * 2);
+                deliver_at += Duration::from_millis(hold as u64);
+            } else {
+                let last = self
+                    .last_scheduled
+                    .entry((remote, direction))
+                    .or_insert(deliver_at);
+                deliver_at = deliver_at.max(*last);
+                *last = deliver_at;
+            }
+
+            self.queue.push(DelayedPacket {
+                deliver_at,
+                remote,
+                direction,
+                bytes: bytes.clone(),
+            });
+        }
+    }
+
+    fn deliver_due(&mut self) {
+        let now = Instant::now();
+        let (mut due, waiting): (Vec<_>, Vec<_>) = self
+            .queue
+            .drain(..)
+            .partition(|packet| packet.deliver_at <= now);
+        self.queue = waiting;
+        due.sort_by_key(|packet| packet.deliver_at);
+
+        let target = self.target;
+        for packet in due {
+            // Sends to a closed socket fail, that is just more packet loss.
+            let _ = match packet.direction {
+                Direction::Inbound => self
+                    .peer_socket(packet.remote)
+                    .and_then(|socket| socket.send_to(&packet.bytes, target)),
+                Direction::Outbound => self.outer.send_to(&packet.bytes, packet.remote),
+            };
+        }
+    }
+}
@@ -2,6 +2,7 @@ use bevy::prelude::Component;
 
 use crate::team::team_enum::Team;
 
+pub mod movement;
 pub mod player_input;
 
 #[derive(Component, Clone, Hash, PartialEq, Eq)]
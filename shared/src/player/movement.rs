@@ -0,0 +1,72 @@
+use bevy::prelude::{Component, Quat, Transform, Vec3};
+use serde::{Deserialize, Serialize};
+
+use crate::ships::ShipDefinition;
+
+use super::player_input::PlayerInput;
+
+/// Velocity of a ship moved by [`step_ship`].
+#[derive(Component, Debug, Default, Clone, Copy, Serialize, Deserialize)]
+pub struct ShipVelocity {
+    pub linear: Vec3,
+    pub angular: Vec3,
+}
+
+/// Advances a ship of type `ship` by one tick of `dt` seconds using `input`.
+///
+/// Both the server and the predicting client run this, so it must only depend on
+/// its arguments.
+pub fn step_ship(
+    transform: &mut Transform,
+    velocity: &mut ShipVelocity,
+    input: &PlayerInput,
+    ship: &ShipDefinition,
+    dt: f32,
+) {
+    let rotation = (input.rotate_right as i8 - input.rotate_left as i8) as f32;
+    let thrust_longitudal = (input.thrust_forward as i8 - input.thrust_reverse as i8) as f32;
+    let thrust_lateral = (input.thrust_left as i8 - input.thrust_right as i8) as f32;
+    let thrust_vertical = (input.thrust_up as i8 - input.thrust_down as i8) as f32;
+
+    let forward = transform.forward();
+    let projected_forward = (forward - Vec3::new(0.0, forward.y, 0.0)).normalize_or_zero();
+    let rotated_forward =
+        (Quat::from_axis_angle(transform.left(), -0.3 * thrust_vertical)) * projected_forward;
+
+    let left = transform.left();
+    let projected_left = (left - Vec3::new(0.0, left.y, 0.0)).normalize_or_zero();
+
+    let longitudal_thrust = match thrust_longitudal > 0.0 {
+        true => ship.thrust.forward,
+        false => ship.thrust.backward,
+    };
+    let longitudal_force = thrust_longitudal * longitudal_thrust * projected_forward;
+    let lateral_force = thrust_lateral * ship.thrust.lateral * projected_left;
+    let vertical_force = thrust_vertical * ship.thrust.vertical * Vec3::Y;
+
+    let impulse = longitudal_force + lateral_force + vertical_force;
+    let mut torque_impulse = rotation * Vec3::NEG_Y * ship.turn_rate;
+
+    {
+        let (axis, angle) =
+            Quat::from_rotation_arc(transform.forward(), rotated_forward).to_axis_angle();
+        torque_impulse += axis.normalize_or_zero() * angle * 200.0;
+    }
+
+    {
+        let (axis, angle) = Quat::from_rotation_arc(transform.up(), Vec3::Y).to_axis_angle();
+        torque_impulse += axis.normalize_or_zero() * angle * 300.0;
+    }
+
+    // The inertia tensor is diagonal in the ship's local frame.
+    let local_torque = transform.rotation.inverse() * torque_impulse;
+    velocity.linear += impulse / ship.mass;
+    velocity.angular += transform.rotation * (local_torque / ship.inertia());
+
+    velocity.linear *= 1.0 / (1.0 + dt * ship.linear_damping);
+    velocity.angular *= 1.0 / (1.0 + dt * ship.angular_damping);
+
+    transform.translation += velocity.linear * dt;
+    transform.rotation =
+        (Quat::from_scaled_axis(velocity.angular * dt) * transform.rotation).normalize();
+}
@@ -23,4 +23,11 @@ pub struct PlayerInput {
     pub ability_slot_9: bool,
     pub aim_point: Vec3,
     pub target_network_id: u64,
+    /// Increases by one for every input the client sends, used for reconciliation.
+    pub sequence: u32,
+    /// Client tick the input was sampled on.
+    pub client_tick: u64,
+    /// Server time in seconds the client was rendering remote entities at, shots are
+    /// resolved against the world as it was then.
+    pub view_time: f64,
 }
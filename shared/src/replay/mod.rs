@@ -0,0 +1,154 @@
+use std::{
+    fs::File,
+    io::{self, BufReader, BufWriter, Read, Write},
+    path::Path,
+};
+
+use bevy::prelude::Resource;
+use bincode::Options;
+use serde::{Deserialize, Serialize};
+
+use crate::{handshake::PROTOCOL_VERSION, ServerMessages, Snapshot};
+
+/// First bytes of every replay file.
+const REPLAY_MAGIC: [u8; 4] = *b"SPRP";
+
+/// Records are never close to this, anything larger means the file is corrupt.
+const MAX_RECORD_BYTES: u64 = 16 * 1024 * 1024;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReplayPlayer {
+    pub id: u64,
+    pub name: String,
+    pub ship_type: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReplayHeader {
+    /// Messages are stored as they are sent, so only clients speaking the same
+    /// protocol can play the replay back.
+    pub protocol_version: u32,
+    pub build_hash: String,
+    pub map: String,
+    pub tick_rate: f32,
+    /// Server time in seconds the recording started at.
+    pub start_time: f64,
+    /// Players connected when the recording started, later ones are recorded as
+    /// [`ReplayFrame::PlayerJoined`]. The server records from startup, so this is empty.
+    pub players: Vec<ReplayPlayer>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub enum ReplayFrame {
+    /// A message broadcast to every client on the reliable channel.
+    Message(ServerMessages),
+    /// Every networked entity, before it is split up per client.
+    Snapshot(Snapshot),
+    PlayerJoined(ReplayPlayer),
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ReplayRecord {
+    /// Server tick the frame was sent on.
+    pub tick: u64,
+    /// Server time in seconds the frame was sent at.
+    pub time: f64,
+    pub frame: ReplayFrame,
+}
+
+fn serialize_options() -> impl Options {
+    bincode::DefaultOptions::new()
+        .with_varint_encoding()
+        .with_limit(MAX_RECORD_BYTES)
+}
+
+fn write_chunk<T: Serialize>(writer: &mut impl Write, value: &T) -> io::Result<()> {
+    let bytes = serialize_options()
+        .serialize(value)
+        .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))?;
+    writer.write_all(&(bytes.len() as u32).to_le_bytes())?;
+    writer.write_all(&bytes)
+}
+
+/// Reads one length prefixed value, or `None` at the end of the file.
+fn read_chunk<T: for<'de> Deserialize<'de>>(reader: &mut impl Read) -> Result<Option<T>, String> {
+    let mut length = [0; 4];
+    match reader.read_exact(&mut length) {
+        Ok(()) => (),
+        Err(error) if error.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(error) => return Err(error.to_string()),
+    }
+
+    let length = u32::from_le_bytes(length) as u64;
+    if length > MAX_RECORD_BYTES {
+        return Err(format!("record of {} bytes is too large", length));
+    }
+    let mut bytes = vec![0; length as usize];
+    // A record cut off by the server stopping ends the replay.
+    if reader.read_exact(&mut bytes).is_err() {
+        return Ok(None);
+    }
+    serialize_options()
+        .deserialize(&bytes)
+        .map(Some)
+        .map_err(|error| error.to_string())
+}
+
+/// Writes everything the server broadcasts to a replay file.
+///
+/// Records are buffered, [`ReplayRecorder::flush`] is called once per tick so a
+/// server that is killed loses at most the last tick.
+#[derive(Resource)]
+pub struct ReplayRecorder {
+    writer: BufWriter<File>,
+}
+
+impl ReplayRecorder {
+    pub fn create(path: &Path, header: &ReplayHeader) -> io::Result<Self> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        writer.write_all(&REPLAY_MAGIC)?;
+        write_chunk(&mut writer, header)?;
+        Ok(Self { writer })
+    }
+
+    pub fn record(&mut self, tick: u64, time: f64, frame: ReplayFrame) {
+        let record = ReplayRecord { tick, time, frame };
+        if let Err(error) = write_chunk(&mut self.writer, &record) {
+            println!("Could not write replay: {}", error);
+        }
+    }
+
+    pub fn flush(&mut self) {
+        if let Err(error) = self.writer.flush() {
+            println!("Could not write replay: {}", error);
+        }
+    }
+}
+
+/// Reads a whole replay file.
+pub fn read_replay(path: &Path) -> Result<(ReplayHeader, Vec<ReplayRecord>), String> {
+    let file = File::open(path).map_err(|error| error.to_string())?;
+    let mut reader = BufReader::new(file);
+
+    let mut magic = [0; 4];
+    reader
+        .read_exact(&mut magic)
+        .map_err(|error| error.to_string())?;
+    if magic != REPLAY_MAGIC {
+        return Err("not a replay file".to_string());
+    }
+
+    let header: ReplayHeader = read_chunk(&mut reader)?.ok_or("missing header")?;
+    if header.protocol_version != PROTOCOL_VERSION {
+        return Err(format!(
+            "recorded with protocol version {}, this client speaks version {}",
+            header.protocol_version, PROTOCOL_VERSION
+        ));
+    }
+
+    let mut records = Vec::new();
+    while let Some(record) = read_chunk(&mut reader)? {
+        records.push(record);
+    }
+    Ok((header, records))
+}
@@ -0,0 +1,223 @@
+use bevy::prelude::{
+    default, Added, App, ChangeTrackers, Changed, Commands, Component, CoreStage, Entity,
+    EventReader, Plugin, Query, RemovedComponents, Res, ResMut, SpatialBundle, SystemSet,
+    Transform,
+};
+use bevy_renet::renet::RenetServer;
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::{
+    broadcast_server_message, codec::decode, handshake::ClientAccepted, replay::ReplayRecorder,
+    run_if_client, run_if_server, send_server_message, tick::NetworkTick, Lobby, NetworkIdProvider,
+    NetworkedId, ServerMessages,
+};
+
+/// A component that is sent from the server to every client.
+///
+/// Register it with [`ReplicationAppExt::replicate`]. The server then sends the
+/// component when it is added to an entity with a [`NetworkedId`], again every time
+/// it changes, and to clients that are accepted later. Clients spawn the entity the first
+/// time they see its id and insert the component on it.
+pub trait Replicated: Component + Clone + Serialize + DeserializeOwned {
+    /// Unique name the component is routed by on the wire.
+    const NAME: &'static str;
+}
+
+pub trait ReplicationAppExt {
+    fn replicate<T: Replicated>(&mut self) -> &mut Self;
+}
+
+impl ReplicationAppExt for App {
+    fn replicate<T: Replicated>(&mut self) -> &mut Self {
+        self.add_system_set(
+            SystemSet::new()
+                .with_run_criteria(run_if_server)
+                .with_system(send_on_client_accepted::<T>)
+                .with_system(send_changed::<T>),
+        )
+        .add_system_set(
+            SystemSet::new()
+                .with_run_criteria(run_if_client)
+                .with_system(receive_replicated::<T>),
+        )
+    }
+}
+
+/// Keeps [`Lobby::networked_entities`] up to date on the server and tells clients
+/// when a networked entity is despawned.
+pub struct ReplicationPlugin;
+
+impl Plugin for ReplicationPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<ClientAccepted>().add_system_set_to_stage(
+            CoreStage::PostUpdate,
+            SystemSet::new()
+                .with_run_criteria(run_if_server)
+                .with_system(track_networked_entities),
+        );
+    }
+}
+
+fn inserted_message<T: Replicated>(
+    component: &T,
+    networked_id: &NetworkedId,
+    transform: &Transform,
+) -> ServerMessages {
+    ServerMessages::ComponentInserted {
+        id: networked_id.id,
+        component: T::NAME.to_string(),
+        data: bincode::serialize(component).unwrap(),
+        translation: transform.translation,
+        rotation: transform.rotation,
+        scale: transform.scale,
+    }
+}
+
+fn send_on_client_accepted<T: Replicated>(
+    mut event_reader: EventReader<ClientAccepted>,
+    mut server: ResMut<RenetServer>,
+    tick: Res<NetworkTick>,
+    query: Query<(&T, &NetworkedId, &Transform)>,
+) {
+    for event in event_reader.iter() {
+        for (component, networked_id, transform) in query.iter() {
+            send_server_message(
+                &mut server,
+                event.client_id,
+                &tick,
+                inserted_message(component, networked_id, transform),
+            );
+        }
+    }
+}
+
+fn send_changed<T: Replicated>(
+    mut server: ResMut<RenetServer>,
+    mut recorder: Option<ResMut<ReplayRecorder>>,
+    tick: Res<NetworkTick>,
+    query: Query<(&T, &NetworkedId, &Transform, ChangeTrackers<T>), Changed<T>>,
+) {
+    for (component, networked_id, transform, tracker) in query.iter() {
+        let message = if tracker.is_added() {
+            inserted_message(component, networked_id, transform)
+        } else {
+            ServerMessages::ComponentUpdated {
+                id: networked_id.id,
+                component: T::NAME.to_string(),
+                data: bincode::serialize(component).unwrap(),
+            }
+        };
+        broadcast_server_message(&mut server, &tick, recorder.as_deref_mut(), message);
+    }
+}
+
+fn receive_replicated<T: Replicated>(
+    mut commands: Commands,
+    mut lobby: ResMut<Lobby>,
+    mut event_reader: EventReader<ServerMessages>,
+) {
+    for event in event_reader.iter() {
+        match event {
+            ServerMessages::ComponentInserted {
+                id,
+                component,
+                data,
+                translation,
+                rotation,
+                scale,
+            } if component == T::NAME => {
+                let value: T = match decode(data) {
+                    Ok(value) => value,
+                    Err(error) => {
+                        println!("Could not decode {} for entity {}: {}", T::NAME, id, error);
+                        continue;
+                    }
+                };
+                match lobby.networked_entities.get(id) {
+                    Some(entity) => {
+                        commands.entity(*entity).insert(value);
+                    }
+                    None => {
+                        let entity = commands
+                            .spawn(SpatialBundle {
+                                transform: Transform {
+                                    translation: *translation,
+                                    rotation: *rotation,
+                                    scale: *scale,
+                                },
+                                ..default()
+                            })
+                            .insert(NetworkedId { id: *id })
+                            .insert(value)
+                            .id();
+                        lobby.networked_entities.insert(*id, entity);
+                    }
+                }
+            }
+            ServerMessages::ComponentUpdated {
+                id,
+                component,
+                data,
+            } if component == T::NAME => {
+                if let Some(entity) = lobby.networked_entities.get(id) {
+                    match decode::<T>(data) {
+                        Ok(value) => {
+                            commands.entity(*entity).insert(value);
+                        }
+                        Err(error) => {
+                            println!("Could not decode {} for entity {}: {}", T::NAME, id, error)
+                        }
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+fn track_networked_entities(
+    mut lobby: ResMut<Lobby>,
+    mut id_provider: ResMut<NetworkIdProvider>,
+    mut server: ResMut<RenetServer>,
+    mut recorder: Option<ResMut<ReplayRecorder>>,
+    tick: Res<NetworkTick>,
+    added_query: Query<(Entity, &NetworkedId), Added<NetworkedId>>,
+    removed: RemovedComponents<NetworkedId>,
+) {
+    for (entity, networked_id) in added_query.iter() {
+        let id = networked_id.id;
+        // A shared id would make clients apply one entity's updates to the other.
+        assert!(
+            id_provider.is_in_use(id),
+            "Entity {:?} has network id {:#x}, which the NetworkIdProvider did not hand out.",
+            entity,
+            id
+        );
+        if let Some(other) = lobby.networked_entities.insert(id, entity) {
+            panic!(
+                "Entities {:?} and {:?} share network id {:#x}.",
+                other, entity, id
+            );
+        }
+    }
+
+    for entity in removed.iter() {
+        let ids: Vec<u64> = lobby
+            .networked_entities
+            .iter()
+            .filter(|(_, networked_entity)| **networked_entity == entity)
+            .map(|(id, _)| *id)
+            .collect();
+
+        for id in ids {
+            lobby.networked_entities.remove(&id);
+            id_provider.free(id);
+            broadcast_server_message(
+                &mut server,
+                &tick,
+                recorder.as_deref_mut(),
+                ServerMessages::EntityDespawn { id },
+            );
+        }
+    }
+}
@@ -1,26 +1,195 @@
+use std::collections::HashMap;
+
 use bevy::{
+    asset::{AssetLoader, LoadContext, LoadedAsset},
     gltf::Gltf,
-    prelude::{Component, Handle},
-};
-use phf::phf_map;
-
-#[derive(Clone, Copy)]
-pub struct ShipType {
-    // model_name is relative to the assets/ships/ folder
-    pub model_name: &'static str,
-    pub forward_thrust_force: f32,
-    pub backward_thrust_force: f32,
-    pub lateral_thrust_force: f32,
-}
-
-pub static SHIP_TYPES: phf::Map<&'static str, ShipType> = phf_map! {
-    "TEST_SHIP" => ShipType{
-        model_name: "test_ship/test_ship.gltf",
-        forward_thrust_force: 2000.,
-        backward_thrust_force: 2000.,
-        lateral_thrust_force: 2000.,
+    prelude::{
+        AddAsset, App, AssetEvent, AssetServer, Assets, Component, EventReader, Handle,
+        HandleUntyped, Plugin, Quat, Res, ResMut, Resource, Transform, Vec3,
     },
+    reflect::TypeUuid,
+    utils::BoxedFuture,
 };
+use bevy_rapier3d::prelude::Collider;
+use serde::Deserialize;
+
+/// Where the ship definitions are loaded from. Like the models it is relative to the
+/// client's and server's own asset folders.
+const SHIP_TYPES_FOLDER: &str = "../../shared/assets/ship_types";
+
+/// Loads the `.ship.toml` definitions and reloads them when they change on disk.
+pub struct ShipsPlugin;
+
+impl Plugin for ShipsPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_asset::<ShipDefinition>()
+            .init_asset_loader::<ShipDefinitionLoader>()
+            .init_resource::<ShipTypes>()
+            .add_startup_system(load_ship_types)
+            .add_system(index_ship_types);
+    }
+}
+
+/// Where a turret is mounted, relative to the ship.
+#[derive(Debug, Clone, Copy, Deserialize)]
+pub struct Hardpoint {
+    pub translation: Vec3,
+    #[serde(default)]
+    pub rotation: Quat,
+}
+
+impl Hardpoint {
+    pub fn transform(&self) -> Transform {
+        Transform {
+            translation: self.translation,
+            rotation: self.rotation,
+            ..Default::default()
+        }
+    }
+}
+
+/// Impulses applied each tick while the thrusters fire.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Thrust {
+    pub forward: f32,
+    pub backward: f32,
+    pub lateral: f32,
+    pub vertical: f32,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "shape", rename_all = "snake_case")]
+pub enum ShipCollider {
+    Cuboid { half_extents: Vec3 },
+    Ball { radius: f32 },
+}
+
+impl ShipCollider {
+    pub fn collider(&self) -> Collider {
+        match self {
+            ShipCollider::Cuboid { half_extents } => {
+                Collider::cuboid(half_extents.x, half_extents.y, half_extents.z)
+            }
+            ShipCollider::Ball { radius } => Collider::ball(*radius),
+        }
+    }
+
+    /// Moments of inertia around the local axes of a solid body of this shape.
+    pub fn inertia(&self, mass: f32) -> Vec3 {
+        match self {
+            ShipCollider::Cuboid { half_extents } => {
+                let squared = *half_extents * *half_extents;
+                mass / 3.0
+                    * Vec3::new(
+                        squared.y + squared.z,
+                        squared.x + squared.z,
+                        squared.x + squared.y,
+                    )
+            }
+            ShipCollider::Ball { radius } => Vec3::splat(0.4 * mass * radius * radius),
+        }
+    }
+}
+
+/// A ship type, read from a `.ship.toml` file in the ship types folder.
+#[derive(Debug, Clone, Deserialize, TypeUuid)]
+#[uuid = "3c1f6a52-8d4e-4b0f-9a7e-5f2d81c6b9e4"]
+pub struct ShipDefinition {
+    /// What clients ask for in their hello, like `TEST_SHIP`.
+    pub name: String,
+    /// Relative to the assets/ships/ folder.
+    pub model: String,
+    pub mass: f32,
+    pub thrust: Thrust,
+    /// Yaw impulse applied each tick while turning.
+    pub turn_rate: f32,
+    pub linear_damping: f32,
+    pub angular_damping: f32,
+    pub collider: ShipCollider,
+    pub hull: f32,
+    /// Matches the turret_pad_large nodes in the model, so the server doesn't have to
+    /// load it.
+    #[serde(default)]
+    pub hardpoints: Vec<Hardpoint>,
+}
+
+impl ShipDefinition {
+    pub fn inertia(&self) -> Vec3 {
+        self.collider.inertia(self.mass)
+    }
+}
+
+#[derive(Default)]
+struct ShipDefinitionLoader;
+
+impl AssetLoader for ShipDefinitionLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), bevy::asset::Error>> {
+        Box::pin(async move {
+            let definition: ShipDefinition = toml::from_slice(bytes)?;
+            load_context.set_default_asset(LoadedAsset::new(definition));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["ship.toml"]
+    }
+}
+
+/// The loaded ship definitions by name. Changes to the files show up here a frame
+/// after they are reloaded.
+#[derive(Resource, Default)]
+pub struct ShipTypes {
+    /// Keeps the definitions loaded and watched.
+    handles: Vec<HandleUntyped>,
+    definitions: HashMap<String, ShipDefinition>,
+}
+
+impl ShipTypes {
+    pub fn get(&self, name: &str) -> Option<&ShipDefinition> {
+        self.definitions.get(name)
+    }
+}
+
+/// Name of the [`ShipDefinition`] a player flies.
+#[derive(Component, Debug, Clone)]
+pub struct PlayerShipType(pub String);
 
 #[derive(Component)]
 pub struct ShipModelLoadHandle(pub Handle<Gltf>);
+
+fn load_ship_types(mut ship_types: ResMut<ShipTypes>, ass: Res<AssetServer>) {
+    match ass.load_folder(SHIP_TYPES_FOLDER) {
+        Ok(handles) => ship_types.handles = handles,
+        Err(error) => println!(
+            "Failed to load ship types from {}: {}",
+            SHIP_TYPES_FOLDER, error
+        ),
+    }
+}
+
+fn index_ship_types(
+    mut events: EventReader<AssetEvent<ShipDefinition>>,
+    mut ship_types: ResMut<ShipTypes>,
+    assets: Res<Assets<ShipDefinition>>,
+) {
+    if events.iter().count() == 0 {
+        return;
+    }
+
+    let mut definitions = HashMap::new();
+    for (_, definition) in assets.iter() {
+        if definitions
+            .insert(definition.name.clone(), definition.clone())
+            .is_some()
+        {
+            println!("Ship type {} is defined more than once.", definition.name);
+        }
+    }
+    println!("Loaded {} ship types.", definitions.len());
+    ship_types.definitions = definitions;
+}
@@ -0,0 +1,450 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    f32::consts::FRAC_1_SQRT_2,
+};
+
+use bevy::prelude::{Quat, Resource, Vec3};
+use bincode::Options;
+use serde::{Deserialize, Serialize};
+
+use crate::{codec::DecodeError, PlayerState, Snapshot, TranslationRotation};
+
+/// How many sent snapshots are kept around to be used as delta baselines.
+const SNAPSHOT_HISTORY: usize = 32;
+
+const ROTATION_BITS: u32 = 10;
+const ROTATION_MAX: f32 = ((1 << ROTATION_BITS) - 1) as f32;
+
+/// A transform with the position rounded to a fixed precision and the rotation packed
+/// into 32 bits.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct QuantizedTransform {
+    pub position: [i32; 3],
+    pub rotation: u32,
+}
+
+impl QuantizedTransform {
+    pub fn new(translation_rotation: &TranslationRotation, precision: f32) -> Self {
+        let position = (translation_rotation.translation / precision).round();
+        Self {
+            position: [position.x as i32, position.y as i32, position.z as i32],
+            rotation: compress_rotation(translation_rotation.rotation),
+        }
+    }
+
+    pub fn to_translation_rotation(&self, precision: f32) -> TranslationRotation {
+        TranslationRotation {
+            translation: Vec3::new(
+                self.position[0] as f32,
+                self.position[1] as f32,
+                self.position[2] as f32,
+            ) * precision,
+            rotation: decompress_rotation(self.rotation),
+        }
+    }
+}
+
+/// Packs a unit quaternion as "smallest three": the index of the largest component in
+/// the top two bits, followed by the other three components at 10 bits each.
+pub fn compress_rotation(rotation: Quat) -> u32 {
+    let mut components = rotation.normalize().to_array();
+    let largest = (0..4)
+        .max_by(|a, b| components[*a].abs().total_cmp(&components[*b].abs()))
+        .unwrap();
+
+    // q and -q are the same rotation, so the dropped component can always be positive.
+    if components[largest] < 0.0 {
+        components = components.map(|component| -component);
+    }
+
+    let mut packed = (largest as u32) << (ROTATION_BITS * 3);
+    let mut shift = ROTATION_BITS * 2;
+    for (index, component) in components.iter().enumerate() {
+        if index == largest {
+            continue;
+        }
+        let normalized = (component / FRAC_1_SQRT_2 * 0.5 + 0.5).clamp(0.0, 1.0);
+        packed |= ((normalized * ROTATION_MAX).round() as u32) << shift;
+        shift = shift.saturating_sub(ROTATION_BITS);
+    }
+    packed
+}
+
+pub fn decompress_rotation(packed: u32) -> Quat {
+    let largest = (packed >> (ROTATION_BITS * 3)) as usize;
+    let mut components = [0.0; 4];
+    let mut shift = ROTATION_BITS * 2;
+    let mut sum_squared = 0.0;
+    for (index, component) in components.iter_mut().enumerate() {
+        if index == largest {
+            continue;
+        }
+        let value = ((packed >> shift) & ROTATION_MAX as u32) as f32 / ROTATION_MAX;
+        *component = (value - 0.5) * 2.0 * FRAC_1_SQRT_2;
+        sum_squared += *component * *component;
+        shift = shift.saturating_sub(ROTATION_BITS);
+    }
+    components[largest] = (1.0 - sum_squared).max(0.0).sqrt();
+    Quat::from_array(components).normalize()
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub enum EntityUpdate {
+    /// Same as in the baseline.
+    Unchanged { id: u64 },
+    /// Position relative to the baseline, rotation only if it changed.
+    Delta {
+        id: u64,
+        position: [i32; 3],
+        rotation: Option<u32>,
+    },
+    /// Not in the baseline.
+    Full {
+        id: u64,
+        transform: QuantizedTransform,
+    },
+}
+
+/// A [`Snapshot`] as it is sent over the unreliable channel.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SnapshotPacket {
+    pub tick: u64,
+    /// Tick of the snapshot the entity updates are relative to.
+    pub baseline: Option<u64>,
+    pub precision: f32,
+    pub entities: Vec<EntityUpdate>,
+    pub players: HashMap<u64, PlayerState>,
+}
+
+type QuantizedState = HashMap<u64, QuantizedTransform>;
+
+/// Snapshots never come close to this, anything larger is garbage.
+const MAX_PACKET_BYTES: u64 = 64 * 1024;
+
+fn serialize_options() -> impl Options {
+    bincode::DefaultOptions::new()
+        .with_varint_encoding()
+        .with_limit(MAX_PACKET_BYTES)
+}
+
+/// Encodes snapshots for one client, relative to the last snapshot it acknowledged.
+pub struct SnapshotEncoder {
+    pub precision: f32,
+    history: VecDeque<(u64, QuantizedState)>,
+    acked: Option<u64>,
+}
+
+impl SnapshotEncoder {
+    pub fn new(precision: f32) -> Self {
+        Self {
+            precision,
+            history: VecDeque::new(),
+            acked: None,
+        }
+    }
+
+    pub fn acknowledge(&mut self, tick: u64) {
+        if self.acked.is_none_or(|acked| tick > acked) {
+            self.acked = Some(tick);
+        }
+    }
+
+    pub fn encode(&mut self, snapshot: &Snapshot) -> Vec<u8> {
+        let baseline = self
+            .acked
+            .and_then(|acked| self.history.iter().find(|(tick, _)| *tick == acked));
+
+        // Only what this snapshot holds, entities that are gone or weren't picked this
+        // time are sent in full when they come back.
+        let mut state = QuantizedState::with_capacity(snapshot.entities.len());
+        let mut entities = Vec::with_capacity(snapshot.entities.len());
+
+        for (id, translation_rotation) in snapshot.entities.iter() {
+            let transform = QuantizedTransform::new(translation_rotation, self.precision);
+            let update = match baseline.and_then(|(_, state)| state.get(id)) {
+                Some(previous) if *previous == transform => EntityUpdate::Unchanged { id: *id },
+                Some(previous) => EntityUpdate::Delta {
+                    id: *id,
+                    position: [
+                        transform.position[0].wrapping_sub(previous.position[0]),
+                        transform.position[1].wrapping_sub(previous.position[1]),
+                        transform.position[2].wrapping_sub(previous.position[2]),
+                    ],
+                    rotation: (previous.rotation != transform.rotation)
+                        .then_some(transform.rotation),
+                },
+                None => EntityUpdate::Full { id: *id, transform },
+            };
+            entities.push(update);
+            state.insert(*id, transform);
+        }
+
+        let packet = SnapshotPacket {
+            tick: snapshot.tick,
+            baseline: baseline.map(|(tick, _)| *tick),
+            precision: self.precision,
+            entities,
+            players: snapshot.players.clone(),
+        };
+
+        self.history.push_back((snapshot.tick, state));
+        while self.history.len() > SNAPSHOT_HISTORY {
+            self.history.pop_front();
+        }
+
+        serialize_options().serialize(&packet).unwrap()
+    }
+}
+
+/// Client side counterpart of [`SnapshotEncoder`].
+#[derive(Resource, Default)]
+pub struct SnapshotDecoder {
+    history: VecDeque<(u64, QuantizedState)>,
+}
+
+impl SnapshotDecoder {
+    /// Returns the entities that were included in the packet. Fails if the packet is
+    /// malformed or its baseline is no longer known.
+    pub fn decode(&mut self, bytes: &[u8]) -> Result<Snapshot, DecodeError> {
+        let packet: SnapshotPacket = serialize_options().deserialize(bytes)?;
+
+        let baseline = match packet.baseline {
+            Some(baseline) => Some(
+                self.history
+                    .iter()
+                    .find(|(tick, _)| *tick == baseline)
+                    .map(|(_, state)| state)
+                    .ok_or(DecodeError::UnknownBaseline { tick: baseline })?,
+            ),
+            None => None,
+        };
+        let baseline_entity = |id: u64| {
+            baseline
+                .and_then(|state| state.get(&id))
+                .copied()
+                .ok_or(DecodeError::MissingBaselineEntity { id })
+        };
+
+        let mut state = QuantizedState::with_capacity(packet.entities.len());
+        let mut snapshot = Snapshot {
+            tick: packet.tick,
+            entities: HashMap::with_capacity(packet.entities.len()),
+            players: packet.players,
+        };
+
+        for update in packet.entities {
+            let (id, transform) = match update {
+                EntityUpdate::Unchanged { id } => (id, baseline_entity(id)?),
+                EntityUpdate::Delta {
+                    id,
+                    position,
+                    rotation,
+                } => {
+                    let previous = baseline_entity(id)?;
+                    (
+                        id,
+                        QuantizedTransform {
+                            position: [
+                                previous.position[0].wrapping_add(position[0]),
+                                previous.position[1].wrapping_add(position[1]),
+                                previous.position[2].wrapping_add(position[2]),
+                            ],
+                            rotation: rotation.unwrap_or(previous.rotation),
+                        },
+                    )
+                }
+                EntityUpdate::Full { id, transform } => (id, transform),
+            };
+            state.insert(id, transform);
+            snapshot
+                .entities
+                .insert(id, transform.to_translation_rotation(packet.precision));
+        }
+
+        self.history.push_back((packet.tick, state));
+        while self.history.len() > SNAPSHOT_HISTORY {
+            self.history.pop_front();
+        }
+
+        Ok(snapshot)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PRECISION: f32 = 0.01;
+
+    fn snapshot(tick: u64, entities: &[(u64, Vec3, Quat)]) -> Snapshot {
+        Snapshot {
+            tick,
+            entities: entities
+                .iter()
+                .map(|(id, translation, rotation)| {
+                    (
+                        *id,
+                        TranslationRotation {
+                            translation: *translation,
+                            rotation: *rotation,
+                        },
+                    )
+                })
+                .collect(),
+            players: HashMap::new(),
+        }
+    }
+
+    fn packet(bytes: &[u8]) -> SnapshotPacket {
+        serialize_options().deserialize(bytes).unwrap()
+    }
+
+    fn update_for(packet: &SnapshotPacket, id: u64) -> &EntityUpdate {
+        packet
+            .entities
+            .iter()
+            .find(|update| match update {
+                EntityUpdate::Unchanged { id: other }
+                | EntityUpdate::Delta { id: other, .. }
+                | EntityUpdate::Full { id: other, .. } => *other == id,
+            })
+            .unwrap()
+    }
+
+    #[test]
+    fn quantized_position_is_within_half_the_precision() {
+        let translation = Vec3::new(12.3456, -0.004, 1000.0049);
+        let quantized = QuantizedTransform::new(
+            &TranslationRotation {
+                translation,
+                rotation: Quat::IDENTITY,
+            },
+            PRECISION,
+        );
+        let restored = quantized.to_translation_rotation(PRECISION).translation;
+        assert!((restored - translation).abs().max_element() <= PRECISION * 0.5 + 1e-4);
+    }
+
+    #[test]
+    fn rotation_survives_compression() {
+        let rotations = [
+            Quat::IDENTITY,
+            Quat::from_rotation_x(0.5),
+            Quat::from_rotation_y(-2.0),
+            Quat::from_euler(bevy::math::EulerRot::XYZ, 1.0, -0.3, 2.5),
+            // The largest component is negative, so it gets flipped.
+            Quat::from_xyzw(0.1, -0.2, 0.3, -0.9).normalize(),
+        ];
+        for rotation in rotations {
+            let restored = decompress_rotation(compress_rotation(rotation));
+            // q and -q are the same rotation.
+            assert!(
+                restored.dot(rotation).abs() > 0.9999,
+                "{:?} came back as {:?}",
+                rotation,
+                restored
+            );
+        }
+    }
+
+    #[test]
+    fn first_snapshot_is_sent_in_full() {
+        let mut encoder = SnapshotEncoder::new(PRECISION);
+        let mut decoder = SnapshotDecoder::default();
+
+        let bytes = encoder.encode(&snapshot(1, &[(7, Vec3::X, Quat::IDENTITY)]));
+        let sent = packet(&bytes);
+        assert_eq!(sent.baseline, None);
+        assert!(matches!(update_for(&sent, 7), EntityUpdate::Full { .. }));
+
+        let decoded = decoder.decode(&bytes).unwrap();
+        assert!((decoded.entities[&7].translation - Vec3::X).length() < PRECISION);
+    }
+
+    #[test]
+    fn acked_snapshot_is_the_baseline_for_deltas() {
+        let mut encoder = SnapshotEncoder::new(PRECISION);
+        let mut decoder = SnapshotDecoder::default();
+        let moved = Vec3::new(1.0, 2.0, 3.0);
+        let turned = Quat::from_rotation_y(1.0);
+
+        let first = encoder.encode(&snapshot(
+            1,
+            &[(1, Vec3::ZERO, Quat::IDENTITY), (2, Vec3::ZERO, Quat::IDENTITY)],
+        ));
+        decoder.decode(&first).unwrap();
+        encoder.acknowledge(1);
+
+        let second = encoder.encode(&snapshot(
+            2,
+            &[
+                (1, Vec3::ZERO, Quat::IDENTITY),
+                (2, moved, turned),
+                (3, Vec3::Y, Quat::IDENTITY),
+            ],
+        ));
+        let sent = packet(&second);
+        assert_eq!(sent.baseline, Some(1));
+        assert!(matches!(update_for(&sent, 1), EntityUpdate::Unchanged { .. }));
+        assert!(matches!(
+            update_for(&sent, 2),
+            EntityUpdate::Delta {
+                rotation: Some(_),
+                ..
+            }
+        ));
+        assert!(matches!(update_for(&sent, 3), EntityUpdate::Full { .. }));
+
+        let decoded = decoder.decode(&second).unwrap();
+        assert_eq!(decoded.entities.len(), 3);
+        assert!(decoded.entities[&1].translation.length() < PRECISION);
+        assert!((decoded.entities[&2].translation - moved).length() < PRECISION);
+        assert!(decoded.entities[&2].rotation.dot(turned).abs() > 0.9999);
+        assert!((decoded.entities[&3].translation - Vec3::Y).length() < PRECISION);
+    }
+
+    #[test]
+    fn entities_missing_from_a_snapshot_leave_the_baseline() {
+        let mut encoder = SnapshotEncoder::new(PRECISION);
+        let mut decoder = SnapshotDecoder::default();
+
+        let first = encoder.encode(&snapshot(
+            1,
+            &[(1, Vec3::ZERO, Quat::IDENTITY), (2, Vec3::X, Quat::IDENTITY)],
+        ));
+        decoder.decode(&first).unwrap();
+        encoder.acknowledge(1);
+
+        let second = encoder.encode(&snapshot(2, &[(1, Vec3::ZERO, Quat::IDENTITY)]));
+        decoder.decode(&second).unwrap();
+        encoder.acknowledge(2);
+        assert_eq!(encoder.history.back().unwrap().1.len(), 1);
+        assert_eq!(decoder.history.back().unwrap().1.len(), 1);
+
+        // Coming back it can't be a delta against a baseline that dropped it.
+        let third = encoder.encode(&snapshot(
+            3,
+            &[(1, Vec3::ZERO, Quat::IDENTITY), (2, Vec3::X, Quat::IDENTITY)],
+        ));
+        assert!(matches!(
+            update_for(&packet(&third), 2),
+            EntityUpdate::Full { .. }
+        ));
+        let decoded = decoder.decode(&third).unwrap();
+        assert!((decoded.entities[&2].translation - Vec3::X).length() < PRECISION);
+    }
+
+    #[test]
+    fn unknown_baseline_is_an_error() {
+        let mut encoder = SnapshotEncoder::new(PRECISION);
+        encoder.encode(&snapshot(1, &[(1, Vec3::ZERO, Quat::IDENTITY)]));
+        encoder.acknowledge(1);
+        let bytes = encoder.encode(&snapshot(2, &[(1, Vec3::ZERO, Quat::IDENTITY)]));
+
+        let mut decoder = SnapshotDecoder::default();
+        assert!(matches!(
+            decoder.decode(&bytes),
+            Err(DecodeError::UnknownBaseline { tick: 1 })
+        ));
+    }
+}
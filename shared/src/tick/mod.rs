@@ -0,0 +1,73 @@
+use bevy::{
+    ecs::schedule::ShouldRun,
+    prelude::{Res, ResMut, Resource},
+    time::Time,
+};
+
+/// Longest stretch of frame time that is turned into ticks in one go, so a long stall
+/// doesn't make the simulation try to catch up forever.
+const MAX_ACCUMULATED_SECONDS: f32 = 0.25;
+
+/// Monotonic fixed-rate simulation clock.
+///
+/// Systems running under [`run_on_tick`] see `tick` already advanced to the tick they
+/// are simulating.
+#[derive(Resource, Debug)]
+pub struct NetworkTick {
+    pub tick: u64,
+    pub rate: f32,
+    /// The tick the rate last changed on, and its time in seconds. Times of later ticks
+    /// are counted from there, so changing the rate doesn't move past ticks in time.
+    pub epoch_tick: u64,
+    pub epoch_seconds: f64,
+    accumulator: f32,
+    looping: bool,
+}
+
+impl NetworkTick {
+    pub fn new(rate: f32) -> Self {
+        Self {
+            tick: 0,
+            rate,
+            epoch_tick: 0,
+            epoch_seconds: 0.0,
+            accumulator: 0.0,
+            looping: false,
+        }
+    }
+
+    /// Length of a single tick in seconds.
+    pub fn delta_seconds(&self) -> f32 {
+        1.0 / self.rate
+    }
+
+    /// Simulation time at the start of `tick`.
+    pub fn tick_to_seconds(&self, tick: u64) -> f64 {
+        self.epoch_seconds + (tick as f64 - self.epoch_tick as f64) / self.rate as f64
+    }
+
+    /// Changes the rate from the current tick on.
+    pub fn set_rate(&mut self, rate: f32) {
+        self.epoch_seconds = self.tick_to_seconds(self.tick);
+        self.epoch_tick = self.tick;
+        self.rate = rate;
+    }
+}
+
+/// Run criteria that runs once for every tick that has elapsed since the last frame.
+pub fn run_on_tick(time: Res<Time>, mut tick: ResMut<NetworkTick>) -> ShouldRun {
+    if !tick.looping {
+        tick.accumulator = (tick.accumulator + time.delta_seconds()).min(MAX_ACCUMULATED_SECONDS);
+    }
+
+    let step = tick.delta_seconds();
+    if tick.accumulator >= step {
+        tick.accumulator -= step;
+        tick.tick += 1;
+        tick.looping = true;
+        ShouldRun::YesAndCheckAgain
+    } else {
+        tick.looping = false;
+        ShouldRun::No
+    }
+}
@@ -1,31 +1,43 @@
 use bevy::{
-    prelude::{
-        App, Bundle, Commands, Component, Entity, Plugin, Query, Res, ResMut, SystemSet, Transform,
-    },
+    prelude::{App, Bundle, Commands, Component, Entity, Plugin, Query, Res, SystemSet, Transform},
     time::Time,
 };
 use bevy_rapier3d::prelude::{ActiveEvents, Collider, Sensor};
-use bevy_renet::renet::{DefaultChannel, RenetServer};
+use serde::{Deserialize, Serialize};
 
-use crate::{run_if_server, NetworkedId, ServerMessages};
+use crate::{
+    replication::{Replicated, ReplicationAppExt},
+    run_if_server,
+    tick::NetworkTick,
+};
+
+/// Health a bullet takes off whatever it hits.
+pub const BULLET_DAMAGE: f32 = 1.0;
 
 pub struct BulletPlugin;
 
 impl Plugin for BulletPlugin {
     fn build(&self, app: &mut App) {
-        app.add_system(bullet_mover).add_system_set(
-            SystemSet::new()
-                .with_run_criteria(run_if_server)
-                .with_system(bullet_remover),
-        );
+        app.replicate::<Bullet>()
+            .add_system(bullet_mover)
+            .add_system_set(
+                SystemSet::new()
+                    .with_run_criteria(run_if_server)
+                    .with_system(bullet_remover),
+            );
     }
 }
-#[derive(Component, Clone, Copy)]
+#[derive(Component, Clone, Copy, Serialize, Deserialize)]
 pub struct Bullet {
     pub speed: f32,
+    /// Server tick time in seconds the bullet is removed at.
     pub lifetime: f32,
 }
 
+impl Replicated for Bullet {
+    const NAME: &'static str = "bullet";
+}
+
 #[derive(Bundle)]
 pub struct BulletBundle {
     pub bullet: Bullet,
@@ -55,21 +67,11 @@ fn bullet_mover(
     }
 }
 
-fn bullet_remover(
-    mut commands: Commands,
-    mut query: Query<(Entity, &Bullet, &NetworkedId)>,
-    mut server: ResMut<RenetServer>,
-    time: Res<Time>,
-) {
-    for (entity, bullet, networked_id) in query.iter_mut() {
-        if time.elapsed_seconds() > bullet.lifetime {
+fn bullet_remover(mut commands: Commands, query: Query<(Entity, &Bullet)>, tick: Res<NetworkTick>) {
+    let now = tick.tick_to_seconds(tick.tick) as f32;
+    for (entity, bullet) in query.iter() {
+        if now > bullet.lifetime {
             commands.entity(entity).despawn();
-
-            let message = bincode::serialize(&ServerMessages::EntityDespawn {
-                id: networked_id.id,
-            })
-            .unwrap();
-            server.broadcast_message(DefaultChannel::Reliable, message);
         }
     }
 }
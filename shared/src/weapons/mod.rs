@@ -1,24 +1,27 @@
 pub mod bullet;
 
-use std::{f32::consts::PI, time::Instant};
+use std::f32::consts::PI;
 
 use bevy::{
     prelude::{
-        default, shape, App, Assets, Color, Commands, Component, Entity, EventReader,
-        GlobalTransform, IntoSystemDescriptor, Mesh, Parent, PbrBundle, Plugin, Quat, Query, Res,
-        ResMut, StandardMaterial, SystemSet, Transform, With, Without,
+        default, shape, Added, App, Assets, BuildChildren, Color, Commands, Component, Entity,
+        GlobalTransform, Mesh, Parent, PbrBundle, Plugin, Quat, Query, Res, ResMut,
+        StandardMaterial, SystemSet, Transform, With, Without,
     },
     time::Time,
     transform::TransformBundle,
 };
-use bevy_renet::renet::{DefaultChannel, RenetServer};
 
 use crate::{
+    health::Health,
+    lag_compensation::{ColliderHistory, LagCompensationSettings},
     player::{player_input::PlayerInput, Player},
-    run_if_client, run_if_server, Lobby, NetworkedId, ServerMessages,
+    run_if_client,
+    tick::NetworkTick,
+    NetworkContext, NetworkIdProvider,
 };
 
-use self::bullet::{Bullet, BulletBundle, BulletPlugin};
+use self::bullet::{Bullet, BulletBundle, BulletPlugin, BULLET_DAMAGE};
 
 #[derive(Component, Debug, Eq, PartialEq)]
 pub struct TurretOwner(pub(crate) Entity);
@@ -56,16 +59,12 @@ pub struct WeaponsPlugin;
 impl Plugin for WeaponsPlugin {
     fn build(&self, app: &mut App) {
         app.add_plugin(BulletPlugin {})
-            .add_system(trigger_weapons)
-            .add_system(turn_turrets)
-            .add_system_set(
-                SystemSet::new()
-                    .with_run_criteria(run_if_server)
-                    .with_system(fire_weapons_server.after(trigger_weapons)),
-            )
+            // The server runs these on its tick instead, it adds them itself.
             .add_system_set(
                 SystemSet::new()
                     .with_run_criteria(run_if_client)
+                    .with_system(trigger_weapons)
+                    .with_system(turn_turrets)
                     .with_system(on_bullet_spawned_client),
             );
     }
@@ -74,23 +73,21 @@ impl Plugin for WeaponsPlugin {
 #[derive(Component)]
 pub struct Barrel {}
 
-fn trigger_weapons(
+pub fn trigger_weapons(
     mut q_child: Query<(&Parent, &mut Turret)>,
     q_parent: Query<(&Player, &PlayerInput)>,
 ) {
     for (parent, mut turret) in q_child.iter_mut() {
-        let result = q_parent.get(parent.get());
-        match result {
-            Ok((_player, player_input)) => {
-                turret.trigger = player_input.primary_fire;
-            }
-            Err(_) => {}
+        if let Ok((_player, player_input)) = q_parent.get(parent.get()) {
+            turret.trigger = player_input.primary_fire;
         }
     }
 }
 
-fn turn_turrets(
+pub fn turn_turrets(
     time: Res<Time>,
+    tick: Res<NetworkTick>,
+    context: Res<NetworkContext>,
     mut turret_query: Query<
         (&TurretOwner, &mut Turret, &mut Transform, &GlobalTransform),
         Without<Barrel>,
@@ -98,6 +95,12 @@ fn turn_turrets(
     mut barrel_query: Query<(&Parent, &mut Transform, &GlobalTransform), With<Barrel>>,
     player_query: Query<&PlayerInput>,
 ) {
+    // The server turns them once per tick, clients every frame.
+    let delta = match context.is_server {
+        true => tick.delta_seconds(),
+        false => time.delta_seconds(),
+    };
+
     for (owner, _, mut transform, global_transform) in turret_query.iter_mut() {
         let player = player_query.get(owner.get());
 
@@ -105,7 +108,7 @@ fn turn_turrets(
             Ok(player_input) => {
                 let direction =
                     (global_transform.translation() - player_input.aim_point).normalize_or_zero();
-                let off_by = global_transform.right().dot(direction) * time.delta_seconds() * 10.0;
+                let off_by = global_transform.right().dot(direction) * delta * 10.0;
                 transform.rotate_local_y(off_by);
             }
             Err(x) => println!("Turret has not player parent: {}", x),
@@ -114,109 +117,115 @@ fn turn_turrets(
 
     for (parent, mut transform, global_transform) in barrel_query.iter_mut() {
         let (turret_parent, _, _, _) = turret_query.get(parent.get()).unwrap();
-        let player = player_query.get(turret_parent.get());
-
-        match player {
-            Ok(player_input) => {
-                let direction =
-                    (global_transform.translation() - player_input.aim_point).normalize_or_zero();
-                let off_by = global_transform.down().dot(direction) * time.delta_seconds() * 10.0;
-                transform.rotate_local_x(off_by);
-            }
-            Err(_) => {}
+        if let Ok(player_input) = player_query.get(turret_parent.get()) {
+            let direction =
+                (global_transform.translation() - player_input.aim_point).normalize_or_zero();
+            let off_by = global_transform.down().dot(direction) * delta * 10.0;
+            transform.rotate_local_x(off_by);
         }
     }
 }
 
-fn fire_weapons_server(
+/// Bullets are fired into the world as the shooter saw it. The stretch of their flight
+/// the shooter's latency accounts for is resolved against the rewound colliders, after
+/// which they carry on from where they would be by now.
+#[allow(clippy::too_many_arguments)]
+pub fn fire_weapons_server(
     mut barrel_query: Query<(&Barrel, &GlobalTransform, &Parent)>,
-    mut turret_query: Query<&mut Turret>,
+    mut turret_query: Query<(&mut Turret, &TurretOwner)>,
+    input_query: Query<&PlayerInput>,
+    mut health_query: Query<&mut Health>,
+    history: Res<ColliderHistory>,
+    lag_compensation: Res<LagCompensationSettings>,
+    tick: Res<NetworkTick>,
+    mut id_provider: ResMut<NetworkIdProvider>,
     mut commands: Commands,
-    time: Res<Time>,
-    mut server: ResMut<RenetServer>,
 ) {
+    let now = tick.tick_to_seconds(tick.tick);
+
     for (_, global_transform, parent) in barrel_query.iter_mut() {
-        let mut turret = turret_query.get_mut(parent.get()).unwrap();
+        let (mut turret, owner) = turret_query.get_mut(parent.get()).unwrap();
         if turret.cooldown <= 0.0 {
             if turret.trigger {
-                let transform = global_transform.compute_transform();
-
-                let now = Instant::now();
-                let since_start = now.duration_since(time.startup());
-                let id = since_start.as_nanos();
-
-                let bullet_transform = TransformBundle::from_transform(transform);
+                let mut transform = global_transform.compute_transform();
                 let bullet = Bullet {
                     speed: 200.,
-                    lifetime: time.elapsed_seconds() + 2.0,
+                    lifetime: now as f32 + 2.0,
                 };
-                commands
-                    .spawn(bullet_transform)
-                    .insert(BulletBundle::new(bullet))
-                    .insert(NetworkedId {
-                        id: id.try_into().unwrap(),
-                        last_sent: 0,
-                    });
-
-                let message = bincode::serialize(&ServerMessages::BulletSpawned {
-                    id: id.try_into().unwrap(),
-                    position: transform.translation,
-                    rotation: transform.rotation,
-                })
-                .unwrap();
-
-                server.broadcast_message(DefaultChannel::Reliable, message);
+
+                let rewind_time = input_query.get(owner.get()).map_or(now, |input| {
+                    lag_compensation.rewind_time(input.view_time, now)
+                });
+                let rewind = (now - rewind_time) as f32;
+
+                let hit = history.cast_ray(
+                    rewind_time,
+                    transform.translation,
+                    transform.forward(),
+                    bullet.speed * rewind,
+                    Some(owner.get()),
+                );
+                match hit {
+                    Some((entity, distance)) => {
+                        if let Ok(mut health) = health_query.get_mut(entity) {
+                            health.health -= BULLET_DAMAGE;
+                        }
+
+                        // Without a collider, so the others see the shot without it
+                        // hitting twice. It flies from the barrel until the hit.
+                        commands
+                            .spawn(TransformBundle::from_transform(transform))
+                            .insert(Bullet {
+                                lifetime: now as f32 + distance / bullet.speed,
+                                ..bullet
+                            })
+                            .insert(id_provider.new_id());
+                    }
+                    None => {
+                        transform.translation += transform.forward() * bullet.speed * rewind;
+
+                        commands
+                            .spawn(TransformBundle::from_transform(transform))
+                            .insert(BulletBundle::new(Bullet {
+                                lifetime: bullet.lifetime - rewind,
+                                ..bullet
+                            }))
+                            .insert(id_provider.new_id());
+                    }
+                }
             }
             turret.cooldown = turret.fire_rate;
         } else {
-            turret.cooldown -= time.delta_seconds();
+            turret.cooldown -= tick.delta_seconds();
         }
     }
 }
 
 fn on_bullet_spawned_client(
     mut commands: Commands,
-    mut lobby: ResMut<Lobby>,
-    mut event_reader: EventReader<ServerMessages>,
+    query: Query<Entity, Added<Bullet>>,
     mut meshes: ResMut<Assets<Mesh>>,
     mut materials: ResMut<Assets<StandardMaterial>>,
 ) {
-    for event in event_reader.iter() {
-        match event {
-            ServerMessages::BulletSpawned {
-                id,
-                position,
-                rotation,
-            } => {
-                let entity_id = commands
-                    .spawn(PbrBundle {
-                        mesh: meshes.add(Mesh::from(shape::Capsule {
-                            depth: 0.5,
-                            radius: 0.1,
-                            ..Default::default()
-                        })),
-                        material: materials.add(StandardMaterial {
-                            base_color: Color::BLACK,
-                            perceptual_roughness: 1.,
-                            emissive: Color::rgb(1., 0.2, 0.2) * 5.,
-                            ..default()
-                        }),
-                        transform: Transform {
-                            translation: *position,
-                            rotation: *rotation * Quat::from_rotation_x(PI / 2.0),
-                            ..Default::default()
-                        },
-                        ..Default::default()
-                    })
-                    .insert(Bullet {
-                        lifetime: 0.0,
-                        speed: 0.0,
-                    })
-                    .id();
-
-                lobby.networked_entities.insert(*id, entity_id);
-            }
-            _ => {}
-        }
+    for entity in query.iter() {
+        let model = commands
+            .spawn(PbrBundle {
+                mesh: meshes.add(Mesh::from(shape::Capsule {
+                    depth: 0.5,
+                    radius: 0.1,
+                    ..Default::default()
+                })),
+                material: materials.add(StandardMaterial {
+                    base_color: Color::BLACK,
+                    perceptual_roughness: 1.,
+                    emissive: Color::rgb(1., 0.2, 0.2) * 5.,
+                    ..default()
+                }),
+                transform: Transform::from_rotation(Quat::from_rotation_x(PI / 2.0)),
+                ..Default::default()
+            })
+            .id();
+
+        commands.entity(entity).add_child(model);
     }
 }